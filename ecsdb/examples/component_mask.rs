@@ -0,0 +1,51 @@
+use ecsdb::{Component, EntityId, query::With};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Component)]
+struct A;
+#[derive(Debug, Serialize, Deserialize, Component)]
+struct B;
+#[derive(Debug, Serialize, Deserialize, Component)]
+struct C;
+#[derive(Debug, Serialize, Deserialize, Component)]
+struct N(u64);
+
+/// Compares `With<(A, B, C)>` via subqueries against the component mask on
+/// 100k entities
+pub fn main() -> Result<(), anyhow::Error> {
+    let mut db = ecsdb::Ecs::open_in_memory()?;
+
+    db.raw_sql().execute_batch("begin")?;
+    for n in 0..100_000 {
+        let e = db.new_entity().attach(N(n));
+        if n % 2 == 0 {
+            e.attach(A);
+        }
+        if n % 3 == 0 {
+            e.attach(B);
+        }
+        if n % 5 == 0 {
+            e.attach(C);
+        }
+    }
+    db.raw_sql().execute_batch("commit")?;
+
+    let run = |db: &ecsdb::Ecs| {
+        let start = std::time::Instant::now();
+        let mut matches = 0;
+        for _ in 0..10 {
+            matches = db.query::<EntityId, With<(A, B, C)>>().count();
+        }
+        (matches, start.elapsed().as_millis() / 10)
+    };
+
+    let (matches, elapsed) = run(&db);
+    println!("Subqueries: {matches} matches in {elapsed}ms");
+
+    db.try_enable_component_mask::<(A, B, C)>()?;
+
+    let (matches, elapsed) = run(&db);
+    println!("Mask: {matches} matches in {elapsed}ms");
+
+    Ok(())
+}
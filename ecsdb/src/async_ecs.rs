@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use crate::{
+    Component, Ecs, EntityId, Error,
+    query::{Query, QueryFilter, QueryFilterValue},
+};
+
+type Job = Box<dyn FnOnce(&mut Ecs) + Send>;
+
+/// Async wrapper around an [`Ecs`] for use from async code. The [`Ecs`] lives
+/// on a dedicated background thread running one call at a time, so hooks,
+/// component aliases etc. behave as with the sync API. Since entities can't
+/// borrow across `.await`, results are returned as owned values.
+#[derive(Clone)]
+pub struct AsyncEcs {
+    jobs: std::sync::mpsc::Sender<Job>,
+}
+
+impl AsyncEcs {
+    pub async fn open_in_memory() -> Result<Self, Error> {
+        Self::from_ecs(Ecs::open_in_memory()?).await
+    }
+
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_ecs(Ecs::open(path)?).await
+    }
+
+    /// Moves `ecs` to the background thread. The thread stops once every
+    /// clone of the returned [`AsyncEcs`] got dropped.
+    pub async fn from_ecs(ecs: Ecs) -> Result<Self, Error> {
+        let (jobs, rx) = std::sync::mpsc::channel::<Job>();
+        std::thread::Builder::new()
+            .name("ecsdb".into())
+            .spawn(move || {
+                let mut ecs = ecs;
+                for job in rx {
+                    job(&mut ecs);
+                }
+            })?;
+
+        Ok(Self { jobs })
+    }
+
+    /// Runs `f` with the [`Ecs`] on the background thread
+    pub async fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Ecs) -> Result<R, Error> + Send + 'static,
+    ) -> Result<R, Error> {
+        let stopped = || Error::AsyncConnection("background thread stopped".into());
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.jobs
+            .send(Box::new(move |ecs| {
+                let _ = tx.send(f(ecs));
+            }))
+            .map_err(|_| stopped())?;
+
+        rx.await.map_err(|_| stopped())?
+    }
+}
+
+impl AsyncEcs {
+    /// All entities with component `C` matching filter `F`, together with
+    /// their `C`. Fails if a `C` can't be deserialized.
+    pub async fn query<C, F>(&self) -> Result<Vec<(EntityId, C)>, Error>
+    where
+        C: Component + Send + 'static,
+        F: QueryFilter,
+    {
+        self.call(|ecs| {
+            Query::<(EntityId, C), F>::new(ecs)
+                .strict()
+                .try_collect_owned()
+        })
+        .await
+    }
+
+    /// Ids of all entities matching `filter`. See [`Ecs::find`].
+    pub async fn find<V>(&self, filter: V) -> Result<Vec<EntityId>, Error>
+    where
+        V: QueryFilterValue + Send + 'static,
+    {
+        self.call(move |ecs| Ok(ecs.try_find(filter)?.map(|e| e.id()).collect()))
+            .await
+    }
+
+    pub async fn component<C>(&self, entity: EntityId) -> Result<Option<C>, Error>
+    where
+        C: Component + Send + 'static,
+    {
+        self.call(move |ecs| ecs.entity(entity).try_component::<C>())
+            .await
+    }
+
+    pub async fn resource<R>(&self) -> Result<Option<R>, Error>
+    where
+        R: Component + Send + 'static,
+    {
+        self.call(|ecs| ecs.try_resource::<R>()).await
+    }
+
+    pub async fn attach_resource<R>(&self, resource: R) -> Result<(), Error>
+    where
+        R: Component + Send + 'static,
+    {
+        self.call(move |ecs| ecs.try_attach_resource(resource))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::AsyncEcs;
+    use crate::query::Without;
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Score(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Hidden;
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Config(String);
+
+    #[tokio::test]
+    async fn async_query() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let a = ecs.new_entity().attach(Score(1)).id();
+        let b = ecs.new_entity().attach(Score(2)).id();
+        ecs.new_entity().attach((Score(3), Hidden));
+
+        let ecs = AsyncEcs::from_ecs(ecs).await.unwrap();
+
+        let scores = ecs.query::<Score, Without<Hidden>>().await.unwrap();
+        assert_eq!(scores, vec![(a, Score(1)), (b, Score(2))]);
+
+        assert_eq!(ecs.find(Score(2)).await.unwrap(), vec![b]);
+        assert_eq!(ecs.component::<Score>(a).await.unwrap(), Some(Score(1)));
+
+        assert_eq!(ecs.resource::<Config>().await.unwrap(), None);
+        ecs.attach_resource(Config("x".into())).await.unwrap();
+        assert_eq!(
+            ecs.resource::<Config>().await.unwrap(),
+            Some(Config("x".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn async_hooks_and_aliases() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static ATTACHED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.set_component_naming(crate::ComponentNaming::StripPrefix("ecsdb::".into()));
+        ecs.register_component::<Config>();
+        ecs.on_attach::<Config>(|_, _| {
+            ATTACHED.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let ecs = AsyncEcs::from_ecs(ecs).await.unwrap();
+        ecs.attach_resource(Config("x".into())).await.unwrap();
+        assert_eq!(ATTACHED.load(Ordering::SeqCst), 1);
+
+        // Stored under the alias, read back by name
+        let names = ecs.call(|ecs| ecs.component_names()).await.unwrap();
+        assert!(!names.iter().any(|name| name == Config::NAME));
+        assert_eq!(
+            ecs.resource::<Config>().await.unwrap(),
+            Some(Config("x".into()))
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use rusqlite::params;
+use tracing::debug;
+
+use crate::{Ecs, EntityId, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Attach,
+    Detach,
+}
+
+/// A single row of the change log
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub seq: i64,
+    pub entity: EntityId,
+    pub component: String,
+    pub op: ChangeOp,
+    /// The attached data, or the removed data for [`ChangeOp::Detach`]
+    pub data: rusqlite::types::Value,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl Ecs {
+    /// Starts recording every attach and detach of non-system components in
+    /// the `changes` table. The log is stored in the database and stays
+    /// enabled until [`Ecs::disable_change_log`] is called.
+    pub fn enable_change_log(&self) -> Result<(), Error> {
+        self.conn.execute_batch(include_str!("changelog.sql"))?;
+        debug!("change log enabled");
+        Ok(())
+    }
+
+    /// Stops recording changes. Already logged changes are kept.
+    pub fn disable_change_log(&self) -> Result<(), Error> {
+        self.conn.execute_batch(
+            r#"
+            drop trigger if exists changes_insert_trigger;
+            drop trigger if exists changes_update_trigger;
+            drop trigger if exists changes_delete_trigger;
+            "#,
+        )?;
+        debug!("change log disabled");
+        Ok(())
+    }
+
+    /// All changes with a sequence number greater than `seq`, in order.
+    pub fn changes_since(&self, seq: i64) -> Result<Vec<Change>, Error> {
+        let mut stmt = self.conn.prepare_cached(
+            r#"
+            select seq, entity, component, op, data, timestamp from changes
+            where seq > ?1
+            order by seq asc
+            "#,
+        )?;
+
+        let changes = stmt
+            .query_map(params![seq], |row| {
+                let op: String = row.get("op")?;
+                let op = match op.as_str() {
+                    "attach" => ChangeOp::Attach,
+                    "detach" => ChangeOp::Detach,
+                    other => {
+                        return Err(rusqlite::Error::FromSqlConversionFailure(
+                            3,
+                            rusqlite::types::Type::Text,
+                            format!("unknown op {other}").into(),
+                        ));
+                    }
+                };
+
+                let timestamp: String = row.get("timestamp")?;
+                let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp)
+                    .map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            5,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?
+                    .to_utc();
+
+                Ok(Change {
+                    seq: row.get("seq")?,
+                    entity: row.get("entity")?,
+                    component: row.get("component")?,
+                    op,
+                    data: row.get("data")?,
+                    timestamp,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::ChangeOp;
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[test]
+    fn change_log() {
+        let ecs = Ecs::open_in_memory().unwrap();
+
+        let untracked = ecs.new_entity().attach(A(0));
+        ecs.enable_change_log().unwrap();
+
+        let e = ecs.new_entity().attach(A(1));
+        e.attach(A(1)); // no-op
+        e.attach(A(2)).attach(B);
+        e.detach::<A>();
+        e.destroy();
+
+        let changes = ecs
+            .changes_since(0)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.entity, c.component, c.op))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            changes,
+            vec![
+                (e.id(), A::NAME.to_owned(), ChangeOp::Attach),
+                (e.id(), A::NAME.to_owned(), ChangeOp::Attach),
+                (e.id(), B::NAME.to_owned(), ChangeOp::Attach),
+                (e.id(), A::NAME.to_owned(), ChangeOp::Detach),
+                (e.id(), B::NAME.to_owned(), ChangeOp::Detach),
+            ]
+        );
+
+        let all = ecs.changes_since(0).unwrap();
+        assert_eq!(all[1].data, rusqlite::types::Value::Text("2".into()));
+        assert_eq!(ecs.changes_since(all[2].seq).unwrap().len(), 2);
+
+        ecs.disable_change_log().unwrap();
+        untracked.destroy();
+        assert_eq!(ecs.changes_since(0).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn bundle_write_order_is_stable() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        ecs.enable_change_log().unwrap();
+
+        ecs.new_entity().attach((B, A(1)));
+        ecs.new_entity().attach((A(1), B));
+        let e = ecs.new_entity().attach(A(0));
+        e.attach((B, A(2)));
+        e.detach::<(B, A)>();
+
+        let components = ecs
+            .changes_since(0)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.component)
+            .collect::<Vec<_>>();
+
+        let (a, b) = (A::NAME, B::NAME);
+        assert_eq!(components, [a, b, a, b, a, a, b, a, b]);
+    }
+}
@@ -0,0 +1,33 @@
+-- Append-only log of component changes, see `Ecs::enable_change_log`
+create table if not exists changes (
+    seq integer primary key autoincrement,
+    entity integer not null,
+    component text not null,
+    op text not null,
+    data blob,
+    timestamp text not null default (strftime ('%Y-%m-%dT%H:%M:%fZ'))
+);
+
+create trigger if not exists changes_insert_trigger
+after insert on components
+for each row when new.component not in (select component from system_components)
+begin
+    insert into changes (entity, component, op, data)
+    values (new.entity, new.component, 'attach', new.data);
+end;
+
+create trigger if not exists changes_update_trigger
+after update on components
+for each row when new.component not in (select component from system_components)
+begin
+    insert into changes (entity, component, op, data)
+    values (new.entity, new.component, 'attach', new.data);
+end;
+
+create trigger if not exists changes_delete_trigger
+after delete on components
+for each row when old.component not in (select component from system_components)
+begin
+    insert into changes (entity, component, op, data)
+    values (old.entity, old.component, 'detach', old.data);
+end;
@@ -46,8 +46,36 @@ where
 pub struct JsonStorage;
 
 #[derive(thiserror::Error, Debug)]
-#[error("Error reading/writing Component: {0}")]
-pub struct StorageError(String);
+pub enum StorageError {
+    #[error("Error reading/writing Component: {0}")]
+    Other(String),
+    #[error("Failed to deserialize {component}: {source} (data: {preview})")]
+    Deserialize {
+        component: &'static str,
+        /// Truncated offending data
+        preview: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl StorageError {
+    const PREVIEW_LENGTH: usize = 64;
+
+    fn deserialize<C: Component>(data: &[u8], source: serde_json::Error) -> Self {
+        let data = String::from_utf8_lossy(data);
+        let mut preview = data.chars().take(Self::PREVIEW_LENGTH).collect::<String>();
+        if data.chars().count() > Self::PREVIEW_LENGTH {
+            preview.push('…');
+        }
+
+        Self::Deserialize {
+            component: C::NAME,
+            preview,
+            source,
+        }
+    }
+}
 
 impl<C> ComponentRead<C> for JsonStorage
 where
@@ -57,10 +85,10 @@ where
         let s = match value {
             rusqlite::types::ToSqlOutput::Borrowed(rusqlite::types::ValueRef::Text(s)) => s,
             rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Text(s)) => s.as_bytes(),
-            other => return Err(StorageError(format!("Unexpected type {other:?}"))),
+            other => return Err(StorageError::Other(format!("Unexpected type {other:?}"))),
         };
 
-        serde_json::from_slice(s).map_err(|e| StorageError(e.to_string()))
+        serde_json::from_slice(s).map_err(|e| StorageError::deserialize::<C>(s, e))
     }
 }
 
@@ -69,7 +97,8 @@ where
     C: Component + Serialize,
 {
     fn to_rusqlite<'a>(component: &'a C) -> Result<rusqlite::types::ToSqlOutput<'a>, StorageError> {
-        let json = serde_json::to_string(&component).map_err(|e| StorageError(e.to_string()))?;
+        let json =
+            serde_json::to_string(&component).map_err(|e| StorageError::Other(e.to_string()))?;
         Ok(rusqlite::types::ToSqlOutput::Owned(
             rusqlite::types::Value::Text(json),
         ))
@@ -86,7 +115,7 @@ where
         let b = match value {
             rusqlite::types::ToSqlOutput::Borrowed(rusqlite::types::ValueRef::Blob(b)) => *b,
             rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Blob(b)) => b,
-            other => return Err(StorageError(format!("Unexpected type {other:?}"))),
+            other => return Err(StorageError::Other(format!("Unexpected type {other:?}"))),
         };
 
         Ok(C::from(b.to_vec()))
@@ -104,37 +133,100 @@ where
     }
 }
 
-// impl<C> ComponentWrite<C> for BlobStorage
-// where
-//     C: Component + Into<Vec<u8>>,
-// {
-//     fn to_rusqlite<'a>(component: &'a C) -> Result<rusqlite::types::ToSqlOutput<'a>, StorageError> {
-//         Ok(rusqlite::types::ToSqlOutput::Owned(
-//             rusqlite::types::Value::Blob(component.into().as_slice()),
-//         ))
-//     }
-// }
+/// Like [`BlobStorage`], but reads the blob into an `Arc<[u8]>`, so components
+/// holding large blobs can be cloned without copying the data.
+pub struct ArcBlobStorage;
+
+impl<C> ComponentRead<C> for ArcBlobStorage
+where
+    C: Component + From<std::sync::Arc<[u8]>>,
+{
+    fn from_rusqlite(value: &rusqlite::types::ToSqlOutput<'_>) -> Result<C, StorageError> {
+        let b = match value {
+            rusqlite::types::ToSqlOutput::Borrowed(rusqlite::types::ValueRef::Blob(b)) => *b,
+            rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Blob(b)) => b,
+            other => return Err(StorageError::Other(format!("Unexpected type {other:?}"))),
+        };
+
+        Ok(C::from(std::sync::Arc::from(b)))
+    }
+}
+
+impl<C> ComponentWrite<C> for ArcBlobStorage
+where
+    C: Component + AsRef<[u8]>,
+{
+    fn to_rusqlite<'a>(component: &'a C) -> Result<rusqlite::types::ToSqlOutput<'a>, StorageError> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Blob(component.as_ref()),
+        ))
+    }
+}
+
+/// Produces the bytes stored by [`OwnedBlobStorage`]
+pub trait ToBlob {
+    fn to_blob(&self) -> Vec<u8>;
+}
+
+impl<T: Clone + Into<Vec<u8>>> ToBlob for T {
+    fn to_blob(&self) -> Vec<u8> {
+        self.clone().into()
+    }
+}
+
+/// Like [`BlobStorage`], for components that can only produce an owned byte
+/// vector, e.g. compressed data. Writes via [`ToBlob`].
+pub struct OwnedBlobStorage;
 
+impl<C> ComponentRead<C> for OwnedBlobStorage
+where
+    C: Component + From<Vec<u8>>,
+{
+    fn from_rusqlite(value: &rusqlite::types::ToSqlOutput<'_>) -> Result<C, StorageError> {
+        <BlobStorage as ComponentRead<C>>::from_rusqlite(value)
+    }
+}
+
+impl<C> ComponentWrite<C> for OwnedBlobStorage
+where
+    C: Component + ToBlob,
+{
+    fn to_rusqlite<'a>(component: &'a C) -> Result<rusqlite::types::ToSqlOutput<'a>, StorageError> {
+        Ok(rusqlite::types::ToSqlOutput::Owned(
+            rusqlite::types::Value::Blob(component.to_blob()),
+        ))
+    }
+}
+
+/// Stores nothing but a SQL `NULL`. The value is rebuilt on read via
+/// [`NullComponent`].
 pub struct NullStorage;
 
+/// The value of a [`NullStorage`] component. Derived along with `Component`
+/// for unit structs, and via `Default` for types with
+/// `#[component(storage = "null")]`.
+pub trait NullComponent: Sized {
+    fn from_null() -> Self;
+}
+
 impl<C> ComponentRead<C> for NullStorage
 where
-    C: Component + DeserializeOwned,
+    C: Component + NullComponent,
 {
     fn from_rusqlite(value: &rusqlite::types::ToSqlOutput<'_>) -> Result<C, StorageError> {
         match value {
             rusqlite::types::ToSqlOutput::Borrowed(rusqlite::types::ValueRef::Null)
             | rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Null) => {
-                serde_json::from_str("null").map_err(|e| StorageError(e.to_string()))
+                Ok(C::from_null())
             }
-            other => Err(StorageError(format!("Unexpected type {other:?}"))),
+            other => Err(StorageError::Other(format!("Unexpected type {other:?}"))),
         }
     }
 }
 
 impl<C> ComponentWrite<C> for NullStorage
 where
-    C: Component + Serialize,
+    C: Component,
 {
     fn to_rusqlite<'a>(
         _component: &'a C,
@@ -155,15 +247,71 @@ pub trait Bundle: Sized {
         Self::COMPONENTS
     }
 
+    /// Rust types of the components in this bundle, used to detect name
+    /// conflicts in strict mode. Strict mode rejects bundles that don't
+    /// report the type of each of their components, so custom
+    /// implementations should override this.
+    fn component_types() -> Vec<ComponentType> {
+        Vec::new()
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<BundleData<'a>, StorageError>;
-    // fn from_rusqlite<'a>(components: BundleDataRef<'a>) -> Result<Option<Self>, StorageError>;
+
+    /// Reconstructs the bundle from the stored data of its components.
+    /// Returns `None` if a required component is missing.
+    fn from_rusqlite<'a>(components: BundleDataRef<'a>) -> Result<Option<Self>, StorageError>;
+}
+
+/// Looks up the data of component `name` in `components`
+#[doc(hidden)]
+pub fn find_component<'a>(
+    components: BundleDataRef<'a>,
+    name: &str,
+) -> Option<&'a rusqlite::types::ToSqlOutput<'a>> {
+    components
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, data)| data.as_ref())
 }
 
 pub trait NonEmptyBundle: Bundle {}
 
+/// The Rust type backing a component name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentType {
+    pub name: &'static str,
+    pub type_id: std::any::TypeId,
+    pub type_name: &'static str,
+}
+
+impl ComponentType {
+    pub fn of<C: Component>() -> Self {
+        Self {
+            name: C::NAME,
+            type_id: std::any::TypeId::of::<C>(),
+            type_name: std::any::type_name::<C>(),
+        }
+    }
+}
+
 pub trait BundleComponent {
     const NAME: &'static str;
+
+    /// Rust type of the component, see [`Bundle::component_types`]. `None`
+    /// if unknown.
+    fn component_type() -> Option<ComponentType> {
+        None
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<Option<rusqlite::types::ToSqlOutput<'a>>, StorageError>;
+
+    /// Reads the component from its stored data, `None` if it's missing. The
+    /// outer `Option` is `None` if the bundle can't be constructed.
+    fn from_bundle_data(
+        data: Option<&rusqlite::types::ToSqlOutput<'_>>,
+    ) -> Result<Option<Self>, StorageError>
+    where
+        Self: Sized;
 }
 
 impl Bundle for () {
@@ -172,33 +320,67 @@ impl Bundle for () {
     fn to_rusqlite<'a>(&'a self) -> Result<BundleData<'a>, StorageError> {
         Ok(vec![])
     }
+
+    fn from_rusqlite<'a>(_components: BundleDataRef<'a>) -> Result<Option<Self>, StorageError> {
+        Ok(Some(()))
+    }
 }
 
 impl<C: Component> BundleComponent for C {
     const NAME: &'static str = C::NAME;
 
+    fn component_type() -> Option<ComponentType> {
+        Some(ComponentType::of::<C>())
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<Option<rusqlite::types::ToSqlOutput<'a>>, StorageError> {
         Ok(Some(C::to_rusqlite(self)?))
     }
+
+    fn from_bundle_data(
+        data: Option<&rusqlite::types::ToSqlOutput<'_>>,
+    ) -> Result<Option<Self>, StorageError> {
+        data.map(<C as ComponentRead<C>>::from_rusqlite).transpose()
+    }
 }
 
 impl<C: Component> BundleComponent for Option<C> {
     const NAME: &'static str = C::NAME;
 
+    fn component_type() -> Option<ComponentType> {
+        Some(ComponentType::of::<C>())
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<Option<rusqlite::types::ToSqlOutput<'a>>, StorageError> {
         match self {
             Some(c) => <C as BundleComponent>::to_rusqlite(c),
             None => Ok(None),
         }
     }
+
+    /// A missing component is read as `None`. Deserialization errors are
+    /// returned as for `C`.
+    fn from_bundle_data(
+        data: Option<&rusqlite::types::ToSqlOutput<'_>>,
+    ) -> Result<Option<Self>, StorageError> {
+        <C as BundleComponent>::from_bundle_data(data).map(Some)
+    }
 }
 
 impl<C: Component> Bundle for C {
     const COMPONENTS: &'static [&'static str] = &[C::NAME];
 
+    fn component_types() -> Vec<ComponentType> {
+        vec![ComponentType::of::<C>()]
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<BundleData<'a>, StorageError> {
         Ok(vec![(C::NAME, Some(C::to_rusqlite(self)?))])
     }
+
+    fn from_rusqlite<'a>(components: BundleDataRef<'a>) -> Result<Option<Self>, StorageError> {
+        <C as BundleComponent>::from_bundle_data(find_component(components, C::NAME))
+    }
 }
 
 impl<C: Component> NonEmptyBundle for C {}
@@ -206,12 +388,20 @@ impl<C: Component> NonEmptyBundle for C {}
 impl<C: Component> Bundle for Option<C> {
     const COMPONENTS: &'static [&'static str] = &[C::NAME];
 
+    fn component_types() -> Vec<ComponentType> {
+        vec![ComponentType::of::<C>()]
+    }
+
     fn to_rusqlite<'a>(&'a self) -> Result<BundleData<'a>, StorageError> {
         Ok(vec![(
             C::NAME,
             self.as_ref().map(C::to_rusqlite).transpose()?,
         )])
     }
+
+    fn from_rusqlite<'a>(components: BundleDataRef<'a>) -> Result<Option<Self>, StorageError> {
+        <Self as BundleComponent>::from_bundle_data(find_component(components, C::NAME))
+    }
 }
 
 macro_rules! bundle_tuples{
@@ -224,6 +414,10 @@ macro_rules! bundle_tuples{
                 $($ts::NAME,)+
             ];
 
+            fn component_types() -> Vec<ComponentType> {
+                [$($ts::component_type(),)+].into_iter().flatten().collect()
+            }
+
             fn to_rusqlite<'a>(
                 &'a self
             ) -> Result<BundleData<'a>, StorageError> {
@@ -235,6 +429,19 @@ macro_rules! bundle_tuples{
                     ]
                 )
             }
+
+            fn from_rusqlite<'a>(
+                components: BundleDataRef<'a>
+            ) -> Result<Option<Self>, StorageError> {
+                Ok(Some((
+                    $(
+                        match $ts::from_bundle_data(find_component(components, $ts::NAME))? {
+                            Some(c) => c,
+                            None => return Ok(None),
+                        },
+                    )+
+                )))
+            }
         }
 
         impl<$($ts,)+> NonEmptyBundle for ($($ts,)+)
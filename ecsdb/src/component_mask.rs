@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use ecsdb_derive::with_infallible;
+use rusqlite::{OptionalExtension, params};
+use tracing::debug;
+
+use crate::{Ecs, Error, component::Bundle};
+
+/// Number of components that fit into a mask. The sign bit is left unused.
+pub const MAX_MASKED_COMPONENTS: usize = 63;
+
+#[with_infallible]
+impl Ecs {
+    /// Assigns each component in `B` a bit in a per-entity presence mask,
+    /// which is kept up to date by triggers on every attach and detach.
+    /// Queries requiring two or more masked components, like
+    /// `With<(A, B, C)>`, then check a single `mask & ? = ?` instead of one
+    /// subquery per component.
+    ///
+    /// Calling this again with further components extends the mask. At most
+    /// [`MAX_MASKED_COMPONENTS`] components can be masked.
+    #[tracing::instrument(name = "enable_component_mask", level = "debug", skip_all)]
+    pub fn try_enable_component_mask<B: Bundle>(&mut self) -> Result<(), Error> {
+        let tx = self.transaction()?;
+        tx.execute_batch(include_str!("component_mask.sql"))?;
+
+        let mut next_bit: u32 = tx.query_row(
+            "select coalesce(max(bit) + 1, 0) from component_bits",
+            [],
+            |row| row.get(0),
+        )?;
+
+        for component in B::COMPONENTS {
+            let component = self.stored_component_name(component);
+            let assigned = tx
+                .query_row(
+                    "select bit from component_bits where component = ?1",
+                    [component],
+                    |row| row.get::<_, u32>(0),
+                )
+                .optional()?;
+            if assigned.is_some() {
+                continue;
+            }
+
+            if next_bit as usize >= MAX_MASKED_COMPONENTS {
+                return Err(Error::TooManyMaskedComponents(MAX_MASKED_COMPONENTS));
+            }
+
+            tx.execute(
+                "insert into component_bits (component, bit) values (?1, ?2)",
+                params![component, next_bit],
+            )?;
+            debug!(component, bit = next_bit, "assigned mask bit");
+            next_bit += 1;
+        }
+
+        // Backfill masks of existing entities
+        tx.execute_batch(
+            r#"
+            insert into component_masks (entity, mask)
+            select c.entity, sum(1 << b.bit)
+            from components c join component_bits b on b.component = c.component
+            where true
+            group by c.entity
+            on conflict (entity) do update set mask = excluded.mask;
+            "#,
+        )?;
+
+        tx.commit()?;
+
+        self.registry.component_bits = load_component_bits(&self.conn)?;
+        Ok(())
+    }
+}
+
+/// Component name to mask bit. Empty if masks were never enabled.
+pub(crate) fn load_component_bits(
+    conn: &rusqlite::Connection,
+) -> Result<HashMap<String, u32>, Error> {
+    let enabled = conn
+        .prepare("select 1 from sqlite_master where type = 'table' and name = 'component_bits'")?
+        .exists([])?;
+    if !enabled {
+        return Ok(HashMap::new());
+    }
+
+    let mut stmt = conn.prepare("select component, bit from component_bits")?;
+    let bits = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::query::{Or, Query, With, Without};
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct C;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct D;
+
+    fn results(db: &Ecs) -> Vec<Vec<EntityId>> {
+        vec![
+            db.query::<EntityId, With<(A, B)>>().collect(),
+            db.query::<EntityId, With<(A, B, C)>>().collect(),
+            db.query::<EntityId, (With<(A, C)>, Without<B>)>().collect(),
+            db.query::<EntityId, (With<(B, D)>, Or<(With<A>, With<C>)>)>()
+                .collect(),
+            db.query::<EntityId, (A, B, C, D)>().collect(),
+        ]
+    }
+
+    #[test]
+    fn component_mask_preserves_results() {
+        let mut db = Ecs::open_in_memory().unwrap();
+
+        for n in 0..64 {
+            let e = db.new_entity().attach(A);
+            if n % 2 == 0 {
+                e.attach(B);
+            }
+            if n % 3 == 0 {
+                e.attach(C);
+            }
+            if n % 5 == 0 {
+                e.attach(D);
+            }
+            if n % 7 == 0 {
+                e.detach::<A>();
+            }
+        }
+
+        let expected = results(&db);
+        assert!(expected.iter().all(|ids| !ids.is_empty()));
+
+        db.enable_component_mask::<(A, B)>();
+        assert_eq!(results(&db), expected);
+
+        // Extending the mask backfills the new bits
+        db.enable_component_mask::<(B, C, D)>();
+        assert_eq!(results(&db), expected);
+
+        let (sql, _) = Query::<EntityId, With<(A, B, C)>>::new(&db).to_sql();
+        assert!(sql.contains("component_masks"), "{sql}");
+
+        // Masks follow attach, detach and destroy
+        let e = db.new_entity().attach((A, B, C));
+        assert!(
+            db.query::<EntityId, With<(A, B, C)>>()
+                .any(|id| id == e.id())
+        );
+        e.detach::<B>();
+        assert!(
+            !db.query::<EntityId, With<(A, B, C)>>()
+                .any(|id| id == e.id())
+        );
+        e.attach(B);
+        assert!(
+            db.query::<EntityId, With<(A, B, C)>>()
+                .any(|id| id == e.id())
+        );
+        e.destroy();
+        assert!(
+            !db.query::<EntityId, With<(A, B, C)>>()
+                .any(|id| id == e.id())
+        );
+
+        // Masks follow renamed components
+        let e = db.new_entity().attach((A, D)).id();
+        db.remove_component_everywhere::<B>().unwrap();
+        db.rename_component(D::NAME, B::NAME).unwrap();
+        assert!(db.query::<EntityId, With<(A, B)>>().any(|id| id == e));
+        db.rename_component(B::NAME, D::NAME).unwrap();
+        assert_eq!(db.query::<EntityId, With<(A, B)>>().count(), 0);
+        assert!(db.query::<EntityId, With<(A, D)>>().any(|id| id == e));
+
+        // Bits are persisted in the database
+        let path = crate::test_util::TempDb::new("mask");
+        db.raw_sql()
+            .execute("vacuum into ?1", [path.path().to_str().unwrap()])
+            .unwrap();
+        let reopened = Ecs::open(&path).unwrap();
+        assert_eq!(reopened.registry.component_bits.len(), 4);
+        assert_eq!(results(&reopened), results(&db));
+        reopened.close().unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+-- Per-entity bitset of component presence, see `Ecs::enable_component_mask`
+create table if not exists component_bits (
+    component text primary key,
+    bit integer not null unique check (bit between 0 and 62)
+);
+
+create table if not exists component_masks (
+    entity integer primary key,
+    mask integer not null default 0
+);
+
+create trigger if not exists component_masks_insert_trigger
+after insert on components
+for each row
+begin
+    insert into component_masks (entity, mask)
+    select new.entity, 1 << bit from component_bits where component = new.component
+    on conflict (entity) do update set mask = mask | excluded.mask;
+end;
+
+create trigger if not exists component_masks_delete_trigger
+after delete on components
+for each row when exists (select 1 from component_bits where component = old.component)
+begin
+    update component_masks
+    set mask = mask & ~(1 << (select bit from component_bits where component = old.component))
+    where entity = old.entity;
+end;
+
+create trigger if not exists component_masks_update_trigger
+after update of entity, component on components
+for each row when old.entity != new.entity or old.component != new.component
+begin
+    update component_masks
+    set mask = mask & ~coalesce((select 1 << bit from component_bits where component = old.component), 0)
+    where entity = old.entity;
+
+    insert into component_masks (entity, mask)
+    select new.entity, 1 << bit from component_bits where component = new.component
+    on conflict (entity) do update set mask = mask | excluded.mask;
+end;
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use ecsdb_derive::with_infallible;
+
+use crate::{Entity, Error};
+
+/// Component-wise difference between two entities, see [`Entity::diff`].
+/// System components like [`crate::CreatedAt`] are ignored. All lists are
+/// sorted by component name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityDiff {
+    pub only_in_self: Vec<String>,
+    pub only_in_other: Vec<String>,
+    /// Components on both entities, with different stored data
+    pub differing_data: Vec<String>,
+}
+
+impl EntityDiff {
+    /// Whether both entities carry the same components with the same data
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty()
+            && self.only_in_other.is_empty()
+            && self.differing_data.is_empty()
+    }
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Compares the components of this entity to those of `other`. Data is
+    /// compared by its stored representation.
+    pub fn try_diff(self, other: Entity<'_>) -> Result<EntityDiff, Error> {
+        let mut this = self.raw_components()?;
+        let other = other.raw_components()?;
+
+        let mut diff = EntityDiff::default();
+        for (component, data) in other {
+            match this.remove(&component) {
+                Some(this_data) if this_data == data => {}
+                Some(_) => diff.differing_data.push(component),
+                None => diff.only_in_other.push(component),
+            }
+        }
+        diff.only_in_self = this.into_keys().collect();
+
+        Ok(diff)
+    }
+}
+
+impl<'a> Entity<'a> {
+    fn raw_components(self) -> Result<BTreeMap<String, rusqlite::types::Value>, Error> {
+        let mut stmt = self.0.conn.prepare_cached(
+            r#"
+            select component, data from components
+            where entity = ?1 and component not in (select component from system_components)
+            "#,
+        )?;
+
+        let components = stmt
+            .query_map([self.id()], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::EntityDiff;
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct C;
+
+    #[test]
+    fn diff() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let x = ecs.new_entity().attach((A, B(1), C));
+        let y = ecs.new_entity().attach((A, B(2)));
+
+        assert_eq!(
+            x.diff(y),
+            EntityDiff {
+                only_in_self: vec![C::NAME.to_owned()],
+                only_in_other: vec![],
+                differing_data: vec![B::NAME.to_owned()],
+            }
+        );
+
+        let reverse = y.diff(x);
+        assert_eq!(reverse.only_in_other, [C::NAME]);
+        assert!(reverse.only_in_self.is_empty());
+
+        y.attach((B(1), C));
+        assert!(x.diff(y).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::de::DeserializeOwned;
 use tracing::warn;
 
 use crate::{Component, component};
@@ -50,6 +51,40 @@ impl<'a> DynComponent<'a> {
         }
     }
 
+    /// The raw SQLite value backing this component
+    pub fn as_value(&self) -> rusqlite::types::Value {
+        use rusqlite::types::ToSqlOutput;
+
+        match self.1 {
+            ToSqlOutput::Borrowed(value) => value.into(),
+            ToSqlOutput::Owned(ref value) => value.clone(),
+            ref other => unreachable!("Unexpected ToSqlOutput {other:?}"),
+        }
+    }
+
+    /// Deserializes a JSON-backed component into any `T`, which doesn't need
+    /// to be a [`Component`]. `T` only needs to be compatible with the stored
+    /// JSON, e.g. a struct with a subset of its fields.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, component::StorageError> {
+        use rusqlite::types::{ToSqlOutput, Value, ValueRef};
+
+        let result = match self.1 {
+            ToSqlOutput::Borrowed(ValueRef::Text(s)) => serde_json::from_slice(s),
+            ToSqlOutput::Owned(Value::Text(ref s)) => serde_json::from_str(s),
+            ToSqlOutput::Owned(Value::Null) | ToSqlOutput::Borrowed(ValueRef::Null) => {
+                T::deserialize(serde_json::Value::Null)
+            }
+            ref other => {
+                return Err(component::StorageError::Other(format!(
+                    "{}: Unexpected type {other:?}",
+                    self.0
+                )));
+            }
+        };
+
+        result.map_err(|e| component::StorageError::Other(format!("{}: {e}", self.0)))
+    }
+
     pub fn as_json(&self) -> Option<serde_json::value::Value> {
         use rusqlite::types::{ToSqlOutput, Value, ValueRef};
 
@@ -0,0 +1,318 @@
+//! Encryption at rest for individual components.
+//!
+//! [`Encrypted<S>`] wraps another storage, encrypting its output with
+//! AES-256-GCM under the key set via [`Ecs::set_encryption_key`]. The
+//! ciphertext is bound to its entity and component, so data copied to
+//! another entity or renamed to another component, e.g. via
+//! [`Entity::clone_entity`](crate::Entity::clone_entity) or
+//! [`Ecs::rename_component`], can't be decrypted anymore.
+//!
+//! Encrypted components can only be converted while reading or writing an
+//! entity. Using them as filter values, e.g. in [`Ecs::find`], fails with
+//! [`Error::InvalidFilter`](crate::Error::InvalidFilter), as every write
+//! uses a fresh nonce.
+//!
+//! ```ignore
+//! struct Secret(String);
+//!
+//! impl Component for Secret {
+//!     type Storage = Encrypted<JsonStorage>;
+//!     const NAME: &'static str = "app::Secret";
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rusqlite::types::{ToSqlOutput, Value, ValueRef};
+
+use crate::component::{Component, ComponentRead, ComponentWrite, StorageError};
+use crate::{Ecs, EntityId};
+
+const NONCE_LENGTH: usize = 12;
+
+/// Key of an [`Ecs`], stored as an extension
+struct EncryptionKey(Aes256Gcm);
+
+/// The entity whose components are being converted, see [`with_entity`]
+struct Context {
+    cipher: Option<Aes256Gcm>,
+    entity: EntityId,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+impl Ecs {
+    /// Sets the key used by [`Encrypted`] storages. Pass `None` to remove it.
+    /// Reading or writing encrypted components without a key fails.
+    pub fn set_encryption_key(&mut self, key: Option<&[u8; 32]>) {
+        match key {
+            Some(key) => {
+                let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+                self.extensions.insert(EncryptionKey(cipher));
+            }
+            None => {
+                self.extensions.remove::<EncryptionKey>();
+            }
+        }
+    }
+}
+
+/// Runs `f`, which converts components of `entity` from or to their storage,
+/// with the key of `ecs`
+pub(crate) fn with_entity<T>(ecs: &Ecs, entity: EntityId, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Context>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CONTEXT.set(self.0.take());
+        }
+    }
+
+    let cipher = ecs
+        .extensions
+        .get::<EncryptionKey>()
+        .map(|key| key.0.clone());
+    let _restore = Restore(CONTEXT.replace(Some(Context { cipher, entity })));
+    f()
+}
+
+fn with_cipher<T>(
+    f: impl FnOnce(&Aes256Gcm, EntityId) -> Result<T, StorageError>,
+) -> Result<T, StorageError> {
+    CONTEXT.with_borrow(|context| match context {
+        Some(Context {
+            cipher: Some(cipher),
+            entity,
+        }) => f(cipher, *entity),
+        Some(Context { cipher: None, .. }) => {
+            Err(StorageError::Other("No encryption key set".into()))
+        }
+        None => Err(StorageError::Other(
+            "Encrypted components can only be converted for an entity".into(),
+        )),
+    })
+}
+
+// Binds the ciphertext to the entity and component it's stored for
+fn associated_data<C: Component>(entity: EntityId) -> Vec<u8> {
+    let mut aad = entity.to_le_bytes().to_vec();
+    aad.extend(C::NAME.as_bytes());
+    aad
+}
+
+/// Storage encrypting the output of storage `S`. Stored as a blob of the nonce
+/// followed by the ciphertext.
+pub struct Encrypted<S>(PhantomData<S>);
+
+impl<C, S> ComponentWrite<C> for Encrypted<S>
+where
+    C: Component,
+    S: ComponentWrite<C>,
+{
+    fn to_rusqlite<'a>(component: &'a C) -> Result<ToSqlOutput<'a>, StorageError> {
+        let plaintext = encode(&S::to_rusqlite(component)?)?;
+
+        let blob = with_cipher(|cipher, entity| {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let payload = Payload {
+                msg: &plaintext,
+                aad: &associated_data::<C>(entity),
+            };
+            let ciphertext = cipher
+                .encrypt(&nonce, payload)
+                .map_err(|e| StorageError::Other(format!("Encryption failed: {e}")))?;
+
+            let mut blob = nonce.to_vec();
+            blob.extend(ciphertext);
+            Ok(blob)
+        })?;
+
+        Ok(ToSqlOutput::Owned(Value::Blob(blob)))
+    }
+}
+
+impl<C, S> ComponentRead<C> for Encrypted<S>
+where
+    C: Component,
+    S: ComponentRead<C>,
+{
+    fn from_rusqlite(value: &ToSqlOutput<'_>) -> Result<C, StorageError> {
+        let blob = match value {
+            ToSqlOutput::Borrowed(ValueRef::Blob(b)) => *b,
+            ToSqlOutput::Owned(Value::Blob(b)) => b,
+            other => return Err(StorageError::Other(format!("Unexpected type {other:?}"))),
+        };
+
+        if blob.len() < NONCE_LENGTH {
+            return Err(StorageError::Other("Encrypted data too short".into()));
+        }
+
+        let (nonce, ciphertext) = blob.split_at(NONCE_LENGTH);
+        let plaintext = with_cipher(|cipher, entity| {
+            let payload = Payload {
+                msg: ciphertext,
+                aad: &associated_data::<C>(entity),
+            };
+            cipher
+                .decrypt(Nonce::from_slice(nonce), payload)
+                .map_err(|e| StorageError::Other(format!("Decryption failed: {e}")))
+        })?;
+
+        S::from_rusqlite(&ToSqlOutput::Owned(decode(&plaintext)?))
+    }
+}
+
+// The inner storage's value, prefixed with a tag byte for its type
+fn encode(value: &ToSqlOutput<'_>) -> Result<Vec<u8>, StorageError> {
+    let value = match value {
+        ToSqlOutput::Borrowed(value) => *value,
+        ToSqlOutput::Owned(value) => value.into(),
+        other => return Err(StorageError::Other(format!("Unexpected type {other:?}"))),
+    };
+
+    let mut bytes = Vec::new();
+    match value {
+        ValueRef::Null => bytes.push(0),
+        ValueRef::Integer(i) => {
+            bytes.push(1);
+            bytes.extend(i.to_le_bytes());
+        }
+        ValueRef::Real(r) => {
+            bytes.push(2);
+            bytes.extend(r.to_le_bytes());
+        }
+        ValueRef::Text(t) => {
+            bytes.push(3);
+            bytes.extend(t);
+        }
+        ValueRef::Blob(b) => {
+            bytes.push(4);
+            bytes.extend(b);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn decode(bytes: &[u8]) -> Result<Value, StorageError> {
+    let invalid = || StorageError::Other("Invalid decrypted data".into());
+
+    let (tag, data) = bytes.split_first().ok_or_else(invalid)?;
+    let value = match tag {
+        0 => Value::Null,
+        1 => Value::Integer(i64::from_le_bytes(data.try_into().map_err(|_| invalid())?)),
+        2 => Value::Real(f64::from_le_bytes(data.try_into().map_err(|_| invalid())?)),
+        3 => Value::Text(String::from_utf8(data.to_vec()).map_err(|_| invalid())?),
+        4 => Value::Blob(data.to_vec()),
+        _ => return Err(invalid()),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Encrypted;
+    use crate::component::{Component, JsonStorage};
+    use crate::{Ecs, Error};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Credentials {
+        user: String,
+        password: String,
+    }
+
+    impl Component for Credentials {
+        type Storage = Encrypted<JsonStorage>;
+        const NAME: &'static str = "ecsdb::test::Credentials";
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            user: "alice".into(),
+            password: "hunter2".into(),
+        }
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.set_encryption_key(Some(&[7; 32]));
+        let entity = db.new_entity().attach(credentials()).id();
+
+        assert_eq!(
+            db.entity(entity).component::<Credentials>(),
+            Some(credentials())
+        );
+
+        let stored = db
+            .entity(entity)
+            .dyn_component(Credentials::NAME)
+            .unwrap()
+            .as_blob()
+            .unwrap()
+            .to_vec();
+        assert!(!String::from_utf8_lossy(&stored).contains("hunter2"));
+
+        db.set_encryption_key(Some(&[8; 32]));
+        assert!(db.entity(entity).try_component::<Credentials>().is_err());
+
+        db.set_encryption_key(None);
+        assert!(db.entity(entity).try_component::<Credentials>().is_err());
+    }
+
+    #[test]
+    fn keys_are_per_database() {
+        let mut a = Ecs::open_in_memory().unwrap();
+        a.set_encryption_key(Some(&[1; 32]));
+        let b = Ecs::open_in_memory().unwrap();
+
+        let entity = a.new_entity().attach(credentials());
+        assert!(b.new_entity().try_attach(credentials()).is_err());
+        assert_eq!(entity.component::<Credentials>(), Some(credentials()));
+    }
+
+    #[test]
+    fn ciphertext_is_bound_to_its_entity() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.set_encryption_key(Some(&[7; 32]));
+        let a = db.new_entity().attach(credentials()).id();
+        let b = db
+            .new_entity()
+            .attach(Credentials {
+                user: "bob".into(),
+                password: "secret".into(),
+            })
+            .id();
+
+        // Swap in the ciphertext of `a`
+        db.raw_sql()
+            .execute(
+                "update components set data = (select data from components where entity = ?1)
+                 where entity = ?2",
+                [a, b],
+            )
+            .unwrap();
+        assert!(db.entity(b).try_component::<Credentials>().is_err());
+        assert_eq!(db.entity(a).component::<Credentials>(), Some(credentials()));
+    }
+
+    #[test]
+    fn encrypted_filter_values_are_rejected() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.set_encryption_key(Some(&[7; 32]));
+        db.new_entity().attach(credentials());
+
+        assert!(matches!(
+            db.try_find(credentials()).map(|found| found.count()),
+            Err(Error::InvalidFilter(_))
+        ));
+    }
+}
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
+
 use ecsdb_derive::with_infallible;
 use rusqlite::{OptionalExtension, params};
 use tracing::{debug, trace};
 
 use crate::{
-    Component, CreatedAt, DynComponent, Ecs, EntityId, Error, LastUpdated,
+    Component, CreatedAt, DynComponent, Ecs, EntityId, Error, LastUpdated, TaggedEntityId,
     component::{Bundle, NonEmptyBundle},
     query::{self},
 };
@@ -17,7 +19,7 @@ pub type Entity<'a> = GenericEntity<'a, WithEntityId>;
 pub type NewEntity<'a> = GenericEntity<'a, WithoutEntityId>;
 
 #[derive(Copy, Clone)]
-pub struct GenericEntity<'a, S>(&'a Ecs, S);
+pub struct GenericEntity<'a, S>(pub(crate) &'a Ecs, S);
 
 impl<'a, T> GenericEntity<'a, T> {
     pub(crate) fn without_id(ecs: &'a Ecs) -> NewEntity<'a> {
@@ -46,7 +48,7 @@ impl<'a> Entity<'a> {
         self.0
             .conn
             .query_row(
-                "select true from components where entity = ?1",
+                "select true from entities where id = ?1",
                 params![self.id()],
                 |_| Ok(()),
             )
@@ -88,12 +90,47 @@ impl<'a> Entity<'a> {
         self.try_has_all_dynamic(B::COMPONENTS)
     }
 
+    /// Whether the entity has at least one component of `B`
+    pub fn try_has_any<B: Bundle>(self) -> Result<bool, Error> {
+        Ok(self.count_components_dynamic(B::COMPONENTS)? > 0)
+    }
+
+    /// Whether the entity has no component of `B`
+    pub fn try_has_none<B: Bundle>(self) -> Result<bool, Error> {
+        Ok(self.count_components_dynamic(B::COMPONENTS)? == 0)
+    }
+
+    fn count_components_dynamic(self, component_names: &[&str]) -> Result<usize, Error> {
+        if component_names.is_empty() {
+            return Ok(0);
+        }
+
+        let sql = format!(
+            "select count(*) from components where entity = ? and component in ({})",
+            vec!["?"; component_names.len()].join(", ")
+        );
+        let id = self.id();
+        let component_names = component_names
+            .iter()
+            .map(|c| self.0.stored_component_name(c))
+            .collect::<Vec<_>>();
+        let params = std::iter::once(&id as &dyn rusqlite::ToSql)
+            .chain(component_names.iter().map(|c| c as &dyn rusqlite::ToSql));
+
+        Ok(self
+            .0
+            .conn
+            .prepare_cached(&sql)?
+            .query_row(rusqlite::params_from_iter(params), |row| row.get(0))?)
+    }
+
     fn try_has_all_dynamic(self, component_names: &[&str]) -> Result<bool, Error> {
         let mut stmt = self
             .0
             .conn
             .prepare_cached("select true from components where entity = ?1 and component = ?2")?;
         for name in component_names {
+            let name = self.0.stored_component_name(name);
             if !stmt.exists(params![self.id(), name])? {
                 return Ok(false);
             }
@@ -107,38 +144,130 @@ impl<'a> Entity<'a> {
 impl<'a> Entity<'a> {
     #[tracing::instrument(name = "destroy", level = "debug")]
     pub fn try_destroy(self) -> Result<(), Error> {
-        self.0
-            .conn
-            .execute("delete from components where entity = ?1", [self.id()])?;
+        match self.0.retry {
+            Some(retry) => retry.run(self.0, || self.destroy_once()),
+            None => self.destroy_once(),
+        }
+    }
+
+    fn destroy_once(self) -> Result<(), Error> {
+        let tx = self.0.transaction()?;
+        let detached = self.0.delete_components("entity = ?1", [self.id()])?;
+        tx.execute("delete from entities where id = ?1", [self.id()])?;
+        tx.commit()?;
         debug!(entity = self.id(), "destroyed");
-        Ok(())
+
+        detached.run_hooks(self.0)
     }
 }
 
 #[with_infallible]
 impl<'a> Entity<'a> {
-    pub fn try_component<T: Component>(self) -> Result<Option<T>, Error> {
-        let name = T::component_name();
-        let mut query = self
-            .0
-            .conn
-            .prepare_cached("select data from components where entity = ?1 and component = ?2")?;
+    /// Copies all components, including the values of
+    /// [`MultiComponent`](crate::MultiComponent)s, to a freshly allocated
+    /// entity. `CreatedAt` and `LastUpdated` aren't copied but set anew. Fails with
+    /// [`Error::EmptyBundle`] if there's nothing to copy.
+    #[tracing::instrument(name = "clone_entity", level = "debug")]
+    pub fn try_clone_entity(self) -> Result<Entity<'a>, Error> {
+        let tx = self.0.transaction()?;
 
-        query
-            .query_and_then(params![self.id(), name], |row| {
-                let data = row.get_ref("data")?;
-                Ok(T::from_rusqlite(&rusqlite::types::ToSqlOutput::Borrowed(
-                    data,
-                ))?)
+        let eid = self.0.allocate_entity_id()?;
+
+        let copied = tx.execute(
+            r#"
+            insert into components (entity, component, data)
+            select ?2, component, data from components
+            where entity = ?1 and component not in (select component from system_components)
+            "#,
+            params![self.id(), eid],
+        )?;
+
+        // Rolls back the allocated id
+        if copied == 0 {
+            return Err(Error::EmptyBundle);
+        }
+
+        tx.execute(
+            r#"
+            insert into multi_components (entity, component, data)
+            select ?2, component, data from multi_components
+            where entity = ?1
+            order by rowid
+            "#,
+            params![self.id(), eid],
+        )?;
+
+        tx.commit()?;
+
+        debug!(entity = self.id(), clone = eid, "cloned");
+
+        Ok(GenericEntity(self.0, WithEntityId(eid)))
+    }
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Reads component or bundle `B` off the entity, e.g. `component::<(A,
+    /// B)>()`. Returns `None` if any non-`Option` component is missing.
+    pub fn try_component<B: Bundle>(self) -> Result<Option<B>, Error> {
+        if B::COMPONENTS.is_empty() {
+            return Ok(B::from_rusqlite(&[])?);
+        }
+
+        // Skips the presence rows of `MultiComponent`s, whose values are
+        // stored in `multi_components`
+        let sql = format!(
+            "select component, data from components
+             where entity = ?1 and component in ({})
+             and (data is not null or not exists (
+                select 1 from multi_components m
+                where m.entity = components.entity and m.component = components.component
+             ))",
+            vec!["?"; B::COMPONENTS.len()].join(", ")
+        );
+        let id = self.id();
+        let stored_names = B::COMPONENTS
+            .iter()
+            .map(|c| self.0.stored_component_name(c))
+            .collect::<Vec<_>>();
+        let params = std::iter::once(&id as &dyn rusqlite::ToSql)
+            .chain(stored_names.iter().map(|c| c as &dyn rusqlite::ToSql));
+
+        let mut stmt = self.0.conn.prepare_cached(&sql)?;
+        let mut rows = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| {
+                Ok((
+                    row.get::<_, String>("component")?,
+                    row.get::<_, rusqlite::types::Value>("data")?,
+                ))
             })?
-            .next()
-            .transpose()
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        let data = B::COMPONENTS
+            .iter()
+            .zip(stored_names)
+            .map(|(name, stored)| {
+                let data = rows.remove(stored).map(rusqlite::types::ToSqlOutput::Owned);
+                (*name, data)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(self
+            .0
+            .with_storage_context(id, || B::from_rusqlite(&data))?)
+    }
+
+    /// Same as [`Self::try_component`]. Reads all components of `B` in a single
+    /// query, e.g. `get::<(A, B, Option<C>)>()`.
+    pub fn try_get<B: Bundle>(self) -> Result<Option<B>, Error> {
+        self.try_component::<B>()
     }
 }
 
 #[with_infallible]
 impl<'a> Entity<'a> {
     pub fn try_dyn_component(self, name: &'a str) -> Result<Option<DynComponent<'a>>, Error> {
+        let name = self.0.stored_component_name(name);
         let mut query = self
             .0
             .conn
@@ -155,16 +284,74 @@ impl<'a> Entity<'a> {
             .next()
             .transpose()
     }
+
+    /// The stored data of component `name` as-is, without deserializing it:
+    /// the UTF-8 bytes of JSON components, the bytes of blob components.
+    /// `NULL` data is returned as an empty `Vec`.
+    pub fn try_component_raw(self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let name = self.0.stored_component_name(name);
+        let mut query = self.0.conn.prepare_cached(
+            "select cast(data as blob) from components where entity = ?1 and component = ?2",
+        )?;
+
+        Ok(query
+            .query_row(params![self.id(), name], |row| {
+                row.get::<_, Option<Vec<u8>>>(0)
+            })
+            .optional()?
+            .map(Option::unwrap_or_default))
+    }
 }
 
 #[with_infallible]
 impl<'a> Entity<'a> {
+    /// Attaches a component only known at runtime. Same as
+    /// [`Self::try_dyn_attach_all`] with a single component.
     pub fn try_dyn_attach(self, component: DynComponent<'a>) -> Result<Self, Error> {
-        let mut query = self.0.conn.prepare_cached(
-            "update components set data = ?1 where entity = ?2 and component = ?3",
+        self.try_dyn_attach_all([component])
+    }
+
+    /// Attaches a set of components only known at runtime, e.g. from an
+    /// import, in a single transaction. Existing components are overwritten.
+    #[tracing::instrument(name = "dyn_attach_all", level = "debug", skip_all)]
+    pub fn try_dyn_attach_all(
+        self,
+        components: impl IntoIterator<Item = DynComponent<'a>>,
+    ) -> Result<Self, Error> {
+        let tx = self.0.transaction()?;
+        let mut stmt = tx.prepare_cached(
+            r#"
+            insert into components (entity, component, data)
+            values (?1, ?2, ?3)
+            on conflict (entity, component) do update
+            set data = excluded.data where data is not excluded.data;
+            "#,
         )?;
 
-        query.execute(params![component.1, self.id(), component.0])?;
+        let mut newly_attached = Vec::new();
+        for DynComponent(component, data) in components {
+            let component = self.0.stored_component_name(component);
+            let is_new = self
+                .0
+                .hooks
+                .has_attach_hooks(self.0.unaliased_component_name(component))
+                && !self.try_has_all_dynamic(&[component])?;
+
+            stmt.execute(params![self.id(), component, data])?;
+            debug!(entity = self.id(), component, "attached");
+
+            if is_new {
+                newly_attached.push(component);
+            }
+        }
+
+        drop(stmt);
+        tx.commit()?;
+
+        for component in newly_attached {
+            self.0.run_attach_hooks(component, self.id());
+        }
+
         Ok(self)
     }
 }
@@ -172,12 +359,10 @@ impl<'a> Entity<'a> {
 #[with_infallible]
 impl<'a> Entity<'a> {
     pub fn try_detach_named(self, component: &str) -> Result<Self, Error> {
-        let mut query = self
-            .0
-            .conn
-            .prepare_cached("delete from components where entity = ?1 and component = ?2")?;
-
-        query.execute(params![self.id(), component])?;
+        let stored = self.0.stored_component_name(component);
+        self.0
+            .delete_components("entity = ?1 and component = ?2", params![self.id(), stored])?
+            .run_hooks(self.0)?;
         Ok(self)
     }
 }
@@ -191,15 +376,68 @@ impl<'a> Entity<'a> {
         .unwrap()
     }
 
-    // TODO: Race Condition; needs refactoring to make Entity generic over
-    // `rusqlite::Connection` and `rusqlite::Transaction`
+    /// Reads `C` (or its default), applies `f` and attaches the result. Read
+    /// and write happen in one transaction, so concurrent modifications from
+    /// other connections aren't lost.
     pub fn try_modify_component<C: Component + Default>(
         self,
         f: impl FnOnce(&mut C) -> Result<(), anyhow::Error>,
     ) -> Result<Self, ModifyComponentError> {
+        let tx = self.0.transaction()?;
         let mut component = self.try_component()?.unwrap_or_default();
         f(&mut component).map_err(ModifyComponentError::Fn)?;
-        Ok(self.try_attach(component)?)
+        let entity = self.try_attach(component)?;
+        tx.commit()?;
+        Ok(entity)
+    }
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Applies the JSON merge patch `patch` (RFC 7396) to the stored data of
+    /// `C` in SQL via `json_patch`, without reading the component first. The
+    /// update is rolled back if the result doesn't deserialize as `C`. Does
+    /// nothing if the entity doesn't have `C`.
+    ///
+    /// Goes through the same checks as [`Self::try_attach`]: `C` has to be
+    /// registered in strict mode, and the write is retried per
+    /// [`crate::RetryPolicy`]. Like overwriting a component, it's reported to
+    /// [`Ecs::on_change`](crate::Ecs::on_change) callbacks, but doesn't fire
+    /// [`Ecs::on_attach`](crate::Ecs::on_attach) hooks.
+    #[tracing::instrument(name = "patch", level = "debug", skip_all)]
+    pub fn try_patch_component<C: Component>(
+        self,
+        patch: serde_json::Value,
+    ) -> Result<Self, Error> {
+        self.0.check_registered::<C>()?;
+
+        match self.0.retry {
+            Some(retry) => retry.run(self.0, || self.patch_component_once::<C>(&patch)),
+            None => self.patch_component_once::<C>(&patch),
+        }
+    }
+}
+
+impl<'a> Entity<'a> {
+    fn patch_component_once<C: Component>(self, patch: &serde_json::Value) -> Result<Self, Error> {
+        let tx = self.0.transaction()?;
+        let patched = tx
+            .prepare_cached(
+                "update components set data = json_patch(data, ?1) where entity = ?2 and component = ?3",
+            )?
+            .execute(params![
+                patch.to_string(),
+                self.id(),
+                self.0.stored_component_name(C::NAME)
+            ])?;
+
+        if patched > 0 {
+            self.try_component::<C>()?;
+            tx.commit()?;
+            debug!(entity = self.id(), component = C::NAME, "patched");
+        }
+
+        Ok(self)
     }
 }
 
@@ -217,13 +455,43 @@ impl<'a> Entity<'a> {
         let q = query::Query::<(), D, EntityId>::with_filter(self.db(), self.id());
         Ok(q.try_iter()?.next().is_some())
     }
+
+    /// Like [`Self::try_matches`], but checks against a runtime filter value,
+    /// e.g. a component value or a [`query::FilterValueWrapper`].
+    pub fn try_matches_filtered<V: query::QueryFilterValue>(
+        self,
+        filter: V,
+    ) -> Result<bool, Error> {
+        let q = query::Query::<(), (), (EntityId, V)>::with_filter(self.0, (self.id(), filter));
+        Ok(q.try_iter()?.next().is_some())
+    }
 }
 
 #[with_infallible]
 impl<'a> Entity<'a> {
-    #[tracing::instrument(name = "attach", level = "debug", skip_all)]
     pub fn try_attach<B: Bundle>(self, component: B) -> Result<Self, Error> {
-        let components = B::to_rusqlite(&component)?;
+        Ok(self.try_attach_reporting(component)?.0)
+    }
+
+    /// Like [`Self::try_attach`], additionally returning whether any component
+    /// was actually inserted or changed. Attaching a value identical to the
+    /// stored one is a no-op and reports `false`.
+    #[tracing::instrument(name = "attach", level = "debug", skip_all)]
+    pub fn try_attach_reporting<B: Bundle>(self, component: B) -> Result<(Self, bool), Error> {
+        self.0.check_registered::<B>()?;
+
+        match self.0.retry {
+            Some(retry) => retry.run(self.0, || self.attach_reporting_once(&component)),
+            None => self.attach_reporting_once(&component),
+        }
+    }
+
+    fn attach_reporting_once<B: Bundle>(self, component: &B) -> Result<(Self, bool), Error> {
+        // Write in a stable order, independent of the bundle's shape
+        let mut components = self
+            .0
+            .with_storage_context(self.id(), || B::to_rusqlite(component))?;
+        components.sort_by_key(|(name, _)| *name);
 
         let mut stmt = self.0.conn.prepare_cached(
             r#"
@@ -234,40 +502,162 @@ impl<'a> Entity<'a> {
             "#,
         )?;
 
+        let mut changed = false;
+        let mut newly_attached = Vec::new();
         for (component, data) in components {
             trace!(params = ?(self.id(), component, &data));
 
             if let Some(data) = data {
-                let attached_rows = stmt.execute(params![self.id(), component, data])?;
+                let is_new = self.0.hooks.has_attach_hooks(component)
+                    && !self.try_has_all_dynamic(&[component])?;
+
+                let stored = self.0.stored_component_name(component);
+                let attached_rows = stmt.execute(params![self.id(), stored, data])?;
                 if attached_rows > 0 {
                     debug!(entity = self.id(), component, "attached");
+                    changed = true;
                 } else {
                     debug!(entity = self.id(), component, "no-op")
                 }
+
+                if is_new {
+                    newly_attached.push(component);
+                }
             } else {
                 debug!(component, ?data, "skipping None");
             }
         }
 
-        Ok(self)
+        drop(stmt);
+        for component in newly_attached {
+            self.0.run_attach_hooks(component, self.id());
+        }
+
+        Ok((self, changed))
     }
 
     #[tracing::instrument(name = "detach", level = "debug")]
     pub fn try_detach<B: Bundle>(self) -> Result<Self, Error> {
+        let stored = B::COMPONENTS
+            .iter()
+            .map(|c| self.0.stored_component_name(c))
+            .collect::<Vec<_>>();
+        let params = std::iter::once(&self.id() as &dyn rusqlite::ToSql)
+            .chain(stored.iter().map(|c| c as &dyn rusqlite::ToSql));
+
+        let detached = self.0.delete_components(
+            &format!(
+                "entity = ?1 and component in ({})",
+                vec!["?"; stored.len()].join(", ")
+            ),
+            rusqlite::params_from_iter(params),
+        )?;
+        debug!(entity = self.id(), detached = detached.len(), "detached");
+
+        detached.run_hooks(self.0)?;
+        Ok(self)
+    }
+
+    /// Attaches the default value of every component in `B`. Meant for
+    /// bundles of unit markers, e.g. `entity.tag::<(Hidden, Pinned)>()`.
+    pub fn try_tag<B: Bundle + Default>(self) -> Result<Self, Error> {
+        self.try_attach(B::default())
+    }
+
+    /// Counterpart to [`Self::try_tag`]. Same as [`Self::try_detach`].
+    pub fn try_untag<B: Bundle>(self) -> Result<Self, Error> {
+        self.try_detach::<B>()
+    }
+
+    /// Attaches `C::default()` if `C` is absent, detaches it otherwise.
+    /// Returns whether `C` is attached afterwards.
+    pub fn try_toggle<C: Component + Default>(self) -> Result<bool, Error> {
+        if self.try_has::<C>()? {
+            self.try_detach::<C>()?;
+            Ok(false)
+        } else {
+            self.try_attach(C::default())?;
+            Ok(true)
+        }
+    }
+
+    /// Attaches `new` only if the current value of `C` equals `expected`,
+    /// with `None` meaning `C` isn't attached. Returns whether `new` was
+    /// written. Reading and writing happen in one transaction.
+    pub fn try_compare_and_swap<C: Component + PartialEq>(
+        self,
+        expected: Option<C>,
+        new: C,
+    ) -> Result<bool, Error> {
+        let tx = self.0.transaction()?;
+        if self.try_component::<C>()? != expected {
+            return Ok(false);
+        }
+        self.try_attach(new)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Detaches `C`, returning its value if it was attached. Like
+    /// [`HashMap::remove`].
+    pub fn try_take<C: Component>(self) -> Result<Option<C>, Error> {
+        let tx = self.0.transaction()?;
+        let component = self.try_component::<C>()?;
+        if component.is_some() {
+            self.try_detach::<C>()?;
+        }
+        tx.commit()?;
+        Ok(component)
+    }
+}
+
+/// Returned by [`Entity::attach_expecting_version`] if the entity was
+/// modified since the expected version was read
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Entity {entity} is at version {found}, expected {expected}")]
+pub struct ConflictError {
+    pub entity: EntityId,
+    pub expected: i64,
+    pub found: i64,
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Counter bumped by every write to one of the entity's components, 0 if
+    /// it has never been written. It keeps counting after the entity got
+    /// destroyed, so a version is never seen twice. Pass it to
+    /// [`Self::attach_expecting_version`] to detect concurrent edits.
+    pub fn try_version(self) -> Result<i64, Error> {
         let mut stmt = self
             .0
             .conn
-            .prepare_cached("delete from components where entity = ?1 and component = ?2")?;
+            .prepare_cached("select version from entity_versions where entity = ?1")?;
+        let version = stmt.query_row([self.id()], |row| row.get(0)).optional()?;
+        Ok(version.unwrap_or(0))
+    }
+}
 
-        for component in B::COMPONENTS {
-            let deleted_rows = stmt.execute(params![self.id(), component])?;
-            if deleted_rows > 0 {
-                debug!(entity = self.id(), component, "detached");
-            } else {
-                debug!(entity = self.id(), component, "no-op")
+impl<'a> Entity<'a> {
+    /// Attaches `bundle` unless the entity's [`Self::version`] moved past
+    /// `expected`, e.g. because another editor wrote to it in the meantime.
+    /// Fails with [`Error::Conflict`] in that case.
+    pub fn attach_expecting_version<B: Bundle>(
+        self,
+        bundle: B,
+        expected: i64,
+    ) -> Result<Self, Error> {
+        let tx = self.0.transaction()?;
+        let found = self.try_version()?;
+        if found != expected {
+            return Err(ConflictError {
+                entity: self.id(),
+                expected,
+                found,
             }
+            .into());
         }
-
+        self.try_attach(bundle)?;
+        tx.commit()?;
         Ok(self)
     }
 }
@@ -276,15 +666,30 @@ impl<'a> Entity<'a> {
 impl<'a> Entity<'a> {
     #[tracing::instrument(name = "detach_all", level = "debug")]
     pub fn try_detach_all(self) -> Result<Self, Error> {
-        self
-            .0
-            .conn
-            .execute("delete from components where entity = ?1 and component not in (select component from system_components)", params![self.id()])?;
+        self.0
+            .delete_components(
+                "entity = ?1 and component not in (select component from system_components)",
+                [self.id()],
+            )?
+            .run_hooks(self.0)?;
 
         Ok(self)
     }
 }
 
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// The entity id tagged with the [`Ecs::db_id`] of its database. Resolve
+    /// it via [`Ecs::entity_checked`] to catch ids used with the wrong
+    /// database.
+    pub fn try_tagged_id(self) -> Result<TaggedEntityId, Error> {
+        Ok(TaggedEntityId {
+            db: self.0.db_id()?,
+            id: self.id(),
+        })
+    }
+}
+
 impl<'a> Entity<'a> {
     pub fn or_none(self) -> Option<Self> {
         self.exists().then_some(self)
@@ -293,40 +698,58 @@ impl<'a> Entity<'a> {
 
 #[with_infallible]
 impl<'a> NewEntity<'a> {
+    /// Creates the entity with `bundle` attached. Fails with
+    /// [`Error::EmptyBundle`] if every component of `bundle` is `None`.
     #[tracing::instrument(name = "attach", level = "debug", skip_all)]
     pub fn try_attach<B: NonEmptyBundle>(
         self,
         bundle: B,
     ) -> Result<GenericEntity<'a, WithEntityId>, Error> {
-        let data = B::to_rusqlite(&bundle)?;
+        self.0.check_registered::<B>()?;
 
-        let mut stmt = self.0.conn.prepare_cached(
+        // The id is needed to encode the bundle. It's rolled back along with
+        // the transaction if the bundle turns out to be empty.
+        let tx = self.0.transaction()?;
+        let eid = self.0.allocate_entity_id()?;
+
+        let mut data = self
+            .0
+            .with_storage_context(eid, || B::to_rusqlite(&bundle))?;
+        data.sort_by_key(|(name, _)| *name);
+
+        let mut stmt = tx.prepare_cached(
             r#"
             insert into components (entity, component, data)
-            values ((select coalesce(?1, max(entity)+1, 100) from components), ?2, ?3)
+            values (?1, ?2, ?3)
             on conflict (entity, component) do update set data = excluded.data
-            returning entity
             "#,
         )?;
 
-        let mut eid = None;
+        let mut attached = Vec::new();
         for (component, data) in data {
             trace!(params = ?(eid, component, &data));
 
             if let Some(data) = data {
-                eid = Some(stmt.query_row(params![eid, component, data], |row| {
-                    row.get::<_, EntityId>("entity")
-                })?);
+                stmt.execute(params![eid, self.0.stored_component_name(component), data])?;
 
-                debug!(entity = eid.unwrap(), component, "attached");
+                debug!(entity = eid, component, "attached");
+                if !attached.contains(&component) {
+                    attached.push(component);
+                }
             } else {
                 debug!(component, ?data, "skipping None");
             }
         }
 
-        let Some(eid) = eid else {
-            panic!("Bundle::to_rusqlite returned zero rows. That shouldn't happen.")
-        };
+        if attached.is_empty() {
+            return Err(Error::EmptyBundle);
+        }
+
+        drop(stmt);
+        tx.commit()?;
+        for component in attached {
+            self.0.run_attach_hooks(component, eid);
+        }
 
         let entity = GenericEntity(self.0, WithEntityId(eid));
 
@@ -338,6 +761,16 @@ impl<'a> NewEntity<'a> {
         Ok(self)
     }
 
+    /// Attaches components only known at runtime to a newly allocated entity.
+    /// See [`Entity::try_dyn_attach_all`].
+    pub fn try_dyn_attach_all(
+        self,
+        components: impl IntoIterator<Item = DynComponent<'a>>,
+    ) -> Result<Entity<'a>, Error> {
+        let entity = Entity::with_id(self.0, self.0.allocate_entity_id()?);
+        entity.try_dyn_attach_all(components)
+    }
+
     #[tracing::instrument(name = "component_names", level = "debug")]
     pub fn try_component_names(self) -> Result<impl Iterator<Item = String>, Error> {
         Ok(std::iter::empty())
@@ -353,8 +786,6 @@ impl<'a> NewEntity<'a> {
         .unwrap()
     }
 
-    // TODO: Race Condition; needs refactoring to make Entity generic over
-    // `rusqlite::Connection` and `rusqlite::Transaction`
     pub fn try_modify_component<C: Component + Default>(
         self,
         f: impl FnOnce(&mut C) -> Result<(), anyhow::Error>,
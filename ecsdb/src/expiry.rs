@@ -0,0 +1,87 @@
+use ecsdb_derive::with_infallible;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{self as ecsdb, Component, Ecs, Entity, Error, query};
+
+/// Point in time after which [`Ecs::sweep_expired`] destroys the entity
+#[derive(Serialize, Deserialize, Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[component(name = "ecsdb::Expiry")]
+pub struct Expiry(pub chrono::DateTime<chrono::Utc>);
+
+/// Relative [`Expiry`], counted from the moment of conversion:
+/// `entity.attach(Expiry::from(ExpiresIn(duration)))`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExpiresIn(pub chrono::Duration);
+
+impl From<ExpiresIn> for Expiry {
+    fn from(ExpiresIn(duration): ExpiresIn) -> Self {
+        Self(chrono::Utc::now() + duration)
+    }
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Attaches an [`Expiry`] `duration` from now
+    pub fn try_expire_in(self, duration: chrono::Duration) -> Result<Self, Error> {
+        self.try_attach(Expiry::from(ExpiresIn(duration)))
+    }
+}
+
+#[with_infallible]
+impl Ecs {
+    /// Destroys all entities whose [`Expiry`] has passed. Returns the number
+    /// of destroyed entities. Meant to be called periodically, e.g. from a
+    /// scheduled system.
+    #[tracing::instrument(name = "sweep_expired", level = "debug", skip(self))]
+    pub fn try_sweep_expired(&self) -> Result<usize, Error> {
+        let now = chrono::Utc::now();
+        let expired = query::Query::<(Entity, Expiry)>::new(self)
+            .include_deleted()
+            .try_iter()?
+            .filter(|(_, Expiry(expires_at))| *expires_at <= now)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+
+        for entity in &expired {
+            entity.try_destroy()?;
+        }
+
+        debug!(swept = expired.len());
+        Ok(expired.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{ExpiresIn, Expiry};
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[test]
+    fn sweep_expired() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let short = ecs.new_entity().attach((
+            A,
+            Expiry::from(ExpiresIn(chrono::Duration::milliseconds(10))),
+        ));
+        let long = ecs
+            .new_entity()
+            .attach(A)
+            .expire_in(chrono::Duration::hours(1));
+        let forever = ecs.new_entity().attach(A);
+
+        assert_eq!(ecs.sweep_expired(), 0);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(ecs.sweep_expired(), 1);
+
+        assert!(!short.exists());
+        assert!(long.exists());
+        assert!(forever.exists());
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::Write;
+
+use tracing::warn;
+
+use crate::{Component, Ecs, EntityId, Error};
+
+impl Ecs {
+    /// Writes all instances of component `C` as CSV to `writer`. The first
+    /// column is `entity`, followed by one column per field of `C`, in the
+    /// order they're stored in. Fields missing from some instances are left
+    /// empty there. Components serializing to a scalar get a single `value`
+    /// column. Nested values are written as JSON. Nothing is written if no
+    /// entity has `C`.
+    pub fn export_component_csv<C: Component>(&self, mut writer: impl Write) -> Result<(), Error> {
+        let mut stmt = self
+            .conn
+            .prepare("select entity, data from components where component = ?1 order by entity")?;
+        let mut rows = stmt.query([self.stored_component_name(C::NAME)])?;
+
+        // The header needs the fields of all rows, so rows are collected
+        // before anything is written
+        let mut columns = Vec::<String>::new();
+        let mut records = Vec::new();
+        while let Some(row) = rows.next()? {
+            let entity: EntityId = row.get(0)?;
+            let fields = match row.get::<_, Option<String>>(1) {
+                Ok(Some(json)) => match json_fields(&json) {
+                    Ok(fields) => fields,
+                    Err(e) => {
+                        warn!(entity, component = C::NAME, error = %e, "Skipping invalid JSON");
+                        continue;
+                    }
+                },
+                Ok(None) => vec![("value".to_owned(), serde_json::Value::Null)],
+                Err(e) => {
+                    warn!(entity, component = C::NAME, error = %e, "Skipping non-JSON row");
+                    continue;
+                }
+            };
+
+            for (name, _) in &fields {
+                if !columns.contains(name) {
+                    columns.push(name.clone());
+                }
+            }
+            records.push((entity, fields));
+        }
+
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        write_record(
+            &mut writer,
+            std::iter::once("entity".to_owned()).chain(columns.iter().cloned()),
+        )?;
+
+        for (entity, fields) in records {
+            let values = columns.iter().map(|c| {
+                fields
+                    .iter()
+                    .find(|(name, _)| name == c)
+                    .map(|(_, value)| csv_field(value))
+                    .unwrap_or_default()
+            });
+            write_record(
+                &mut writer,
+                std::iter::once(entity.to_string()).chain(values),
+            )?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The fields of the JSON object `json` in document order, which
+/// `serde_json::Map` doesn't keep. Anything else is a single `value` field.
+fn json_fields(json: &str) -> Result<Vec<(String, serde_json::Value)>, serde_json::Error> {
+    struct Fields(Vec<(String, serde_json::Value)>);
+
+    impl<'de> serde::Deserialize<'de> for Fields {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+
+            impl<'de> serde::de::Visitor<'de> for Visitor {
+                type Value = Fields;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a JSON object")
+                }
+
+                fn visit_map<A: serde::de::MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> Result<Fields, A::Error> {
+                    let mut fields = Vec::new();
+                    while let Some(field) = map.next_entry()? {
+                        fields.push(field);
+                    }
+                    Ok(Fields(fields))
+                }
+            }
+
+            deserializer.deserialize_map(Visitor)
+        }
+    }
+
+    match serde_json::from_str::<serde_json::Value>(json)? {
+        serde_json::Value::Object(_) => Ok(serde_json::from_str::<Fields>(json)?.0),
+        value => Ok(vec![("value".to_owned(), value)]),
+    }
+}
+
+fn csv_field(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn write_record(
+    writer: &mut impl Write,
+    fields: impl Iterator<Item = String>,
+) -> Result<(), std::io::Error> {
+    let line = fields
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(writer, "{line}")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Item {
+        name: String,
+        price: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Score(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Contact {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        email: Option<String>,
+        age: u32,
+    }
+
+    #[test]
+    fn export_component_csv() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(Item {
+            name: "apple".into(),
+            price: 3,
+        });
+        let b = db.new_entity().attach(Item {
+            name: "pear, ripe".into(),
+            price: 5,
+        });
+        let c = db.new_entity().attach(Score(7));
+
+        let mut out = Vec::new();
+        db.export_component_csv::<Item>(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "entity,name,price\n{},apple,3\n{},\"pear, ripe\",5\n",
+                a.id(),
+                b.id()
+            )
+        );
+
+        let mut out = Vec::new();
+        db.export_component_csv::<Score>(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("entity,value\n{},7\n", c.id())
+        );
+    }
+
+    #[test]
+    fn export_columns_in_field_order() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(Contact {
+            name: "a".into(),
+            email: None,
+            age: 30,
+        });
+        let b = db.new_entity().attach(Contact {
+            name: "b".into(),
+            email: Some("b@example.com".into()),
+            age: 40,
+        });
+
+        let mut out = Vec::new();
+        db.export_component_csv::<Contact>(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "entity,name,age,email\n{},a,30,\n{},b,40,b@example.com\n",
+                a.id(),
+                b.id()
+            )
+        );
+    }
+}
@@ -1,13 +1,18 @@
 use tracing::{instrument, warn};
 
-use crate::{Ecs, SystemParam};
+use crate::{Ecs, Error, SystemParam, savepoint::quote_identifier};
 
 #[derive(Debug, thiserror::Error)]
 #[error("Extension already registered")]
 pub struct ExtensionExistsError;
 
 pub trait Extension: Send + Sync {
-    // fn create(ecs: &Ecs) -> Self;
+    /// Called once by [`Ecs::register_extension`], before the extension is
+    /// stored. Extensions can set up their tables or resources here. An error
+    /// fails the registration.
+    fn init(&mut self, _ecs: &Ecs) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl<E: Extension + 'static> SystemParam for &E {
@@ -16,17 +21,22 @@ impl<E: Extension + 'static> SystemParam for &E {
     fn get_param<'world>(world: &'world Ecs, _system: &str) -> Self::Item<'world> {
         world.extension::<E>()
     }
+
+    fn try_get_param<'world>(world: &'world Ecs, _system: &str) -> Option<Self::Item<'world>> {
+        world.try_extension::<E>()
+    }
 }
 
 impl Ecs {
     #[instrument(level = "debug", skip_all, fields(extension = std::any::type_name::<E>()), ret, err)]
     pub fn register_extension<E: Extension + 'static>(
         &mut self,
-        extension: E,
-    ) -> Result<(), ExtensionExistsError> {
+        mut extension: E,
+    ) -> Result<(), Error> {
         if self.extensions.contains::<E>() {
-            Err(ExtensionExistsError)
+            Err(ExtensionExistsError.into())
         } else {
+            extension.init(self)?;
             self.extensions.insert(extension);
             Ok(())
         }
@@ -46,9 +56,102 @@ impl Ecs {
     }
 }
 
+/// Virtual table mirroring the data of one component, kept in sync by
+/// triggers on `components`. Used by [`crate::fts::Fts`] and
+/// [`crate::spatial::SpatialIndex`].
+pub(crate) struct ComponentIndex<'a> {
+    /// Unquoted table name, also the prefix of the trigger names
+    pub table: String,
+    /// [`crate::Component::NAME`] of the indexed component
+    pub component: &'static str,
+    /// Module and arguments of the virtual table, e.g. `fts5(body)`
+    pub module: &'a str,
+    /// Column holding the entity id
+    pub key: &'a str,
+    /// Columns filled from the component data
+    pub columns: &'a str,
+    /// Expressions for `columns`, with `{data}` standing in for the data
+    pub values: &'a str,
+}
+
+impl ComponentIndex<'_> {
+    /// Creates the table and its triggers, and indexes the existing
+    /// components if the table didn't exist yet. Returns the number of
+    /// backfilled rows.
+    pub(crate) fn create(&self, ecs: &Ecs) -> Result<usize, Error> {
+        let ComponentIndex {
+            key,
+            columns,
+            module,
+            ..
+        } = self;
+        let table = quote_identifier(&self.table);
+        let trigger = |kind: &str| quote_identifier(&format!("{}_{kind}", self.table));
+        let values = |data: &str| self.values.replace("{data}", data);
+        let stored = ecs.stored_component_name(self.component);
+        let component = stored.replace('\'', "''");
+
+        let exists = ecs
+            .conn
+            .prepare("select 1 from sqlite_master where type = 'table' and name = ?1")?
+            .exists([&self.table])?;
+
+        let tx = ecs.transaction()?;
+        tx.execute_batch(&format!(
+            r#"
+            create virtual table if not exists {table} using {module};
+
+            create trigger if not exists {insert_trigger}
+            after insert on components
+            for each row when new.component = '{component}'
+            begin
+                delete from {table} where {key} = new.entity;
+                insert into {table} ({key}, {columns}) values (new.entity, {new_values});
+            end;
+
+            create trigger if not exists {update_trigger}
+            after update on components
+            for each row when new.component = '{component}'
+            begin
+                delete from {table} where {key} = old.entity;
+                insert into {table} ({key}, {columns}) values (new.entity, {new_values});
+            end;
+
+            create trigger if not exists {delete_trigger}
+            after delete on components
+            for each row when old.component = '{component}'
+            begin
+                delete from {table} where {key} = old.entity;
+            end;
+            "#,
+            insert_trigger = trigger("insert"),
+            update_trigger = trigger("update"),
+            delete_trigger = trigger("delete"),
+            new_values = values("new.data"),
+        ))?;
+
+        let indexed = if exists {
+            0
+        } else {
+            tx.execute(
+                &format!(
+                    "insert into {table} ({key}, {columns}) select entity, {} from components where component = ?1",
+                    values("data")
+                ),
+                [stored],
+            )?
+        };
+
+        tx.commit()?;
+        Ok(indexed)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{Ecs, Extension};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{self as ecsdb, Component, Ecs, Error, Extension};
 
     #[test]
     fn extension() {
@@ -67,6 +170,49 @@ mod test {
         assert!(ecs.try_extension::<Unregistered>().is_none());
     }
 
+    #[test]
+    fn extension_init() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Initialized(u32);
+
+        struct Test(u32);
+        impl Extension for Test {
+            fn init(&mut self, ecs: &Ecs) -> Result<(), Error> {
+                ecs.try_attach_resource(Initialized(self.0))?;
+                self.0 += 1;
+                Ok(())
+            }
+        }
+
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.register_extension(Test(1)).unwrap();
+
+        assert_eq!(ecs.resource::<Initialized>().unwrap().0, 1);
+        assert_eq!(ecs.extension::<Test>().0, 2);
+        assert!(matches!(
+            ecs.register_extension(Test(1)),
+            Err(Error::ExtensionExists(_))
+        ));
+    }
+
+    #[test]
+    fn extension_init_error() {
+        struct Failing;
+        impl Extension for Failing {
+            fn init(&mut self, ecs: &Ecs) -> Result<(), Error> {
+                ecs.raw_sql().execute_batch("not sql")?;
+                Ok(())
+            }
+        }
+
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        assert!(matches!(
+            ecs.register_extension(Failing),
+            Err(Error::Database(_))
+        ));
+        assert!(ecs.try_extension::<Failing>().is_none());
+    }
+
     #[test]
     fn extension_system_param() {
         struct Test(i32);
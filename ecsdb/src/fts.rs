@@ -0,0 +1,164 @@
+use std::marker::PhantomData;
+
+use ecsdb_derive::with_infallible;
+use tracing::debug;
+
+use crate::{
+    Component, Ecs, Entity, EntityId, Error, Extension, extension::ComponentIndex,
+    savepoint::quote_identifier,
+};
+
+/// Full-text search over a text component, backed by an SQLite FTS5 table.
+///
+/// On registration the index table and the triggers keeping it in sync with
+/// the component are created, and existing components are indexed. Both
+/// persist in the database. Each component gets its own index.
+///
+/// ```
+/// use ecsdb::{Component, Ecs, fts::Fts};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, Component)]
+/// struct Body(String);
+///
+/// let mut ecs = Ecs::open_in_memory()?;
+/// ecs.register_extension(Fts::<Body>::new())?;
+///
+/// let e = ecs.new_entity().attach(Body("Hello World".into()));
+/// let found = ecs.extension::<Fts<Body>>().search(&ecs, "world");
+/// assert_eq!(found.map(|e| e.id()).collect::<Vec<_>>(), [e.id()]);
+/// # Ok::<(), ecsdb::Error>(())
+/// ```
+pub struct Fts<C> {
+    component: PhantomData<fn() -> C>,
+}
+
+impl<C: Component> Fts<C> {
+    /// Indexes component `C`, which has to be stored as a JSON string
+    pub fn new() -> Self {
+        Self {
+            component: PhantomData,
+        }
+    }
+
+    fn table() -> String {
+        format!("fts_{}", C::NAME)
+    }
+}
+
+impl<C: Component> Default for Fts<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Clone for Fts<C> {
+    fn clone(&self) -> Self {
+        Self {
+            component: PhantomData,
+        }
+    }
+}
+
+impl<C: Component> std::fmt::Debug for Fts<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Fts").field("component", &C::NAME).finish()
+    }
+}
+
+impl<C: Component> Extension for Fts<C> {
+    fn init(&mut self, ecs: &Ecs) -> Result<(), Error> {
+        let indexed = ComponentIndex {
+            table: Self::table(),
+            component: C::NAME,
+            module: "fts5(body)",
+            key: "rowid",
+            columns: "body",
+            values: "json_extract({data}, '$')",
+        }
+        .create(ecs)?;
+
+        debug!(component = C::NAME, indexed, "created full-text index");
+        Ok(())
+    }
+}
+
+#[with_infallible]
+impl<C: Component> Fts<C> {
+    /// Entities whose component matches the FTS5 `query`, best matches first.
+    /// See <https://www.sqlite.org/fts5.html#full_text_query_syntax> for the
+    /// query syntax.
+    pub fn try_search<'a>(
+        &self,
+        ecs: &'a Ecs,
+        query: &str,
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        let mut stmt = ecs.conn.prepare_cached(&format!(
+            "select rowid from {} where {0} match ?1 order by rank",
+            quote_identifier(&Self::table())
+        ))?;
+
+        let ids = stmt
+            .query_map([query], |row| row.get::<_, EntityId>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ids.into_iter().map(|id| ecs.entity(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Fts;
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Body(String);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Title(String);
+
+    #[test]
+    fn search() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        let existing = ecs
+            .new_entity()
+            .attach(Body("the quick brown fox".into()))
+            .id();
+
+        ecs.register_extension(Fts::<Body>::new()).unwrap();
+        ecs.register_extension(Fts::<Title>::new()).unwrap();
+
+        let lazy = ecs.new_entity().attach(Body("a lazy dog".into()));
+        let both = ecs
+            .new_entity()
+            .attach(Body("the fox jumps over the dog".into()));
+        let title = ecs.new_entity().attach(Title("dog".into()));
+
+        let search = |query| {
+            let mut ids = ecs
+                .extension::<Fts<Body>>()
+                .search(&ecs, query)
+                .map(|e| e.id())
+                .collect::<Vec<EntityId>>();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(search("dog"), [lazy.id(), both.id()]);
+        assert_eq!(search("fox"), [existing, both.id()]);
+
+        let titles = ecs
+            .extension::<Fts<Title>>()
+            .search(&ecs, "dog")
+            .map(|e| e.id())
+            .collect::<Vec<EntityId>>();
+        assert_eq!(titles, [title.id()]);
+
+        lazy.attach(Body("a sleepy cat".into()));
+        both.destroy();
+        assert!(search("dog").is_empty());
+        assert_eq!(search("cat"), [lazy.id()]);
+    }
+}
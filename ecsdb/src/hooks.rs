@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::functions::FunctionFlags;
+use tracing::debug;
+
+use crate::{Component, Ecs, EntityId, Error};
+
+type Hook = Box<dyn Fn(&Ecs, EntityId) + Send>;
+type ChangeHook = Box<dyn FnMut(ChangeAction, EntityId, &str) + Send>;
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    on_attach: HashMap<&'static str, Vec<Hook>>,
+    on_detach: HashMap<&'static str, Vec<Hook>>,
+    on_change: Option<Arc<Mutex<Vec<ChangeHook>>>>,
+}
+
+/// Kind of row change reported to [`Ecs::on_change`] callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Hooks {
+    pub(crate) fn has_attach_hooks(&self, component: &str) -> bool {
+        self.on_attach.contains_key(component)
+    }
+
+    pub(crate) fn has_detach_hooks(&self) -> bool {
+        !self.on_detach.is_empty()
+    }
+}
+
+impl Ecs {
+    /// Registers `f` to be called after `C` got attached to an entity that
+    /// didn't have it before. Overwriting an existing `C` doesn't fire.
+    ///
+    /// Hooks run synchronously after the write. A hook attaching components
+    /// itself will trigger further hooks.
+    pub fn on_attach<C: Component>(&mut self, f: impl Fn(&Ecs, EntityId) + Send + 'static) {
+        self.hooks
+            .on_attach
+            .entry(C::NAME)
+            .or_default()
+            .push(Box::new(f));
+    }
+
+    /// Registers `f` to be called after `C` got detached from an entity,
+    /// either via `detach` or `destroy`.
+    pub fn on_detach<C: Component>(&mut self, f: impl Fn(&Ecs, EntityId) + Send + 'static) {
+        self.hooks
+            .on_detach
+            .entry(C::NAME)
+            .or_default()
+            .push(Box::new(f));
+    }
+
+    /// Registers `f` to be called for every insert, update and delete of a
+    /// non-system component, including writes done by triggers and raw SQL.
+    ///
+    /// Unlike [`Self::on_attach`], `f` runs synchronously *inside* the write,
+    /// before the statement (or transaction) completes, and therefore can't
+    /// access the database.
+    pub fn on_change(
+        &mut self,
+        f: impl FnMut(ChangeAction, EntityId, &str) + Send + 'static,
+    ) -> Result<(), Error> {
+        if let Some(hooks) = &self.hooks.on_change {
+            hooks.lock().unwrap().push(Box::new(f));
+            return Ok(());
+        }
+
+        let hooks: Arc<Mutex<Vec<ChangeHook>>> = Arc::new(Mutex::new(vec![Box::new(f)]));
+
+        // `Connection::update_hook` only reports rowids, which can't be
+        // resolved to entity and component from within the hook. Per-connection
+        // temp triggers calling a function get us the full row instead.
+        self.conn
+            .create_scalar_function("ecsdb_on_change", 3, FunctionFlags::SQLITE_UTF8, {
+                let hooks = hooks.clone();
+                move |ctx| {
+                    let action = match ctx.get::<String>(0)?.as_str() {
+                        "insert" => ChangeAction::Insert,
+                        "update" => ChangeAction::Update,
+                        _ => ChangeAction::Delete,
+                    };
+                    let entity = ctx.get::<EntityId>(1)?;
+                    let component = ctx.get::<String>(2)?;
+
+                    for hook in hooks.lock().unwrap().iter_mut() {
+                        hook(action, entity, &component);
+                    }
+
+                    Ok(rusqlite::types::Value::Null)
+                }
+            })?;
+
+        self.conn.execute_batch(
+            r#"
+            create temp trigger if not exists ecsdb_on_change_insert_trigger
+            after insert on components
+            for each row when new.component not in (select component from system_components)
+            begin
+                select ecsdb_on_change('insert', new.entity, new.component);
+            end;
+
+            create temp trigger if not exists ecsdb_on_change_update_trigger
+            after update on components
+            for each row when new.component not in (select component from system_components)
+            begin
+                select ecsdb_on_change('update', new.entity, new.component);
+            end;
+
+            create temp trigger if not exists ecsdb_on_change_delete_trigger
+            after delete on components
+            for each row when old.component not in (select component from system_components)
+            begin
+                select ecsdb_on_change('delete', old.entity, old.component);
+            end;
+            "#,
+        )?;
+
+        self.hooks.on_change = Some(hooks);
+        debug!("installed on_change hook");
+
+        Ok(())
+    }
+
+    pub(crate) fn run_attach_hooks(&self, component: &str, entity: EntityId) {
+        let component = self.unaliased_component_name(component);
+        for hook in self.hooks.on_attach.get(component).into_iter().flatten() {
+            debug!(component, entity, "running on_attach hook");
+            hook(self, entity);
+        }
+    }
+
+    fn run_detach_hooks(&self, component: &str, entity: EntityId) {
+        let component = self.unaliased_component_name(component);
+        for hook in self.hooks.on_detach.get(component).into_iter().flatten() {
+            debug!(component, entity, "running on_detach hook");
+            hook(self, entity);
+        }
+    }
+
+    /// Deletes the components matching `condition`, the `where` clause of a
+    /// `delete from components`. Every detach path goes through here, so that
+    /// detach hooks fire the same way for all of them.
+    pub(crate) fn delete_components(
+        &self,
+        condition: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Detached, Error> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "delete from components where {condition} returning entity, component"
+        ))?;
+        let deleted = stmt
+            .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Detached(deleted))
+    }
+}
+
+/// Components deleted by [`Ecs::delete_components`], as `(entity, stored
+/// name)`. Their detach hooks still have to run via [`Self::run_hooks`].
+#[must_use]
+#[derive(Default)]
+pub(crate) struct Detached(Vec<(EntityId, String)>);
+
+impl Detached {
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn append(&mut self, mut other: Detached) {
+        self.0.append(&mut other.0);
+    }
+
+    /// Runs the detach hooks of the deleted components, except for system
+    /// components like `CreatedAt`. Call once the delete got committed.
+    pub(crate) fn run_hooks(self, ecs: &Ecs) -> Result<(), Error> {
+        if !ecs.hooks.has_detach_hooks() || self.0.is_empty() {
+            return Ok(());
+        }
+
+        let system = ecs
+            .conn
+            .prepare_cached("select component from system_components")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        for (entity, component) in self.0 {
+            if !system.contains(&component) {
+                ecs.run_detach_hooks(&component, entity);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use super::ChangeAction;
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[test]
+    fn attach_detach_hooks() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+
+        let attached = Arc::new(AtomicUsize::new(0));
+        let detached = Arc::new(AtomicUsize::new(0));
+
+        ecs.on_attach::<A>({
+            let attached = attached.clone();
+            move |_ecs, _eid| {
+                attached.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        ecs.on_detach::<A>({
+            let detached = detached.clone();
+            move |_ecs, _eid| {
+                detached.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let e = ecs.new_entity().attach(A(1));
+        assert_eq!(attached.load(Ordering::SeqCst), 1);
+
+        // Overwriting isn't a structural change
+        e.attach(A(2)).attach(B);
+        assert_eq!(attached.load(Ordering::SeqCst), 1);
+
+        e.detach::<A>();
+        assert_eq!(detached.load(Ordering::SeqCst), 1);
+
+        e.detach::<A>();
+        assert_eq!(detached.load(Ordering::SeqCst), 1);
+
+        e.attach(A(3));
+        assert_eq!(attached.load(Ordering::SeqCst), 2);
+
+        e.destroy();
+        assert_eq!(detached.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn on_change() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        ecs.on_change({
+            let changes = changes.clone();
+            move |action, entity, component| {
+                changes
+                    .lock()
+                    .unwrap()
+                    .push((action, entity, component.to_string()));
+            }
+        })
+        .unwrap();
+
+        let e = ecs.new_entity().attach(A(1));
+        e.attach(A(2));
+        e.detach::<A>();
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (ChangeAction::Insert, e.id(), A::NAME.to_string()),
+                (ChangeAction::Update, e.id(), A::NAME.to_string()),
+                (ChangeAction::Delete, e.id(), A::NAME.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_detach_path_fires_hooks() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+
+        let detached = Arc::new(Mutex::new(Vec::new()));
+        ecs.on_detach::<A>({
+            let detached = detached.clone();
+            move |_ecs, eid| detached.lock().unwrap().push(eid)
+        });
+        ecs.on_detach::<crate::CreatedAt>(|_ecs, _eid| panic!("system component"));
+
+        let attached = Arc::new(AtomicUsize::new(0));
+        ecs.on_attach::<A>({
+            let attached = attached.clone();
+            move |_ecs, _eid| {
+                attached.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let a = ecs.new_entity().attach(A(1)).detach_all();
+        let b = ecs.new_entity().attach(A(2)).detach_named(A::NAME);
+        let c = ecs.new_entity().attach((A(3), B));
+        ecs.detach_many::<A>(&[c.id()]).unwrap();
+        let d = ecs.new_entity().attach(A(4));
+        ecs.remove_component_everywhere::<A>().unwrap();
+        let e = ecs.new_entity().attach(A(5));
+        e.destroy();
+
+        assert_eq!(
+            *detached.lock().unwrap(),
+            [a.id(), b.id(), c.id(), d.id(), e.id()]
+        );
+        assert_eq!(attached.load(Ordering::SeqCst), 5);
+
+        let component = crate::DynComponent::from_json(A::NAME, &serde_json::json!(6)).unwrap();
+        ecs.new_entity().attach(B).dyn_attach(component);
+        assert_eq!(attached.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn reentrant_hook() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.on_attach::<A>(|ecs, eid| {
+            ecs.entity(eid).attach(B);
+        });
+
+        let e = ecs.new_entity().attach(A(1));
+        assert!(e.has::<B>());
+    }
+}
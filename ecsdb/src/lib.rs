@@ -1,28 +1,81 @@
+#[cfg(feature = "async")]
+pub mod async_ecs;
+#[cfg(feature = "async")]
+pub use async_ecs::AsyncEcs;
+
+pub mod changelog;
+
 pub mod component;
 
+pub mod component_mask;
+
 pub use component::Bundle;
 pub use component::{Component, ComponentRead, ComponentWrite, Resource};
 
+pub mod diff;
+pub use diff::EntityDiff;
+
 pub mod dyn_component;
 pub use dyn_component::DynComponent;
 
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::Encrypted;
+
 pub mod entity;
 use ecsdb_derive::with_infallible;
-pub use entity::{Entity, NewEntity};
+pub use entity::{ConflictError, Entity, NewEntity};
+
+mod export;
+
+pub mod expiry;
+pub use expiry::{ExpiresIn, Expiry};
 
 pub mod extension;
 pub use extension::Extension;
 
+pub mod fts;
+
 pub mod hierarchy;
 
+mod hooks;
+pub use hooks::ChangeAction;
+
+pub mod multi_component;
+pub use multi_component::MultiComponent;
+
+pub mod options;
+pub use options::{EcsOptions, RetryPolicy};
+
+pub mod prefab;
+pub use prefab::Prefab;
+
 pub mod query;
 
+mod registry;
+pub use registry::ComponentNaming;
+
+pub mod reference;
+pub use reference::Reference;
+
 pub mod resource;
-pub use resource::ResourceProxy;
+pub use resource::{Res, ResourceEntry, ResourceProxy};
+
+pub mod savepoint;
+pub use savepoint::Savepoint;
 
 pub mod schedule;
 pub use schedule::Schedule;
 
+pub mod snapshot;
+pub use snapshot::EntitySnapshot;
+
+pub mod soft_delete;
+pub use soft_delete::Deleted;
+
+pub mod spatial;
+
 pub mod sqlite_ext;
 
 pub mod system;
@@ -37,27 +90,112 @@ pub use system::*;
 
 mod tuple_macros;
 
+#[cfg(test)]
+mod test_util;
+
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 pub type EntityId = i64;
 
+/// An [`EntityId`] together with the [`Ecs::db_id`] of the database it
+/// belongs to, see [`Entity::tagged_id`] and [`Ecs::entity_checked`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaggedEntityId {
+    pub db: uuid::Uuid,
+    pub id: EntityId,
+}
+
 /// Reserved entity ID for storing resources (world-level singletons).
 pub const WORLD_ENTITY: EntityId = 0;
 
+/// Schema version stored in `pragma user_version`. Databases with an older
+/// version are migrated on open, newer ones are rejected.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Migrations applied after `schema.sql`, in order. Entry `n` migrates a
+/// database from version `n` to `n + 1` and runs in a transaction together
+/// with the `user_version` update, so it must not contain `begin`/`commit`.
+/// Version 0 databases predate versioning, so the first one has to be
+/// idempotent. Columns added here must not be part of `schema.sql`. The file
+/// numbers in `migrations/` continue the unversioned migrations `01` to `03`,
+/// so `04` migrates to version 1 and `05` to version 2.
+const MIGRATIONS: &[Migration] = &[
+    |conn| conn.execute_batch(include_str!("migrations/04_entities.sql")),
+    migrate_entities_created_at,
+];
+
+type Migration = fn(&rusqlite::Connection) -> Result<(), rusqlite::Error>;
+
+/// Schema version 2. SQLite has no `add column if not exists`, so the column
+/// is only added if missing, keeping the migration re-runnable.
+fn migrate_entities_created_at(conn: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+    let has_column = conn
+        .prepare("select 1 from pragma_table_info('entities') where name = 'created_at'")?
+        .exists([])?;
+    if !has_column {
+        conn.execute_batch("alter table entities add column created_at text")?;
+    }
+
+    conn.execute_batch(include_str!("migrations/05_entities_created_at.sql"))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Database Error: {0}")]
     Database(#[from] rusqlite::Error),
     #[error(transparent)]
     ComponentStorage(#[from] component::StorageError),
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Component name {name} is registered for {registered}, not {conflicting}")]
+    ComponentNameConflict {
+        name: &'static str,
+        registered: &'static str,
+        conflicting: &'static str,
+    },
+    #[error("Component {0} isn't registered")]
+    UnregisteredComponent(String),
+    #[error("More than one entity has component {0}")]
+    NotASingleton(&'static str),
+    #[error("Component {0} already exists")]
+    ComponentExists(String),
+    #[error("Database schema version {found} is newer than the supported version {supported}")]
+    UnsupportedSchemaVersion { found: i64, supported: i64 },
+    #[error("Checkpoint didn't complete, the database is in use by another connection")]
+    CheckpointBusy,
+    #[error("Can't create an entity from a bundle without any component")]
+    EmptyBundle,
+    #[error(transparent)]
+    Conflict(#[from] entity::ConflictError),
+    #[error(transparent)]
+    ExtensionExists(#[from] extension::ExtensionExistsError),
+    #[error("Entity {id} belongs to database {db}, not {expected}")]
+    ForeignEntity {
+        id: EntityId,
+        db: uuid::Uuid,
+        expected: uuid::Uuid,
+    },
+    #[error("Invalid query filter: {0}")]
+    InvalidFilter(String),
+    #[error("At most {0} components can be part of the component mask")]
+    TooManyMaskedComponents(usize),
+    #[cfg(feature = "async")]
+    #[error("Async connection error: {0}")]
+    AsyncConnection(String),
 }
 
 pub struct Ecs {
     conn: rusqlite::Connection,
     extensions: anymap::Map<dyn anymap::any::Any + Send>,
+    hooks: hooks::Hooks,
+    registry: registry::ComponentRegistry,
+    retry: Option<options::RetryPolicy>,
+    query_stats: Option<query::stats::QueryStats>,
+    query_cache: Option<query::cache::ResultCache>,
 }
 
 impl Ecs {
@@ -76,8 +214,27 @@ impl Ecs {
         Self::from_rusqlite(rusqlite::Connection::open_with_flags(path, flags)?)
     }
 
-    pub fn from_rusqlite(mut conn: rusqlite::Connection) -> Result<Self, Error> {
-        conn.pragma_update(None, "journal_mode", "wal")?;
+    pub fn open_with(path: impl AsRef<Path>, options: EcsOptions) -> Result<Self, Error> {
+        Self::from_rusqlite_with(rusqlite::Connection::open(path)?, options)
+    }
+
+    pub fn from_rusqlite(conn: rusqlite::Connection) -> Result<Self, Error> {
+        Self::from_rusqlite_with(conn, EcsOptions::default())
+    }
+
+    pub fn from_rusqlite_with(
+        mut conn: rusqlite::Connection,
+        options: EcsOptions,
+    ) -> Result<Self, Error> {
+        options.apply(&conn)?;
+
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::UnsupportedSchemaVersion {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
 
         // Migrate resources table to components on the world entity
         let has_resources: bool = conn
@@ -88,14 +245,75 @@ impl Ecs {
         }
 
         conn.execute_batch(include_str!("schema.sql"))?;
+
+        // Each migration commits together with its `user_version` bump, so that
+        // an interrupted open never leaves a migration applied but unrecorded
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction_with_behavior(::rusqlite::TransactionBehavior::Immediate)?;
+            let current: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            if current > from as i64 {
+                // Migrated by a concurrent open
+                continue;
+            }
+
+            migration(&tx)?;
+            tx.pragma_update(None, "user_version", from as i64 + 1)?;
+            tx.commit()?;
+            debug!(version = from + 1, "migrated schema");
+        }
         conn.set_transaction_behavior(::rusqlite::TransactionBehavior::Immediate);
 
         sqlite_ext::add_regexp_function(&conn)?;
 
-        Ok(Self {
+        let mut ecs = Self {
             conn,
             extensions: anymap::Map::new(),
-        })
+            hooks: hooks::Hooks::default(),
+            registry: registry::ComponentRegistry::default(),
+            retry: options.retry,
+            query_stats: options.query_stats.then(query::stats::QueryStats::default),
+            query_cache: None,
+        };
+        ecs.registry.component_bits = component_mask::load_component_bits(&ecs.conn)?;
+        ecs.registry
+            .set_aliases(registry::load_component_aliases(&ecs.conn)?);
+
+        Ok(ecs)
+    }
+}
+
+impl Ecs {
+    /// Opens another, read-only connection to this database, sharing the
+    /// component registry, retry policy and busy timeout. Unlike
+    /// [`Ecs::open_with_flags`], the schema isn't set up or migrated. Returns
+    /// `None` for databases without a file.
+    pub(crate) fn open_read_only(&self) -> Result<Option<Self>, Error> {
+        let Some(path) = self.conn.path().filter(|path| !path.is_empty()) else {
+            return Ok(None);
+        };
+
+        let conn = rusqlite::Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let busy_timeout: u64 = self
+            .conn
+            .pragma_query_value(None, "busy_timeout", |row| row.get(0))?;
+        conn.busy_timeout(std::time::Duration::from_millis(busy_timeout))?;
+        sqlite_ext::add_regexp_function(&conn)?;
+
+        Ok(Some(Self {
+            conn,
+            extensions: anymap::Map::new(),
+            hooks: hooks::Hooks::default(),
+            registry: self.registry.clone(),
+            retry: self.retry,
+            query_stats: self
+                .query_stats
+                .as_ref()
+                .map(|_| query::stats::QueryStats::default()),
+            query_cache: None,
+        }))
     }
 }
 
@@ -104,6 +322,44 @@ impl Ecs {
         self.conn.close().map_err(|(_conn, e)| Error::Database(e))
     }
 
+    /// Sets how many prepared statements are kept for reuse. Raise it when
+    /// running many different queries repeatedly. Defaults to 16.
+    pub fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.conn.set_prepared_statement_cache_capacity(capacity);
+    }
+
+    /// Caches the entity ids of the `capacity` most recently run queries
+    /// until the database changes. Repeating a query on an unchanged database
+    /// then doesn't run any SQL.
+    pub fn with_query_cache(mut self, capacity: usize) -> Self {
+        self.query_cache = Some(query::cache::ResultCache::new(capacity));
+        self
+    }
+
+    /// Random id identifying this database, created on first use and stored
+    /// in the database. Entities tagged with it via [`Entity::tagged_id`] can
+    /// be resolved with [`Ecs::entity_checked`], which detects entities used
+    /// with the wrong database.
+    pub fn db_id(&self) -> Result<uuid::Uuid, Error> {
+        let select = "select value from meta where key = 'db_id'";
+        let id = match self
+            .conn
+            .query_row(select, [], |row| row.get::<_, [u8; 16]>(0))
+        {
+            Ok(id) => id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                self.conn.execute(
+                    "insert or ignore into meta (key, value) values ('db_id', randomblob(16))",
+                    [],
+                )?;
+                self.conn.query_row(select, [], |row| row.get(0))?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(uuid::Builder::from_random_bytes(id).into_uuid())
+    }
+
     pub fn data_version(&self) -> Result<i64, Error> {
         Ok(self
             .conn
@@ -112,6 +368,75 @@ impl Ecs {
             })?)
     }
 
+    /// Whether another connection wrote to the database since
+    /// [`Self::data_version`] returned `version`. Writes through this
+    /// connection don't change the data version.
+    pub fn changed_since(&self, version: i64) -> Result<bool, Error> {
+        Ok(self.data_version()? != version)
+    }
+
+    /// Runs `f`, retrying with exponential backoff while it fails with
+    /// `SQLITE_BUSY`. Each attempt runs in its own transaction, or a savepoint
+    /// if one is already active, which is rolled back when the attempt fails.
+    /// Uses the [`RetryPolicy`] from [`EcsOptions::retry`], or the default
+    /// policy if none was set.
+    pub fn with_retry<T>(&self, f: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        self.retry.unwrap_or_default().run(self, f)
+    }
+
+    /// Number of rows changed through this connection since it was opened
+    pub(crate) fn total_changes(&self) -> Result<i64, Error> {
+        Ok(self
+            .conn
+            .query_row("select total_changes()", [], |row| row.get(0))?)
+    }
+
+    /// Runs `pragma wal_checkpoint`, moving the contents of the write-ahead log
+    /// back into the database file. Fails with [`Error::CheckpointBusy`] if
+    /// other connections kept the checkpoint from completing.
+    pub fn checkpoint(&self, mode: CheckpointMode) -> Result<(), Error> {
+        let mode = match mode {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Truncate => "TRUNCATE",
+        };
+
+        // Columns are `busy`, `log` and `checkpointed`
+        let busy: bool =
+            self.conn
+                .query_row(&format!("pragma wal_checkpoint({mode})"), [], |row| {
+                    row.get(0)
+                })?;
+        if busy {
+            return Err(Error::CheckpointBusy);
+        }
+
+        debug!(mode, "checkpointed");
+        Ok(())
+    }
+
+    pub fn vacuum(&self) -> Result<(), Error> {
+        self.conn.execute_batch("vacuum")?;
+        Ok(())
+    }
+
+    /// Size of the `-wal` file in bytes. Returns `0` for in-memory databases
+    /// or if no WAL file exists.
+    pub fn wal_size(&self) -> Result<u64, Error> {
+        let path = match self.conn.path() {
+            None | Some("") => return Ok(0),
+            Some(path) => path,
+        };
+
+        match std::fs::metadata(format!("{path}-wal")) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Names of all components attached to any entity, including system
+    /// components and resources, sorted by name.
     pub fn component_names(&self) -> Result<Box<[String]>, Error> {
         let mut stmt = self
             .conn
@@ -121,6 +446,278 @@ impl Ecs {
             .collect::<Result<_, _>>()?;
         Ok(names)
     }
+
+    /// Renames all stored instances of component `old_name`, including
+    /// resources, to `new_name`. Useful after renaming a component type.
+    /// Fails if `new_name` is already in use. Returns the number of renamed
+    /// rows.
+    ///
+    /// Multi component values and the mask bit move along. An alias of
+    /// `old_name`, see [`ComponentNaming`], is dropped, as nothing is stored
+    /// under it anymore.
+    pub fn rename_component(&mut self, old_name: &str, new_name: &str) -> Result<usize, Error> {
+        let old_name = self.stored_component_name(old_name).to_owned();
+        let new_name = self.stored_component_name(new_name).to_owned();
+        let tx = self.transaction()?;
+
+        let exists = tx
+            .prepare("select 1 from components where component = ?1")?
+            .exists([&new_name])?;
+        if exists {
+            return Err(Error::ComponentExists(new_name));
+        }
+
+        // Mask bits first, so the mask trigger moves the bits along
+        if !self.registry.component_bits.is_empty() {
+            tx.execute(
+                r#"
+                update component_bits set component = ?1 where component = ?2
+                and not exists (select 1 from component_bits where component = ?1)
+                "#,
+                [&new_name, &old_name],
+            )?;
+        }
+
+        let renamed = tx.execute(
+            "update components set component = ?1 where component = ?2",
+            [&new_name, &old_name],
+        )?;
+        tx.execute(
+            "update multi_components set component = ?1 where component = ?2",
+            [&new_name, &old_name],
+        )?;
+        tx.execute(
+            "delete from component_aliases where alias = ?1",
+            [&old_name],
+        )?;
+
+        tx.commit()?;
+
+        self.registry.component_bits = component_mask::load_component_bits(&self.conn)?;
+        self.registry
+            .set_aliases(registry::load_component_aliases(&self.conn)?);
+
+        debug!(old_name, new_name, renamed, "renamed component");
+
+        Ok(renamed)
+    }
+
+    /// Deletes component `C` from all entities, including the resource.
+    /// Partial indexes on `components` restricted to `C`, i.e. whose `where`
+    /// clause has a `component = '<name>'` predicate, are dropped as well. Returns the number of
+    /// deleted rows.
+    pub fn remove_component_everywhere<C: Component>(&self) -> Result<usize, Error> {
+        self.remove_component_everywhere_named(C::NAME)
+    }
+
+    /// Like [`Self::remove_component_everywhere`], by component name
+    pub fn remove_component_everywhere_named(&self, component: &str) -> Result<usize, Error> {
+        let component = self.stored_component_name(component);
+        let tx = self.transaction()?;
+
+        let detached = self.delete_components("component = ?1", [component])?;
+        let removed = detached.len();
+
+        let indexes = tx
+            .prepare(
+                "select name, sql from sqlite_schema where type = 'index' and tbl_name = 'components' and sql is not null",
+            )?
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, sql) in indexes {
+            if is_partial_index_of(&sql, component) {
+                tx.execute(
+                    &format!("drop index {}", savepoint::quote_identifier(&index)),
+                    [],
+                )?;
+                debug!(component, index, "dropped partial index");
+            }
+        }
+
+        tx.commit()?;
+        debug!(component, removed, "removed component everywhere");
+
+        detached.run_hooks(self)?;
+        Ok(removed)
+    }
+
+    /// Detaches bundle `B` from all entities in `entities`. Runs in a single
+    /// transaction, deleting in chunks to stay below SQLite's variable limit.
+    /// Returns the number of deleted rows.
+    pub fn detach_many<B: Bundle>(&self, entities: &[EntityId]) -> Result<usize, Error> {
+        const CHUNK_SIZE: usize = 500;
+
+        if B::COMPONENTS.is_empty() {
+            return Ok(0);
+        }
+
+        let tx = self.transaction()?;
+
+        let components = vec!["?"; B::COMPONENTS.len()].join(", ");
+        let mut detached = hooks::Detached::default();
+        for chunk in entities.chunks(CHUNK_SIZE) {
+            let condition = format!(
+                "entity in ({}) and component in ({components})",
+                vec!["?"; chunk.len()].join(", ")
+            );
+
+            let params = chunk
+                .iter()
+                .map(|e| rusqlite::types::Value::Integer(*e))
+                .chain(B::COMPONENTS.iter().map(|c| {
+                    rusqlite::types::Value::Text(self.stored_component_name(c).to_owned())
+                }));
+
+            detached
+                .append(self.delete_components(&condition, rusqlite::params_from_iter(params))?);
+        }
+
+        tx.commit()?;
+
+        let removed = detached.len();
+        debug!(detached = removed, "detached many");
+
+        detached.run_hooks(self)?;
+        Ok(removed)
+    }
+
+    /// Number of entities carrying each component. Resources (components on
+    /// [`WORLD_ENTITY`]) are not included.
+    pub fn component_stats(&self) -> Result<ComponentStats, Error> {
+        let mut stmt = self.conn.prepare(
+            "select component, count(*) from components where entity != ?1 group by component",
+        )?;
+        let components = stmt
+            .query_map([WORLD_ENTITY], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let entities = self.conn.query_row(
+            "select count(distinct entity) from components where entity != ?1",
+            [WORLD_ENTITY],
+            |row| row.get(0),
+        )?;
+
+        Ok(ComponentStats {
+            components,
+            entities,
+        })
+    }
+
+    /// Number of existing entities, not counting [`WORLD_ENTITY`]
+    pub fn entity_count(&self) -> Result<i64, Error> {
+        Ok(self.conn.query_row(
+            "select count(*) from entities where id != ?1",
+            [WORLD_ENTITY],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Whether no entities (besides [`WORLD_ENTITY`]) exist
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.entity_count()? == 0)
+    }
+
+    /// All distinct values of component `C` across all entities. Rows that
+    /// fail to deserialize are skipped with a warning.
+    pub fn distinct_values<C: Component>(&self) -> Result<Vec<C>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("select distinct data from components where component = ?1")?;
+        let rows = stmt.query_map([self.stored_component_name(C::NAME)], |row| {
+            row.get::<_, rusqlite::types::Value>(0)
+        })?;
+
+        let mut values = Vec::new();
+        for data in rows {
+            match C::from_rusqlite(&rusqlite::types::ToSqlOutput::Owned(data?)) {
+                Ok(value) => values.push(value),
+                Err(e) => warn!(component = C::NAME, error = %e, "Skipping undeserializable value"),
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Computes `agg` over the data of component `C`, which must hold a
+    /// numeric JSON scalar. Returns `None` if no entity has `C`. Like in
+    /// [`Self::component_stats`], a resource of type `C` isn't included.
+    pub fn aggregate<C: Component>(&self, agg: Agg) -> Result<Option<f64>, Error> {
+        let sql = format!(
+            "select {}(velodb_extract_data(data)), count(*) from components where component = ?1 and entity != ?2",
+            agg.as_sql()
+        );
+        let (value, count) = self.conn.query_row(
+            &sql,
+            rusqlite::params![self.stored_component_name(C::NAME), WORLD_ENTITY],
+            |row| Ok((row.get::<_, Option<f64>>(0)?, row.get::<_, i64>(1)?)),
+        )?;
+
+        Ok(if count == 0 { None } else { value })
+    }
+}
+
+/// Whether the index created by `sql` is restricted to `component` via a
+/// `component = '<name>'` predicate. Other mentions of the name, e.g. in a
+/// comparison against `data`, don't count.
+fn is_partial_index_of(sql: &str, component: &str) -> bool {
+    let Some(pos) = sql.to_ascii_lowercase().find(" where ") else {
+        return false;
+    };
+    let predicate = &sql[pos..];
+    let literal = format!("'{}'", component.replace('\'', "''"));
+
+    predicate.match_indices(&literal).any(|(start, _)| {
+        let Some(column) = predicate[..start].trim_end().strip_suffix('=') else {
+            return false;
+        };
+        let column = column
+            .trim_end()
+            .trim_end_matches(['"', '`', ']'])
+            .to_ascii_lowercase();
+        column.strip_suffix("component").is_some_and(|prefix| {
+            !prefix
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ComponentStats {
+    /// Component name to number of entities with that component
+    pub components: BTreeMap<String, i64>,
+    /// Total number of distinct entities
+    pub entities: i64,
+}
+
+/// Aggregate function for [`Ecs::aggregate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Agg {
+    Sum,
+    Min,
+    Max,
+    Avg,
+    Count,
+}
+
+impl Agg {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Agg::Sum => "sum",
+            Agg::Min => "min",
+            Agg::Max => "max",
+            Agg::Avg => "avg",
+            Agg::Count => "count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    Passive,
+    Full,
+    Truncate,
 }
 
 impl Ecs {
@@ -128,10 +725,51 @@ impl Ecs {
         Entity::without_id(self)
     }
 
+    /// Alias for [`Self::new_entity`]
+    pub fn spawn_empty<'a>(&'a self) -> NewEntity<'a> {
+        self.new_entity()
+    }
+
+    /// Allocates a fresh, never before used entity id
+    pub(crate) fn allocate_entity_id(&self) -> Result<EntityId, Error> {
+        Ok(self.conn.query_row(
+            "insert into entities default values returning id",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Runs `f`, which converts components of `entity` from or to their
+    /// storage. Provides the key for encrypted storages.
+    #[cfg(feature = "encryption")]
+    pub(crate) fn with_storage_context<T>(&self, entity: EntityId, f: impl FnOnce() -> T) -> T {
+        encryption::with_entity(self, entity, f)
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub(crate) fn with_storage_context<T>(&self, _entity: EntityId, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+
     pub fn entity<'a>(&'a self, eid: EntityId) -> Entity<'a> {
         Entity::with_id(self, eid)
     }
 
+    /// Like [`Ecs::entity`], but fails with [`Error::ForeignEntity`] if `eid`
+    /// was tagged by another database
+    pub fn entity_checked<'a>(&'a self, eid: TaggedEntityId) -> Result<Entity<'a>, Error> {
+        let expected = self.db_id()?;
+        if eid.db != expected {
+            return Err(Error::ForeignEntity {
+                id: eid.id,
+                db: eid.db,
+                expected,
+            });
+        }
+
+        Ok(Entity::with_id(self, eid.id))
+    }
+
     /// Returns the world entity (ID 0), which stores resources.
     pub fn world_entity(&self) -> Entity<'_> {
         Entity::with_id(self, WORLD_ENTITY)
@@ -147,6 +785,29 @@ impl Ecs {
 
 #[with_infallible]
 impl Ecs {
+    /// Iterates all entities matching filter `F`, yielding `D` for each.
+    ///
+    /// Entities are always returned in ascending entity id order. Use
+    /// [`query::Query::reverse_iter`] for descending order, or
+    /// [`query::Query::order_by`] for a different one. With
+    /// [`query::ir::OrderBy::None`], the order is unspecified.
+    ///
+    /// ```
+    /// use ecsdb::{Component, Ecs, EntityId};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Component)]
+    /// struct Marker;
+    ///
+    /// let ecs = Ecs::open_in_memory()?;
+    /// ecs.entity(300).attach(Marker);
+    /// ecs.entity(100).attach(Marker);
+    /// ecs.entity(200).attach(Marker);
+    ///
+    /// let ids = ecs.query::<EntityId, Marker>().collect::<Vec<_>>();
+    /// assert_eq!(ids, [100, 200, 300]);
+    /// # Ok::<(), ecsdb::Error>(())
+    /// ```
     #[instrument(name = "query", level = "debug", skip_all)]
     pub fn try_query<'a, D, F>(&'a self) -> Result<impl Iterator<Item = D::Output<'a>> + 'a, Error>
     where
@@ -161,6 +822,8 @@ impl Ecs {
         query.try_iter()
     }
 
+    /// Like [`Self::try_query`], additionally filtering by `filter_value`.
+    /// Ordered by ascending entity id.
     #[instrument(name = "find", level = "debug", skip_all)]
     pub fn try_query_filtered<'a, D, F>(
         &'a self,
@@ -177,60 +840,353 @@ impl Ecs {
 
 #[component::with_infallible]
 impl Ecs {
+    /// Entities matching `filter_value`, in ascending entity id order
     pub fn try_find<'a>(
         &'a self,
         filter_value: impl query::QueryFilterValue + 'a,
     ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
         self.try_query_filtered::<Entity<'a>, ()>(filter_value)
     }
+
+    /// Like [`Self::new_entity`], but allocates the entity id right away, so
+    /// that database errors surface here instead of panicking on the first
+    /// [`NewEntity::attach`]. The entity exists even without components.
+    pub fn try_create_entity<'a>(&'a self) -> Result<Entity<'a>, Error> {
+        Ok(Entity::with_id(self, self.allocate_entity_id()?))
+    }
+
+    /// Registers an entity with the caller-chosen `id`, e.g. when importing
+    /// data with preserved ids. A no-op if the entity already exists. Later
+    /// allocated ids are always greater than `id`.
+    pub fn try_spawn_with_id<'a>(&'a self, id: EntityId) -> Result<Entity<'a>, Error> {
+        self.conn
+            .execute("insert or ignore into entities (id) values (?1)", [id])?;
+        Ok(Entity::with_id(self, id))
+    }
+
+    /// Spawns a new entity with `bundle` attached. Shorthand for
+    /// `new_entity().attach(bundle)`.
+    pub fn try_spawn<'a, B: component::NonEmptyBundle>(
+        &'a self,
+        bundle: B,
+    ) -> Result<Entity<'a>, Error> {
+        self.new_entity().try_attach(bundle)
+    }
+
+    /// Spawns one entity per bundle in a single transaction. Returns the new
+    /// entities in the order of `bundles`.
+    pub fn try_spawn_batch<'a, B: component::NonEmptyBundle>(
+        &'a self,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Result<Vec<Entity<'a>>, Error> {
+        let tx = self.transaction()?;
+        let entities = bundles
+            .into_iter()
+            .map(|bundle| self.new_entity().try_attach(bundle))
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.commit()?;
+        Ok(entities)
+    }
+
+    /// The existing entities among `ids`, each once, in ascending id order.
+    /// Looks them up with one query per chunk of ids instead of one per id.
+    pub fn try_entities_by_ids<'a>(
+        &'a self,
+        ids: &[EntityId],
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        Ok(self.try_get_by_ids::<()>(ids)?.map(|(entity, ())| entity))
+    }
+
+    /// Like [`Self::try_entities_by_ids`], but also reads bundle `B` in the
+    /// same queries, e.g. `get_by_ids::<(A, Option<B>)>(&ids)`. Entities
+    /// missing a non-`Option` component of `B` are skipped.
+    pub fn try_get_by_ids<'a, B: Bundle>(
+        &'a self,
+        ids: &[EntityId],
+    ) -> Result<impl Iterator<Item = (Entity<'a>, B)> + 'a, Error> {
+        const CHUNK_SIZE: usize = 500;
+
+        let stored_names = B::COMPONENTS
+            .iter()
+            .map(|c| self.stored_component_name(c))
+            .collect::<Vec<_>>();
+
+        let mut entities = BTreeMap::<EntityId, std::collections::HashMap<_, _>>::new();
+        for chunk in ids.chunks(CHUNK_SIZE) {
+            // Skips the presence rows of `MultiComponent`s, like
+            // `Entity::try_component`
+            let sql = format!(
+                "select e.id, c.component, c.data from entities e
+                 left join components c on c.entity = e.id and c.component in ({})
+                 and (c.data is not null or not exists (
+                    select 1 from multi_components m
+                    where m.entity = c.entity and m.component = c.component
+                 ))
+                 where e.id in ({})",
+                vec!["?"; stored_names.len()].join(", "),
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let params = stored_names
+                .iter()
+                .map(|c| c as &dyn rusqlite::ToSql)
+                .chain(chunk.iter().map(|id| id as &dyn rusqlite::ToSql));
+
+            let mut stmt = self.conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                Ok((
+                    row.get::<_, EntityId>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, rusqlite::types::Value>(2)?,
+                ))
+            })?;
+
+            for row in rows {
+                let (id, component, data) = row?;
+                let components = entities.entry(id).or_default();
+                if let Some(component) = component {
+                    components.insert(component, data);
+                }
+            }
+        }
+
+        let mut found = Vec::with_capacity(entities.len());
+        for (id, mut components) in entities {
+            let data = B::COMPONENTS
+                .iter()
+                .zip(&stored_names)
+                .map(|(name, stored)| {
+                    let data = components
+                        .remove(*stored)
+                        .map(rusqlite::types::ToSqlOutput::Owned);
+                    (*name, data)
+                })
+                .collect::<Vec<_>>();
+
+            if let Some(bundle) = self.with_storage_context(id, || B::from_rusqlite(&data))? {
+                found.push((Entity::with_id(self, id), bundle));
+            }
+        }
+
+        Ok(found.into_iter())
+    }
+
+    /// Every existing entity, including ones without any components, in
+    /// creation order. Doesn't include [`WORLD_ENTITY`].
+    pub fn try_all_entities<'a>(&'a self) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("select id from entities where id != ?1 order by created_at, id")?;
+        let ids = stmt
+            .query_map([WORLD_ENTITY], |row| row.get(0))?
+            .collect::<Result<Vec<EntityId>, _>>()?;
+
+        Ok(ids.into_iter().map(|id| Entity::with_id(self, id)))
+    }
+
+    /// Entities carrying the component named `name`, in ascending entity id
+    /// order. For components whose type isn't known at compile time.
+    pub fn try_entities_with_component<'a>(
+        &'a self,
+        name: &str,
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        self.try_find(query::ComponentName(name.to_owned()))
+    }
+
+    /// Returns the first entity with component value `c`, or spawns a new one
+    /// with it.
+    pub fn try_entity_or_create<'a, C: Component + Clone>(
+        &'a self,
+        c: C,
+    ) -> Result<Entity<'a>, Error> {
+        match self.try_find(c.clone())?.next() {
+            Some(entity) => Ok(entity),
+            None => self.new_entity().try_attach(c),
+        }
+    }
+
+    /// Returns the only entity with component `C`, regardless of its value,
+    /// or spawns one with `c`. Fails if more than one entity has `C`.
+    pub fn try_singleton<'a, C: Component>(&'a self, c: C) -> Result<Entity<'a>, Error> {
+        let mut entities = self.try_query::<Entity<'a>, query::With<C>>()?;
+        match (entities.next(), entities.next()) {
+            (None, _) => self.new_entity().try_attach(c),
+            (Some(entity), None) => Ok(entity),
+            (Some(_), Some(_)) => Err(Error::NotASingleton(C::NAME)),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+/// Time an entity got its first component. Maintained by the database.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 pub struct CreatedAt(pub chrono::DateTime<chrono::Utc>);
 
+impl CreatedAt {
+    /// Filter value matching entities created at or after `time`
+    pub fn since(time: chrono::DateTime<chrono::Utc>) -> std::ops::RangeFrom<Self> {
+        Self(time)..
+    }
+}
+
+/// Uses the same format as the database triggers, so that range filters
+/// compare correctly
+impl Serialize for CreatedAt {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0.format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct LastUpdated(pub chrono::DateTime<chrono::Utc>);
 
+/// A point in time, stored as RFC3339 with a fixed number of fractional
+/// digits so that string comparison matches chronological order. This makes
+/// range filters like `Timestamp(a)..Timestamp(b)` (excluding `b`) and
+/// `Timestamp(a)..=Timestamp(b)` work as expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub chrono::DateTime<chrono::Utc>);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(chrono::Utc::now())
+    }
+}
+
 impl Ecs {
     #[instrument(level = "debug", skip_all)]
     fn fetch<'a, Q: query::QueryData + 'a>(
         &'a self,
         sql_query: query::ir::Query,
+        capacity: usize,
     ) -> Result<impl Iterator<Item = Q::Output<'a>> + 'a, Error> {
-        let entity_ids = self.fetch_entity_ids(sql_query)?;
+        let entity_ids = self.fetch_entity_ids(sql_query, capacity)?;
 
         let rows = entity_ids
             .into_iter()
             .scan(self, |ecs, eid| Some(Entity::with_id(ecs, eid)))
-            .map(|e| {
+            .filter_map(|e| {
                 debug!(
                     data = std::any::type_name::<Q>(),
                     entity = ?e,
                     "Fetching QueryData"
                 );
-                Q::from_entity(e).unwrap()
+                Q::from_entity(e)
             });
 
         Ok(rows)
     }
 
-    fn fetch_entity_ids<'a>(&'a self, sql_query: query::ir::Query) -> Result<Vec<EntityId>, Error> {
+    fn fetch_entity_ids<'a>(
+        &'a self,
+        sql_query: query::ir::Query,
+        capacity: usize,
+    ) -> Result<Vec<EntityId>, Error> {
+        sql_query.filter.validate()?;
+        if self.lacks_required_component(&sql_query.filter)? {
+            return Ok(Vec::new());
+        }
+
         let (sql, placeholders) = sql_query.into_sql();
         debug!(sql);
 
-        let mut stmt = self.conn.prepare(&sql)?;
+        let cached = self.query_cache_slot(&sql, &placeholders)?;
+        if let Some((cache, version, key)) = &cached
+            && let Some(entities) = cache.get(*version, key)
+        {
+            debug!("served from query cache");
+            return Ok(entities);
+        }
+
+        let started = std::time::Instant::now();
+
+        let mut stmt = self.conn.prepare_cached(&sql)?;
         let params: Box<[(&str, &dyn rusqlite::ToSql)]> = placeholders
             .iter()
             .map(|(p, v)| (p.as_str(), v.as_ref()))
             .collect();
 
-        let rows = stmt
-            .query_map(&params[..], |row| row.get("entity"))?
-            .map(|r| r.expect("EntityId from Query"))
-            .collect();
+        let mut rows = Vec::with_capacity(capacity);
+        for row in stmt.query_map(&params[..], |row| row.get("entity"))? {
+            rows.push(row?);
+        }
+
+        if let Some(stats) = &self.query_stats {
+            stats.record(&sql, started.elapsed());
+        }
+
+        if let Some((cache, version, key)) = cached {
+            cache.insert(version, key, rows.clone());
+        }
 
         Ok(rows)
     }
+
+    /// The query cache, plus the database version and key to look up `sql`
+    /// under. `None` if the cache is disabled or a transaction is open: a
+    /// rollback doesn't lower `total_changes`, so results read inside a
+    /// transaction could be served after it was rolled back.
+    fn query_cache_slot(
+        &self,
+        sql: &str,
+        placeholders: &query::ir::SqlParameters,
+    ) -> Result<Option<(&query::cache::ResultCache, (i64, i64), String)>, Error> {
+        let Some(cache) = &self.query_cache else {
+            return Ok(None);
+        };
+        if !self.conn.is_autocommit() {
+            return Ok(None);
+        }
+
+        let version = (self.data_version()?, self.total_changes()?);
+        let key = query::cache::ResultCache::key(sql, placeholders);
+        Ok(Some((cache, version, key)))
+    }
+
+    fn count_entities(&self, sql_query: query::ir::Query) -> Result<usize, Error> {
+        sql_query.filter.validate()?;
+        if self.lacks_required_component(&sql_query.filter)? {
+            return Ok(0);
+        }
+
+        let (sql, placeholders) = sql_query.into_sql();
+        let sql = format!("select count(*) from ({sql})");
+        debug!(sql);
+
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let params: Box<[(&str, &dyn rusqlite::ToSql)]> = placeholders
+            .iter()
+            .map(|(p, v)| (p.as_str(), v.as_ref()))
+            .collect();
+
+        Ok(stmt.query_row(&params[..], |row| row.get(0))?)
+    }
+
+    /// Whether a component required by `filter` isn't stored on any entity,
+    /// in which case the query can't match and doesn't need to run. Only
+    /// looks at `With`; `Without` an absent component matches everything.
+    fn lacks_required_component(
+        &self,
+        filter: &query::ir::FilterExpression,
+    ) -> Result<bool, Error> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("select 1 from components where component = ?1 limit 1")?;
+        for component in filter.required_components() {
+            if !stmt.exists([component])? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Execution statistics per generated query SQL, by descending total
+    /// duration. Empty unless enabled via [`EcsOptions::query_stats`].
+    pub fn query_stats(&self) -> Vec<query::QueryStat> {
+        self.query_stats
+            .as_ref()
+            .map(query::stats::QueryStats::snapshot)
+            .unwrap_or_default()
+    }
 }
 
 #[allow(unused)]
@@ -239,17 +1195,17 @@ impl Ecs {
     fn fetch_lazy<'a, Q: query::QueryData + 'a>(
         &'a self,
         sql_query: query::ir::Query,
-    ) -> Result<impl Iterator<Item = Q::Output<'a>> + 'a, Error> {
+    ) -> Result<impl Iterator<Item = Result<Q::Output<'a>, Error>> + 'a, Error> {
         let rows = self
             .fetch_entity_ids_lazy(sql_query)?
-            .scan(self, |ecs, eid| Some(Entity::with_id(ecs, eid)))
-            .map(|e| {
+            .map(move |eid| Ok(Entity::with_id(self, eid?)))
+            .filter_map(|e: Result<Entity<'a>, Error>| {
                 debug!(
                     data = std::any::type_name::<Q>(),
                     entity = ?e,
                     "Fetching QueryData"
                 );
-                Q::from_entity(e).unwrap()
+                e.and_then(Q::try_from_entity).transpose()
             });
 
         Ok(rows)
@@ -258,10 +1214,23 @@ impl Ecs {
     fn fetch_entity_ids_lazy<'a>(
         &'a self,
         sql_query: query::ir::Query,
-    ) -> Result<impl Iterator<Item = EntityId> + 'a, Error> {
+    ) -> Result<Box<dyn Iterator<Item = Result<EntityId, Error>> + 'a>, Error> {
+        sql_query.filter.validate()?;
+        if self.lacks_required_component(&sql_query.filter)? {
+            return Ok(Box::new(std::iter::empty()));
+        }
+
         let (sql, placeholders) = sql_query.into_sql();
         debug!(sql);
 
+        // Rows are stepped one at a time, so the cache is only read from
+        if let Some((cache, version, key)) = self.query_cache_slot(&sql, &placeholders)?
+            && let Some(entities) = cache.get(version, &key)
+        {
+            debug!("served from query cache");
+            return Ok(Box::new(entities.into_iter().map(Ok)));
+        }
+
         type RowsRef<'a> = ::rusqlite::Rows<'a>;
 
         self_cell::self_cell!(
@@ -283,6 +1252,8 @@ impl Ecs {
             }
         }
 
+        let started = std::time::Instant::now();
+
         let stmt = self.conn.prepare(&sql)?;
         let params: Box<[(&str, &dyn rusqlite::ToSql)]> = placeholders
             .iter()
@@ -290,10 +1261,13 @@ impl Ecs {
             .collect();
 
         let owning_rows =
-            OwningRows::try_new(MutBorrow::new(stmt), |s| s.borrow_mut().query(&params[..]))
-                .unwrap();
+            OwningRows::try_new(MutBorrow::new(stmt), |s| s.borrow_mut().query(&params[..]))?;
+
+        if let Some(stats) = &self.query_stats {
+            stats.record(&sql, started.elapsed());
+        }
 
-        Ok(owning_rows.map(|result| result.expect("EntityId from Row")))
+        Ok(Box::new(owning_rows.map(|row| Ok(row?))))
     }
 }
 
@@ -320,6 +1294,35 @@ impl Default for CreatedAt {
     }
 }
 
+impl AsRef<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn as_ref(&self) -> &chrono::DateTime<chrono::Utc> {
+        &self.0
+    }
+}
+
+impl Component for Timestamp {
+    type Storage = component::JsonStorage;
+    const NAME: &'static str = "ecsdb::Timestamp";
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self(chrono::DateTime::<chrono::Utc>::MIN_UTC)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0.format("%Y-%m-%dT%H:%M:%S%.9fZ"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        chrono::DateTime::<chrono::Utc>::deserialize(deserializer).map(Self)
+    }
+}
+
 impl AsRef<chrono::DateTime<chrono::Utc>> for LastUpdated {
     fn as_ref(&self) -> &chrono::DateTime<chrono::Utc> {
         &self.0
@@ -378,7 +1381,7 @@ mod tests {
     #[derive(Debug, Serialize, Deserialize, Component)]
     struct MarkerComponent;
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq, Component)]
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Component)]
     struct ComponentWithData(u64);
 
     #[derive(Debug, Serialize, Deserialize, Component)]
@@ -696,7 +1699,7 @@ mod tests {
             .query_filtered::<Entity, ()>(ComponentWithData(0)..ComponentWithData(10))
             .map(|e| e.component::<ComponentWithData>().unwrap());
         assert_eq!(res.next(), Some(ComponentWithData(0)));
-        assert_eq!(res.last(), Some(ComponentWithData(10)));
+        assert_eq!(res.last(), Some(ComponentWithData(9)));
 
         let mut res = db
             .query_filtered::<Entity, ()>(ComponentWithData(10)..)
@@ -708,7 +1711,36 @@ mod tests {
             .query_filtered::<Entity, ()>(..ComponentWithData(10))
             .map(|e| e.component::<ComponentWithData>().unwrap());
         assert_eq!(res.next(), Some(ComponentWithData(0)));
-        assert_eq!(res.last(), Some(ComponentWithData(10)));
+        assert_eq!(res.last(), Some(ComponentWithData(9)));
+    }
+
+    #[test]
+    fn find_inclusive_ranges() {
+        let db = Ecs::open_in_memory().unwrap();
+        for n in 0..100 {
+            let _ = db.new_entity().attach(ComponentWithData(n));
+        }
+
+        let inclusive = db
+            .query_filtered::<ComponentWithData, ()>(ComponentWithData(10)..=ComponentWithData(20))
+            .collect::<Vec<_>>();
+        assert_eq!(inclusive.first(), Some(&ComponentWithData(10)));
+        assert_eq!(inclusive.last(), Some(&ComponentWithData(20)));
+        assert_eq!(inclusive.len(), 11);
+
+        // `Range` filters exclude their end
+        let range = db
+            .query_filtered::<ComponentWithData, ()>(ComponentWithData(10)..ComponentWithData(20))
+            .collect::<Vec<_>>();
+        assert_eq!(range.len(), 10);
+        assert_eq!(range.last(), Some(&ComponentWithData(19)));
+        assert!(!range.contains(&ComponentWithData(20)));
+
+        let to = db
+            .query_filtered::<ComponentWithData, ()>(..=ComponentWithData(5))
+            .collect::<Vec<_>>();
+        assert_eq!(to.last(), Some(&ComponentWithData(5)));
+        assert_eq!(to.len(), 6);
     }
 
     // #[test]
@@ -768,6 +1800,100 @@ mod tests {
         assert_eq!(entity.component::<X>().unwrap(), x.clone());
     }
 
+    #[test]
+    fn component_raw() {
+        #[derive(Component, Debug, PartialEq, Clone)]
+        #[component(storage = "blob")]
+        struct X(Vec<u8>);
+
+        impl AsRef<[u8]> for X {
+            fn as_ref(&self) -> &[u8] {
+                self.0.as_slice()
+            }
+        }
+
+        impl From<Vec<u8>> for X {
+            fn from(value: Vec<u8>) -> Self {
+                Self(value)
+            }
+        }
+
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db
+            .new_entity()
+            .attach((ComponentWithData(42), X(vec![0, 159, 146, 150])));
+
+        assert_eq!(
+            entity.component_raw(ComponentWithData::NAME),
+            Some(serde_json::to_vec(&ComponentWithData(42)).unwrap())
+        );
+        assert_eq!(entity.component_raw(X::NAME), Some(vec![0, 159, 146, 150]));
+        assert_eq!(entity.component_raw(A::NAME), None);
+    }
+
+    #[test]
+    fn owned_blob_component() {
+        /// Run-length encoded bytes, without a contiguous byte representation
+        #[derive(Component, Debug, PartialEq, Clone)]
+        #[component(storage = "owned_blob")]
+        struct Rle(Vec<(u8, u8)>);
+
+        impl From<Rle> for Vec<u8> {
+            fn from(value: Rle) -> Self {
+                value.0.into_iter().flat_map(|(n, b)| [n, b]).collect()
+            }
+        }
+
+        impl From<Vec<u8>> for Rle {
+            fn from(value: Vec<u8>) -> Self {
+                Self(value.chunks(2).map(|c| (c[0], c[1])).collect())
+            }
+        }
+
+        let rle = Rle(vec![(3, b'a'), (1, b'b')]);
+
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach(rle.clone());
+
+        assert_eq!(entity.component::<Rle>(), Some(rle));
+        assert_eq!(
+            entity.dyn_component(Rle::NAME).unwrap().as_blob(),
+            Some(&[3, b'a', 1, b'b'][..])
+        );
+    }
+
+    #[test]
+    fn arc_blob_component() {
+        use std::sync::Arc;
+
+        #[derive(Component, Debug, PartialEq, Clone)]
+        #[component(storage = "arc_blob")]
+        struct Image(Arc<[u8]>);
+
+        impl AsRef<[u8]> for Image {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl From<Arc<[u8]>> for Image {
+            fn from(value: Arc<[u8]>) -> Self {
+                Self(value)
+            }
+        }
+
+        let data = (0..1024 * 1024).map(|n| n as u8).collect::<Arc<[u8]>>();
+
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach(Image(data.clone()));
+
+        let image = entity.component::<Image>().unwrap();
+        assert_eq!(image.0, data);
+
+        let shared = image.clone();
+        assert!(Arc::ptr_eq(&image.0, &shared.0));
+    }
+
     #[test]
     fn has_many() {
         let db = Ecs::open_in_memory().unwrap();
@@ -792,11 +1918,31 @@ mod tests {
         #[derive(Serialize, Deserialize, Component)]
         struct B;
 
+        #[derive(Serialize, Deserialize, Component, Debug, PartialEq)]
+        struct C(u64);
+
+        #[derive(Debug, Bundle)]
+        struct Composite {
+            a: A,
+            c: C,
+            b: Option<B>,
+        }
+
         let db = super::Ecs::open_in_memory()?;
-        let _e = db.new_entity().attach((A, B));
+        let e = db.new_entity().attach((A, C(42)));
+
+        let ab = e.component::<(A, C)>();
+        assert!(matches!(ab, Some((A, C(42)))));
+        assert!(e.component::<(A, B)>().is_none());
+        assert!(matches!(e.component::<(A, Option<B>)>(), Some((A, None))));
 
-        // let ab = e.component::<(A, B)>();
-        // assert!(ab.is_some());
+        let composite = e.component::<Composite>().unwrap();
+        assert_eq!(composite.c, C(42));
+        assert!(composite.b.is_none());
+
+        e.attach(B);
+        assert!(e.component::<Composite>().unwrap().b.is_some());
+        assert!(e.component::<(A, B, C)>().is_some());
 
         Ok(())
     }
@@ -821,25 +1967,36 @@ mod tests {
         assert!(e2.matches::<With<(A, B)>>());
     }
 
-    // #[test]
-    // fn entity_matches_filtered() {
-    //     #[derive(Serialize, Deserialize, Component)]
-    //     struct A;
-    //     #[derive(Serialize, Deserialize, Component)]
-    //     struct B;
-
-    //     let db = super::Ecs::open_in_memory().unwrap();
-    //     let e = db.new_entity().attach(A);
-    //     let e2 = db.new_entity().attach((A, B));
-
-    //     assert!(e.matches_filtered((With::<A>::default(), e.id())));
-    //     assert!(!e.matches::<With<B>>());
-    //     assert!(!e.matches::<With<(A, B)>>());
-
-    //     assert!(e2.matches::<With<A>>());
-    //     assert!(e2.matches::<With<B>>());
-    //     assert!(e2.matches::<With<(A, B)>>());
-    // }
+    #[test]
+    fn entity_matches_filtered() {
+        #[derive(Serialize, Deserialize, Component)]
+        struct A;
+        #[derive(Serialize, Deserialize, Component)]
+        struct B;
+        #[derive(Serialize, Deserialize, Component)]
+        struct Value(u32);
+
+        let db = super::Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+        let e2 = db.new_entity().attach((A, B, Value(42)));
+
+        assert!(e.matches_filtered(With::<A>::default()));
+        assert!(!e.matches_filtered(With::<B>::default()));
+        assert!(!e.matches_filtered(With::<(A, B)>::default()));
+        assert!(e.matches_filtered(Without::<B>::default()));
+        assert!(!e.matches_filtered(e2.id()));
+
+        assert!(e2.matches_filtered(With::<A>::default()));
+        assert!(e2.matches_filtered(With::<(A, B)>::default()));
+        assert!(e2.matches_filtered(FilterValueWrapper::<Or<(With<B>, Without<A>)>>::default()));
+        assert!(!e.matches_filtered(FilterValueWrapper::<Or<(With<B>, Without<A>)>>::default()));
+
+        assert!(e2.matches_filtered(Value(42)));
+        assert!(!e2.matches_filtered(Value(23)));
+        assert!(!e.matches_filtered(Value(42)));
+        assert!(e2.matches_filtered((With::<B>::default(), Value(42))));
+        assert!(e2.matches_filtered(Value(40)..Value(50)));
+    }
 
     #[test]
     fn last_modified() {
@@ -884,6 +2041,77 @@ mod tests {
         assert_eq!(e.created_at(), old);
     }
 
+    #[test]
+    fn clone_entity() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db
+            .new_entity()
+            .attach((A, B, ComponentWithData(42)))
+            .attach(MarkerComponent);
+        e.detach::<MarkerComponent>();
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        let clone = e.clone_entity();
+        assert_ne!(clone.id(), e.id());
+        assert!(clone.has::<(A, B, ComponentWithData)>());
+        assert!(!clone.has::<MarkerComponent>());
+        assert_eq!(
+            clone.component::<ComponentWithData>(),
+            Some(ComponentWithData(42))
+        );
+        assert_eq!(
+            clone
+                .component_names()
+                .collect::<std::collections::BTreeSet<_>>(),
+            e.component_names()
+                .collect::<std::collections::BTreeSet<_>>()
+        );
+        assert!(clone.created_at() > e.created_at());
+    }
+
+    #[test]
+    fn clone_multi_components() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Component)]
+        struct Tag(String);
+
+        impl crate::MultiComponent for Tag {}
+
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db
+            .new_entity()
+            .attach(A)
+            .attach_multi(Tag("one".into()))
+            .attach_multi(Tag("two".into()));
+
+        let clone = e.clone_entity();
+        assert!(clone.has::<Tag>());
+        assert_eq!(
+            clone.components_of::<Tag>(),
+            vec![Tag("one".into()), Tag("two".into())]
+        );
+
+        // The values are copies, not shared
+        e.detach::<Tag>();
+        assert_eq!(clone.components_of::<Tag>().len(), 2);
+    }
+
+    #[test]
+    fn clone_empty_entity() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A).detach::<A>();
+
+        assert!(matches!(
+            e.try_clone_entity(),
+            Err(crate::Error::EmptyBundle)
+        ));
+        assert!(matches!(
+            db.entity(12345).try_clone_entity(),
+            Err(crate::Error::EmptyBundle)
+        ));
+        assert_eq!(db.all_entities().count(), 1);
+    }
+
     #[test]
     fn modify_component() -> Result<(), anyhow::Error> {
         let ecs = super::Ecs::open_in_memory()?;
@@ -931,6 +2159,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn concurrent_modify_component() {
+        #[derive(Component, Debug, Default, Deserialize, Serialize, PartialEq)]
+        struct Counter(u64);
+
+        let path = crate::test_util::TempDb::new("modify-component");
+        let id = Ecs::open(&path)
+            .unwrap()
+            .new_entity()
+            .attach(Counter(0))
+            .id();
+
+        let spawn = || {
+            let path = path.path().to_path_buf();
+            std::thread::spawn(move || {
+                let db = Ecs::open(&path).unwrap();
+                for _ in 0..100 {
+                    db.entity(id).modify_component(|Counter(c)| *c += 1);
+                }
+            })
+        };
+
+        let (a, b) = (spawn(), spawn());
+        a.join().unwrap();
+        b.join().unwrap();
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(db.entity(id).component(), Some(Counter(200)));
+        assert!(db.raw_sql().is_autocommit());
+    }
+
     #[test]
     fn destroy() {
         #[derive(Serialize, Deserialize, Component)]
@@ -1006,11 +2265,9 @@ mod tests {
 
         e.detach::<A>();
 
-        assert!(
-            !e.exists(),
-            "entity should not exist after detaching last component, but still has: {:?}",
-            e.component_names().collect::<Vec<_>>()
-        );
+        // Entities exist until destroyed, even without components
+        assert!(e.exists());
+        assert_eq!(e.component_names().count(), 0);
         let row_count: i64 = db
             .conn
             .query_row(
@@ -1022,6 +2279,24 @@ mod tests {
         assert_eq!(row_count, 0);
     }
 
+    #[test]
+    fn exists_until_destroyed() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+        assert!(e.exists());
+
+        e.detach::<A>();
+        assert!(e.exists());
+        assert!(db.entity_with::<A>(e.id()).is_none());
+
+        e.attach(B);
+        assert!(e.has::<B>());
+
+        e.destroy();
+        assert!(!e.exists());
+        assert!(!db.entity(12345).exists());
+    }
+
     #[test]
     fn detach_partial_preserves_timestamps() {
         #[derive(Serialize, Deserialize, Component)]
@@ -1070,59 +2345,1674 @@ mod tests {
     }
 
     #[test]
-    fn try_detach_all_removes_all_components() {
+    fn attach_reporting() {
         let db = Ecs::open_in_memory().unwrap();
-        let e = db.new_entity().attach(A).attach(B).attach(C);
+        let (e, changed) = db
+            .new_entity()
+            .attach(A)
+            .attach_reporting(ComponentWithData(1));
+        assert!(changed);
 
-        assert!(e.has::<(A, B, C)>());
+        let (e, changed) = e.attach_reporting(ComponentWithData(1));
+        assert!(!changed);
 
-        e.try_detach_all().unwrap();
+        let (e, changed) = e.attach_reporting((A, ComponentWithData(1)));
+        assert!(!changed);
 
-        assert!(!e.has::<A>());
-        assert!(!e.has::<B>());
-        assert!(!e.has::<C>());
-        assert!(
-            !e.exists(),
-            "entity should not exist after detach_all components={:?}",
-            e.component_names().collect::<Vec<_>>()
-        );
+        let (_, changed) = e.attach_reporting((A, ComponentWithData(2)));
+        assert!(changed);
+    }
 
-        let row_count: i64 = db
-            .conn
-            .query_row(
-                "select count(*) from components where entity = ?1",
-                [e.id()],
-                |row| row.get(0),
+    #[test]
+    fn schema_version_migrations() {
+        let path = crate::test_util::TempDb::new("schema");
+        let user_version = |conn: &rusqlite::Connection| -> i64 {
+            conn.pragma_query_value(None, "user_version", |row| row.get(0))
+                .unwrap()
+        };
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(user_version(db.raw_sql()), crate::SCHEMA_VERSION);
+        let e = db.new_entity().attach(A).id();
+        db.close().unwrap();
+
+        // Simulate a database from before the entities table
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch("pragma user_version = 0; drop table entities;")
+            .unwrap();
+        drop(conn);
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(user_version(db.raw_sql()), crate::SCHEMA_VERSION);
+        assert!(db.entity(e).exists());
+        assert!(db.new_entity().attach(A).id() > e);
+        db.close().unwrap();
+
+        // Migrations can be re-run, e.g. after an interrupted open
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        drop(conn);
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(user_version(db.raw_sql()), crate::SCHEMA_VERSION);
+        assert_eq!(db.all_entities().count(), 2);
+        db.close().unwrap();
+
+        // Databases from the future are refused
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", crate::SCHEMA_VERSION + 1)
+            .unwrap();
+        drop(conn);
+
+        assert!(matches!(
+            Ecs::open(&path),
+            Err(crate::Error::UnsupportedSchemaVersion { found, supported })
+                if found == crate::SCHEMA_VERSION + 1 && supported == crate::SCHEMA_VERSION
+        ));
+    }
+
+    #[test]
+    fn concurrent_entity_ids_are_unique() {
+        let path = crate::test_util::TempDb::new("entity-ids");
+        Ecs::open(&path).unwrap().close().unwrap();
+
+        let spawn = || {
+            let path = path.path().to_path_buf();
+            std::thread::spawn(move || {
+                let db = Ecs::open(&path).unwrap();
+                (0..100)
+                    .map(|_| db.new_entity().attach((A, B)).id())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let (a, b) = (spawn(), spawn());
+        let mut ids = a.join().unwrap();
+        ids.extend(b.join().unwrap());
+
+        let unique = ids.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), 200);
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(db.query::<EntityId, (A, B)>().count(), 200);
+
+        // Ids aren't reused after an entity is destroyed
+        let last = db.new_entity().attach(A);
+        last.destroy();
+        assert!(db.new_entity().attach(A).id() > last.id());
+
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn deserialize_error_mentions_component() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+        db.conn
+            .execute(
+                "insert into components (entity, component, data) values (?1, ?2, 'not json')",
+                rusqlite::params![e.id(), ComponentWithData::NAME],
             )
             .unwrap();
-        assert_eq!(row_count, 0);
+
+        let err = e.try_component::<ComponentWithData>().unwrap_err();
+        let super::Error::ComponentStorage(super::component::StorageError::Deserialize {
+            component,
+            ..
+        }) = &err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(*component, ComponentWithData::NAME);
+
+        let message = err.to_string();
+        assert!(message.contains(ComponentWithData::NAME), "{message}");
+        assert!(message.contains("not json"), "{message}");
     }
 
     #[test]
-    fn detach_all_removes_all_components() {
+    fn lenient_and_strict_queries() {
         let db = Ecs::open_in_memory().unwrap();
-        let e = db.new_entity().attach(A).attach(B).attach(C);
+        let a = db.new_entity().attach(ComponentWithData(1));
+        let corrupt = db.new_entity().attach(A);
+        let b = db.new_entity().attach(ComponentWithData(2));
+        db.conn
+            .execute(
+                "insert into components (entity, component, data) values (?1, ?2, 'not json')",
+                rusqlite::params![corrupt.id(), ComponentWithData::NAME],
+            )
+            .unwrap();
 
-        assert!(e.has::<(A, B, C)>());
+        let lenient = Query::<(EntityId, ComponentWithData)>::new(&db)
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lenient,
+            vec![
+                (a.id(), ComponentWithData(1)),
+                (b.id(), ComponentWithData(2))
+            ]
+        );
 
-        e.detach_all();
+        assert!(matches!(
+            Query::<(EntityId, ComponentWithData)>::new(&db)
+                .strict()
+                .try_iter()
+                .map(|iter| iter.count()),
+            Err(super::Error::ComponentStorage(_))
+        ));
 
-        assert!(!e.has::<A>());
-        assert!(!e.has::<B>());
-        assert!(!e.has::<C>());
-        assert!(!e.exists());
+        // Entities aren't deserialized, so strict mode doesn't fail
+        assert_eq!(
+            Query::<EntityId, ComponentWithData>::new(&db)
+                .strict()
+                .iter()
+                .count(),
+            3
+        );
     }
 
     #[test]
-    fn detach_all_does_not_affect_other_entities() {
+    fn optional_components_report_corrupt_rows() {
         let db = Ecs::open_in_memory().unwrap();
-        let e1 = db.new_entity().attach(A).attach(B);
-        let e2 = db.new_entity().attach(A).attach(C);
+        let missing = db.new_entity().attach(A);
+        let e = db.new_entity().attach(A);
+        db.conn
+            .execute(
+                "insert into components (entity, component, data) values (?1, ?2, 'not json')",
+                rusqlite::params![e.id(), ComponentWithData::NAME],
+            )
+            .unwrap();
 
-        e1.detach_all();
+        assert!(matches!(
+            missing.try_get::<(A, Option<ComponentWithData>)>(),
+            Ok(Some((A, None)))
+        ));
+        assert!(matches!(
+            e.try_get::<(A, Option<ComponentWithData>)>(),
+            Err(super::Error::ComponentStorage(
+                super::component::StorageError::Deserialize { .. }
+            ))
+        ));
+
+        assert!(matches!(
+            e.try_get::<(A, ComponentWithData)>(),
+            Err(super::Error::ComponentStorage(_))
+        ));
+    }
 
-        assert!(!e1.exists());
-        assert!(e2.exists());
-        assert!(e2.has::<(A, C, CreatedAt, LastUpdated)>());
+    #[test]
+    fn lazy_query_data() {
+        let db = Ecs::open_in_memory().unwrap();
+        for n in 0..10 {
+            db.new_entity().attach(ComponentWithData(n));
+        }
+        let corrupt = db.new_entity().attach(A);
+        db.conn
+            .execute(
+                "insert into components (entity, component, data) values (?1, ?2, 'not json')",
+                rusqlite::params![corrupt.id(), ComponentWithData::NAME],
+            )
+            .unwrap();
+
+        let mut lazy_rows = 0;
+        let mut even = Vec::new();
+        for (id, data) in Query::<(EntityId, Lazy<ComponentWithData>)>::new(&db)
+            .strict()
+            .iter()
+        {
+            lazy_rows += 1;
+            if id != corrupt.id() && id % 2 == 0 {
+                even.push(data.get().unwrap());
+            }
+        }
+
+        assert_eq!(lazy_rows, 11);
+        assert_eq!(even.len(), 5);
+        assert!(
+            Query::<Lazy<ComponentWithData>, ()>::new(&db)
+                .iter()
+                .any(|data| data.get().is_err())
+        );
+    }
+
+    #[test]
+    fn iter_entities_with() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(ComponentWithData(1));
+        db.new_entity().attach(A);
+        let b = db.new_entity().attach(ComponentWithData(2));
+
+        let pairs = Query::<ComponentWithData>::new(&db)
+            .iter_entities_with()
+            .map(|(e, data)| (e.id(), data))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                (a.id(), ComponentWithData(1)),
+                (b.id(), ComponentWithData(2))
+            ]
+        );
+
+        for (e, data) in Query::<ComponentWithData>::new(&db).iter_entities_with() {
+            assert_eq!(e.component::<ComponentWithData>(), Some(data));
+        }
+    }
+
+    #[test]
+    fn has_any_and_has_none() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+
+        assert!(e.has_any::<(A, B)>());
+        assert!(!e.has_any::<(B, C)>());
+        assert!(e.has_none::<(B, C)>());
+        assert!(!e.has_none::<(A, B)>());
+    }
+
+    #[test]
+    fn component_names_and_entities_with_component() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach((A, B));
+        let b = db.new_entity().attach(B);
+        db.new_entity().attach(ComponentWithData(1));
+
+        let names = db.component_names().unwrap();
+        for name in [
+            A::NAME,
+            B::NAME,
+            ComponentWithData::NAME,
+            CreatedAt::NAME,
+            LastUpdated::NAME,
+        ] {
+            assert!(names.contains(&name.to_string()), "{name} in {names:?}");
+        }
+        assert_eq!(names.len(), 5);
+        assert!(names.is_sorted());
+
+        assert_eq!(
+            db.entities_with_component(B::NAME)
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+            vec![a.id(), b.id()]
+        );
+        assert_eq!(db.entities_with_component("ecsdb::Missing").count(), 0);
+    }
+
+    #[test]
+    fn spawn_batch() {
+        let db = Ecs::open_in_memory().unwrap();
+
+        let e = db.spawn((A, B));
+        assert!(e.has::<(A, B)>());
+        assert!(db.spawn_empty().attach(C).has::<C>());
+
+        #[derive(Debug, Bundle)]
+        struct Spawned {
+            b: B,
+            data: ComponentWithData,
+        }
+
+        let entities = db
+            .spawn_batch((0..50).map(|i| Spawned {
+                b: B,
+                data: ComponentWithData(i),
+            }))
+            .unwrap();
+        assert_eq!(entities.len(), 50);
+        assert!(entities.windows(2).all(|w| w[0].id() < w[1].id()));
+        for (i, e) in entities.iter().enumerate() {
+            assert_eq!(
+                e.component::<ComponentWithData>(),
+                Some(ComponentWithData(i as u64))
+            );
+        }
+        assert_eq!(db.query::<EntityId, (B, ComponentWithData)>().count(), 50);
+    }
+
+    #[test]
+    fn spawn_with_id() {
+        let db = Ecs::open_in_memory().unwrap();
+
+        let e = db.spawn_with_id(999);
+        assert_eq!(e.id(), 999);
+        assert!(db.entity(999).exists());
+
+        e.attach(ComponentWithData(7));
+        assert_eq!(
+            db.entity(999).component::<ComponentWithData>(),
+            Some(ComponentWithData(7))
+        );
+
+        // Spawning an existing id keeps its components
+        assert!(db.spawn_with_id(999).has::<ComponentWithData>());
+
+        // Attaching to an unknown id creates the entity as well
+        let imported = db.entity(5000).attach(A);
+        assert!(imported.exists());
+
+        assert!(db.new_entity().attach(A).id() > 5000);
+    }
+
+    #[test]
+    fn all_entities() {
+        let db = Ecs::open_in_memory().unwrap();
+        db.world_entity().attach(ComponentWithData(1));
+
+        let a = db.new_entity().attach(A);
+        let b = db.new_entity().attach(B);
+        let imported = db.spawn_with_id(1000);
+        a.detach::<A>();
+        assert!(!a.has::<CreatedAt>());
+
+        let ids = |db: &Ecs| db.all_entities().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&db), [a.id(), b.id(), imported.id()]);
+
+        b.destroy();
+        assert_eq!(ids(&db), [a.id(), imported.id()]);
+
+        let missing_created_at: i64 = db
+            .raw_sql()
+            .query_row(
+                "select count(*) from entities where created_at is null",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(missing_created_at, 0);
+    }
+
+    #[test]
+    fn try_spawn_errors() {
+        #[derive(Debug, Default, Serialize, Deserialize, Component)]
+        struct Counter(u64);
+
+        let db = Ecs::open_in_memory().unwrap();
+        db.raw_sql()
+            .pragma_update(None, "query_only", true)
+            .unwrap();
+
+        assert!(matches!(
+            db.try_create_entity(),
+            Err(crate::Error::Database(_))
+        ));
+        assert!(matches!(db.try_spawn(A), Err(crate::Error::Database(_))));
+        assert!(db.try_spawn_batch([A, A]).is_err());
+        assert!(db.new_entity().try_attach((A, B)).is_err());
+        assert!(matches!(
+            db.new_entity().try_modify_component::<Counter>(|c| {
+                c.0 += 1;
+                Ok(())
+            }),
+            Err(crate::entity::ModifyComponentError::Ecs(_))
+        ));
+        assert!(db.query::<Entity, ()>().next().is_none());
+
+        db.raw_sql()
+            .pragma_update(None, "query_only", false)
+            .unwrap();
+        assert!(db.is_empty().unwrap());
+
+        let e = db.create_entity();
+        assert!(e.exists());
+        assert_eq!(e.component_names().count(), 0);
+        assert!(db.try_spawn(A).unwrap().has::<A>());
+    }
+
+    #[test]
+    fn entity_count() {
+        let db = Ecs::open_in_memory().unwrap();
+        assert_eq!(db.entity_count().unwrap(), 0);
+        assert!(db.is_empty().unwrap());
+
+        db.world_entity().attach(ComponentWithData(1));
+        assert!(db.is_empty().unwrap());
+
+        let e = db.new_entity().attach(A);
+        db.new_entity().attach((A, B));
+        assert_eq!(db.entity_count().unwrap(), 2);
+        assert!(!db.is_empty().unwrap());
+
+        e.destroy();
+        assert_eq!(db.entity_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn get_bundle() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach((B, ComponentWithData(42)));
+
+        let (b, data, c) = e.get::<(B, ComponentWithData, Option<C>)>().unwrap();
+        assert_eq!(Some(b), e.component::<B>());
+        assert_eq!(Some(data), e.component::<ComponentWithData>());
+        assert_eq!(c, e.component::<C>());
+
+        assert!(e.get::<(B, C)>().is_none());
+    }
+
+    #[test]
+    fn statement_cache_capacity() {
+        let db = Ecs::open_in_memory().unwrap();
+
+        for capacity in [0, 64] {
+            db.set_statement_cache_capacity(capacity);
+            db.spawn_batch((0..5_000).map(ComponentWithData));
+            db.query::<EntityId, ComponentWithData>().count();
+        }
+
+        assert_eq!(db.query::<EntityId, ComponentWithData>().count(), 10_000);
+        assert_eq!(
+            db.query::<ComponentWithData, ()>()
+                .map(|c| c.0)
+                .sum::<u64>(),
+            2 * (0..5_000).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn query_stats() {
+        let db = Ecs::open_in_memory().unwrap();
+        db.query::<EntityId, A>().count();
+        assert!(db.query_stats().is_empty());
+
+        let options = crate::EcsOptions {
+            query_stats: true,
+            ..Default::default()
+        };
+        let db = Ecs::from_rusqlite_with(rusqlite::Connection::open_in_memory().unwrap(), options)
+            .unwrap();
+        db.new_entity().attach((A, B));
+
+        for _ in 0..3 {
+            db.query::<EntityId, A>().count();
+        }
+        db.query::<EntityId, B>().count();
+
+        let stats = db.query_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats.iter().map(|s| s.count).sum::<u64>(), 4);
+        assert!(stats.iter().any(|s| s.count == 3));
+        assert!(stats.iter().all(|s| !s.total_duration.is_zero()));
+        assert!(stats.iter().all(|s| s.sql.contains("components")));
+    }
+
+    #[test]
+    fn find_any() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Label(String);
+
+        let db = Ecs::open_in_memory().unwrap();
+        let one = db.new_entity().attach(ComponentWithData(1));
+        db.new_entity().attach(ComponentWithData(2));
+        let x = db.new_entity().attach(Label("x".into()));
+        db.new_entity().attach(Label("y".into()));
+
+        assert_eq!(
+            db.find(crate::any![ComponentWithData(1), Label("x".into())])
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+            [one.id(), x.id()]
+        );
+        assert_eq!(db.find(crate::any![]).count(), 0);
+    }
+
+    #[test]
+    fn date_range_queries() {
+        #[derive(Debug, Clone, Copy, Serialize, Deserialize, Component, PartialEq)]
+        struct Date(chrono::NaiveDate);
+
+        #[derive(Debug, Clone, Copy, Serialize, Deserialize, Component, PartialEq)]
+        struct Written(chrono::DateTime<chrono::FixedOffset>);
+
+        let date = |s: &str| Date(s.parse().unwrap());
+        let written = |s: &str| Written(chrono::DateTime::parse_from_rfc3339(s).unwrap());
+
+        let db = Ecs::open_in_memory().unwrap();
+        for (d, w) in [
+            ("2023-12-31", "2023-12-31T20:00:00+00:00"),
+            ("2024-01-09", "2024-01-09T08:00:00+00:00"),
+            ("2024-01-31", "2024-01-31T23:30:00-02:00"),
+            ("2024-02-01", "2024-02-01T00:30:00+00:00"),
+            ("2024-02-10", "2024-02-10T12:00:00+05:00"),
+            ("2024-10-01", "2024-10-01T09:00:00+00:00"),
+        ] {
+            db.new_entity().attach((date(d), written(w)));
+        }
+
+        let dates = db
+            .query_filtered::<Date, ()>(date("2024-01-09")..date("2024-02-09"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dates,
+            [date("2024-01-09"), date("2024-01-31"), date("2024-02-01")]
+        );
+
+        // 2024-01-31T23:30:00-02:00 is 01:30 UTC on February 1st
+        let mut february = db
+            .query_filtered::<Written, ()>(
+                written("2024-02-01T00:00:00+00:00")..written("2024-03-01T00:00:00+00:00"),
+            )
+            .map(|Written(w)| w.to_utc())
+            .collect::<Vec<_>>();
+        february.sort();
+        assert_eq!(
+            february,
+            [
+                written("2024-02-01T00:30:00+00:00").0.to_utc(),
+                written("2024-01-31T23:30:00-02:00").0.to_utc(),
+                written("2024-02-10T12:00:00+05:00").0.to_utc(),
+            ]
+        );
+    }
+
+    #[test]
+    fn attach_empty_bundle_to_new_entity() {
+        let db = Ecs::open_in_memory().unwrap();
+
+        assert!(matches!(
+            db.new_entity().try_attach((None::<A>,)),
+            Err(crate::Error::EmptyBundle)
+        ));
+        assert!(matches!(
+            db.new_entity().try_attach((None::<A>, None::<B>)),
+            Err(crate::Error::EmptyBundle)
+        ));
+        assert_eq!(db.entity_count().unwrap(), 0);
+
+        let e = db.new_entity().try_attach((None::<A>, Some(B))).unwrap();
+        assert!(e.has::<B>());
+    }
+
+    #[test]
+    fn read_only_connection() {
+        let path = crate::test_util::TempDb::new("read-only");
+        let options = crate::EcsOptions {
+            busy_timeout: Some(std::time::Duration::from_millis(250)),
+            ..Default::default()
+        };
+        let mut db = Ecs::open_with(&path, options).unwrap();
+        db.set_strict_components(true);
+        db.register_component::<A>();
+        db.new_entity().attach(A);
+
+        let worker = db.open_read_only().unwrap().unwrap();
+        let busy_timeout: i64 = worker
+            .conn
+            .pragma_query_value(None, "busy_timeout", |row| row.get(0))
+            .unwrap();
+        assert_eq!(busy_timeout, 250);
+        assert_eq!(worker.query::<EntityId, A>().count(), 1);
+        assert!(matches!(
+            worker.new_entity().try_attach(A),
+            Err(crate::Error::Database(_))
+        ));
+        // Shares the registry of `db`
+        assert!(matches!(
+            worker.new_entity().try_attach(B),
+            Err(crate::Error::UnregisteredComponent(_))
+        ));
+        worker.close().unwrap();
+
+        assert!(
+            Ecs::open_in_memory()
+                .unwrap()
+                .open_read_only()
+                .unwrap()
+                .is_none()
+        );
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn db_id() {
+        let path = crate::test_util::TempDb::new("db-id");
+
+        let db = Ecs::open(&path).unwrap();
+        let id = db.db_id().unwrap();
+        assert_eq!(db.db_id().unwrap(), id);
+        let e = db.new_entity().attach(A).id();
+        db.close().unwrap();
+
+        let db = Ecs::open(&path).unwrap();
+        assert_eq!(db.db_id().unwrap(), id);
+        let tagged = db.entity(e).tagged_id();
+        assert_eq!(tagged, crate::TaggedEntityId { db: id, id: e });
+        assert_eq!(db.entity_checked(tagged).unwrap().id(), e);
+
+        let other = Ecs::open_in_memory().unwrap();
+        let foreign = other.new_entity().attach(A).tagged_id();
+        assert_ne!(foreign.db, id);
+        assert!(matches!(
+            db.entity_checked(foreign),
+            Err(crate::Error::ForeignEntity { db: f, expected, .. }) if f == foreign.db && expected == id
+        ));
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn prefetch_count() {
+        let db = Ecs::open_in_memory().unwrap();
+        db.spawn_batch((0..100_000).map(ComponentWithData));
+        db.new_entity().attach(A);
+
+        let query = Query::<EntityId, ComponentWithData>::new(&db);
+        assert_eq!(query.count(), 100_000);
+        assert_eq!(query.limit(10).count(), 10);
+
+        let query = Query::<EntityId, ComponentWithData>::new(&db).prefetch_count();
+        assert_eq!(query.iter().count(), 100_000);
+        assert_eq!(query.reverse_iter().next(), query.iter().last());
+    }
+
+    #[test]
+    fn with_absent_component_skips_query() {
+        #[derive(Serialize, Deserialize, Component)]
+        struct Nonexistent;
+
+        let options = crate::EcsOptions {
+            query_stats: true,
+            ..Default::default()
+        };
+        let db = Ecs::from_rusqlite_with(rusqlite::Connection::open_in_memory().unwrap(), options)
+            .unwrap();
+        db.new_entity().attach(A);
+
+        assert_eq!(db.query::<EntityId, Nonexistent>().count(), 0);
+        assert_eq!(db.query::<EntityId, (A, Nonexistent)>().count(), 0);
+        assert_eq!(Query::<EntityId, Nonexistent>::new(&db).count(), 0);
+        Query::<EntityId, With<Nonexistent>>::new(&db).for_each_while(|_| unreachable!());
+        assert!(db.query_stats().is_empty());
+
+        // `Without` an absent component still matches
+        assert_eq!(db.query::<EntityId, Without<Nonexistent>>().count(), 1);
+        assert_eq!(db.query_stats().len(), 1);
+    }
+
+    #[test]
+    fn entities_by_ids() {
+        let db = Ecs::open_in_memory().unwrap();
+        let ids = db
+            .spawn_batch((0..1000).map(ComponentWithData))
+            .into_iter()
+            .map(|e| e.id())
+            .collect::<Vec<_>>();
+
+        // Every fifth entity, twice, in reverse, plus unknown ids
+        let mut wanted = ids.iter().step_by(5).copied().collect::<Vec<_>>();
+        assert_eq!(wanted.len(), 200);
+        let lookup = wanted
+            .iter()
+            .rev()
+            .chain(wanted.iter())
+            .copied()
+            .chain([-1, 1_000_000])
+            .collect::<Vec<_>>();
+
+        let found = db.entities_by_ids(&lookup).collect::<Vec<_>>();
+        wanted.sort();
+        assert_eq!(found.iter().map(|e| e.id()).collect::<Vec<_>>(), wanted);
+        assert!(found.iter().all(|e| e.exists()));
+        assert!(found.iter().all(|e| e.get::<ComponentWithData>().is_some()));
+    }
+
+    #[test]
+    fn get_by_ids() {
+        let db = Ecs::open_in_memory().unwrap();
+        let with_a = db.new_entity().attach((ComponentWithData(1), A));
+        let without_a = db.new_entity().attach(ComponentWithData(2));
+        let without_data = db.new_entity().attach(A);
+
+        let ids = [without_data.id(), without_a.id(), with_a.id(), -1];
+        let found = db
+            .get_by_ids::<(ComponentWithData, Option<A>)>(&ids)
+            .map(|(e, (data, a))| (e.id(), data.0, a.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, [(with_a.id(), 1, true), (without_a.id(), 2, false)]);
+
+        let found = db
+            .get_by_ids::<A>(&ids)
+            .map(|(e, _)| e.id())
+            .collect::<Vec<_>>();
+        assert_eq!(found, [with_a.id(), without_data.id()]);
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+
+        assert!(e.compare_and_swap(None, ComponentWithData(1)));
+        assert!(!e.compare_and_swap(None, ComponentWithData(2)));
+        assert!(!e.compare_and_swap(Some(ComponentWithData(5)), ComponentWithData(2)));
+        assert_eq!(
+            e.component::<ComponentWithData>(),
+            Some(ComponentWithData(1))
+        );
+
+        assert!(e.compare_and_swap(Some(ComponentWithData(1)), ComponentWithData(2)));
+        assert_eq!(
+            e.component::<ComponentWithData>(),
+            Some(ComponentWithData(2))
+        );
+    }
+
+    #[test]
+    fn patch_component() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Profile {
+            name: String,
+            visits: u64,
+        }
+
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach(Profile {
+            name: "ecsdb".into(),
+            visits: 1,
+        });
+
+        entity.patch_component::<Profile>(serde_json::json!({ "visits": 2 }));
+        assert_eq!(
+            entity.component::<Profile>(),
+            Some(Profile {
+                name: "ecsdb".into(),
+                visits: 2
+            })
+        );
+
+        // Patches not deserializing as `Profile` are rolled back
+        assert!(
+            entity
+                .try_patch_component::<Profile>(serde_json::json!({ "visits": "many" }))
+                .is_err()
+        );
+        assert_eq!(entity.component::<Profile>().unwrap().visits, 2);
+
+        // Missing components aren't created
+        let empty = db.new_entity().attach(A);
+        empty.patch_component::<Profile>(serde_json::json!({ "visits": 2 }));
+        assert!(!empty.has::<Profile>());
+    }
+
+    #[test]
+    fn patch_component_checks_and_hooks() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Profile {
+            visits: u64,
+        }
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach(Profile { visits: 1 }).id();
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        db.on_change({
+            let changes = changes.clone();
+            move |action, _entity, _component| changes.lock().unwrap().push(action)
+        })
+        .unwrap();
+        db.on_attach::<Profile>(|_, _| panic!("patching isn't an attach"));
+
+        db.entity(entity)
+            .patch_component::<Profile>(serde_json::json!({ "visits": 2 }));
+        assert_eq!(*changes.lock().unwrap(), [crate::ChangeAction::Update]);
+
+        db.set_strict_components(true);
+        assert!(matches!(
+            db.entity(entity)
+                .try_patch_component::<Profile>(serde_json::json!({ "visits": 3 })),
+            Err(crate::Error::UnregisteredComponent(_))
+        ));
+        assert_eq!(db.entity(entity).component::<Profile>().unwrap().visits, 2);
+    }
+
+    #[test]
+    fn attach_expecting_version() {
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach(ComponentWithData(1));
+        let other = db.new_entity().attach(A);
+
+        let version = entity.version();
+        assert!(version > 0);
+
+        // A concurrent edit moves the version
+        entity.attach(ComponentWithData(2));
+        assert!(entity.version() > version);
+        other.attach(B);
+
+        let stale = entity.attach_expecting_version(ComponentWithData(3), version);
+        assert!(matches!(
+            stale,
+            Err(crate::Error::Conflict(crate::ConflictError { entity: e, expected, .. }))
+                if e == entity.id() && expected == version
+        ));
+        assert_eq!(
+            entity.component::<ComponentWithData>(),
+            Some(ComponentWithData(2))
+        );
+
+        let current = entity.version();
+        entity
+            .attach_expecting_version(ComponentWithData(3), current)
+            .unwrap();
+        assert_eq!(
+            entity.component::<ComponentWithData>(),
+            Some(ComponentWithData(3))
+        );
+        assert!(entity.version() > current);
+
+        // Destroying is a write, too. The version doesn't start over.
+        let before_destroy = entity.version();
+        let eid = entity.id();
+        entity.destroy();
+        assert!(db.entity(eid).version() > before_destroy);
+    }
+
+    #[test]
+    fn multi_component_writes_bump_version() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Tag(String);
+        impl crate::MultiComponent for Tag {}
+
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db.new_entity().attach_multi(Tag("a".into()));
+
+        let version = entity.version();
+        entity.attach_multi(Tag("b".into()));
+        assert!(entity.version() > version);
+
+        let version = entity.version();
+        entity.detach::<Tag>();
+        assert!(entity.version() > version);
+    }
+
+    #[test]
+    fn take() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach((A, ComponentWithData(1)));
+
+        assert_eq!(e.take::<ComponentWithData>(), Some(ComponentWithData(1)));
+        assert!(!e.has::<ComponentWithData>());
+        assert!(e.has::<A>());
+        assert_eq!(e.take::<ComponentWithData>(), None);
+    }
+
+    #[test]
+    fn collect_owned() {
+        let db = Ecs::open_in_memory().unwrap();
+        for i in 0..5 {
+            db.new_entity().attach(ComponentWithData(i));
+        }
+
+        let owned = Query::<(EntityId, ComponentWithData)>::new(&db).collect_owned();
+        assert_eq!(owned.len(), 5);
+
+        for (eid, ComponentWithData(i)) in owned {
+            db.entity(eid).attach(ComponentWithData(i * 10));
+        }
+
+        assert_eq!(
+            db.query::<ComponentWithData, ()>().collect::<Vec<_>>(),
+            (0..5)
+                .map(|i| ComponentWithData(i * 10))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dyn_component_deserialize() {
+        #[derive(Serialize, Deserialize, Component)]
+        struct Position {
+            x: f64,
+            y: f64,
+            label: String,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Point {
+            x: f64,
+            y: f64,
+        }
+
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(Position {
+            x: 1.0,
+            y: 2.0,
+            label: "home".into(),
+        });
+
+        let dyn_component = e.dyn_component(Position::NAME).unwrap();
+        assert_eq!(
+            dyn_component.deserialize::<Point>().unwrap(),
+            Point { x: 1.0, y: 2.0 }
+        );
+        assert_eq!(
+            dyn_component.deserialize::<serde_json::Value>().unwrap()["label"],
+            "home"
+        );
+        assert!(dyn_component.deserialize::<Vec<u64>>().is_err());
+        assert!(matches!(
+            dyn_component.as_value(),
+            rusqlite::types::Value::Text(s) if s.contains("home")
+        ));
+    }
+
+    #[test]
+    fn dyn_attach_all() {
+        let db = Ecs::open_in_memory().unwrap();
+
+        let components = vec![
+            crate::DynComponent::from_typed(&B).unwrap(),
+            crate::DynComponent::from_json(ComponentWithData::NAME, &serde_json::json!(42))
+                .unwrap(),
+        ];
+        let e = db.new_entity().dyn_attach_all(components);
+        assert!(e.has::<B>());
+        assert_eq!(
+            e.component::<ComponentWithData>(),
+            Some(ComponentWithData(42))
+        );
+
+        let updated = ComponentWithData(23);
+        let e = e.dyn_attach_all(vec![
+            crate::DynComponent::from_typed(&updated).unwrap(),
+            crate::DynComponent::from_typed(&C).unwrap(),
+        ]);
+        assert!(e.has::<(B, C)>());
+        assert_eq!(e.component::<ComponentWithData>(), Some(updated));
+
+        let empty = db.new_entity().dyn_attach_all(vec![]);
+        assert!(empty.exists());
+        assert_eq!(empty.component_names().count(), 0);
+    }
+
+    #[test]
+    fn tag_untag_and_toggle() {
+        #[derive(Debug, Default, Serialize, Deserialize, Component)]
+        struct Pinned;
+
+        #[derive(Debug, Default, Serialize, Deserialize, Component)]
+        struct Hidden;
+
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A).tag::<(Pinned, Hidden)>();
+        assert!(e.has::<(Pinned, Hidden)>());
+
+        let e = e.untag::<Hidden>();
+        assert!(!e.has::<Hidden>());
+
+        assert!(!e.toggle::<Pinned>());
+        assert!(!e.has::<Pinned>());
+        assert!(e.toggle::<Pinned>());
+        assert!(e.has::<Pinned>());
+        assert!(!e.toggle::<Pinned>());
+        assert!(!e.has::<Pinned>());
+    }
+
+    #[test]
+    fn try_detach_all_removes_all_components() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A).attach(B).attach(C);
+
+        assert!(e.has::<(A, B, C)>());
+
+        e.try_detach_all().unwrap();
+
+        assert!(!e.has::<A>());
+        assert!(!e.has::<B>());
+        assert!(!e.has::<C>());
+        assert!(e.exists());
+
+        let row_count: i64 = db
+            .conn
+            .query_row(
+                "select count(*) from components where entity = ?1",
+                [e.id()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(row_count, 0);
+    }
+
+    #[test]
+    fn detach_all_removes_all_components() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A).attach(B).attach(C);
+
+        assert!(e.has::<(A, B, C)>());
+
+        e.detach_all();
+
+        assert!(!e.has::<A>());
+        assert!(!e.has::<B>());
+        assert!(!e.has::<C>());
+        assert!(e.exists());
+    }
+
+    #[test]
+    fn detach_all_does_not_affect_other_entities() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e1 = db.new_entity().attach(A).attach(B);
+        let e2 = db.new_entity().attach(A).attach(C);
+
+        e1.detach_all();
+
+        assert!(!e1.has_any::<(A, B)>());
+        assert!(e2.exists());
+        assert!(e2.has::<(A, C, CreatedAt, LastUpdated)>());
+    }
+
+    #[test]
+    fn checkpoint_truncates_wal() {
+        let path = crate::test_util::TempDb::new("checkpoint");
+        let db = Ecs::open(&path).unwrap();
+
+        for n in 0..100 {
+            db.new_entity().attach(ComponentWithData(n));
+        }
+
+        assert!(db.wal_size().unwrap() > 0);
+
+        db.checkpoint(super::CheckpointMode::Truncate).unwrap();
+        assert_eq!(db.wal_size().unwrap(), 0);
+
+        db.vacuum().unwrap();
+        assert_eq!(db.query::<EntityId, ComponentWithData>().count(), 100);
+
+        // A reader on an older snapshot keeps the checkpoint from completing
+        let reader = rusqlite::Connection::open(&path).unwrap();
+        reader
+            .execute_batch("begin; select count(*) from components;")
+            .unwrap();
+        db.new_entity().attach(ComponentWithData(100));
+        db.raw_sql()
+            .busy_timeout(std::time::Duration::ZERO)
+            .unwrap();
+        assert!(matches!(
+            db.checkpoint(super::CheckpointMode::Full),
+            Err(crate::Error::CheckpointBusy)
+        ));
+        reader.execute_batch("commit").unwrap();
+        drop(reader);
+        db.checkpoint(super::CheckpointMode::Full).unwrap();
+
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn component_stats() {
+        let db = Ecs::open_in_memory().unwrap();
+        db.new_entity().attach(A).attach(B);
+        db.new_entity().attach(A).attach(ComponentWithData(1));
+        db.new_entity().attach(A);
+        db.attach_resource(ComponentWithData(42));
+
+        let stats = db.component_stats().unwrap();
+        assert_eq!(stats.entities, 3);
+        assert_eq!(stats.components[A::NAME], 3);
+        assert_eq!(stats.components[B::NAME], 1);
+        assert_eq!(stats.components[ComponentWithData::NAME], 1);
+        assert_eq!(stats.components[CreatedAt::NAME], 3);
+        assert!(!stats.components.contains_key(C::NAME));
+    }
+
+    #[test]
+    fn distinct_values() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq, Eq, PartialOrd, Ord)]
+        struct Category(String);
+
+        let db = Ecs::open_in_memory().unwrap();
+        db.new_entity().attach(Category("a".into()));
+        db.new_entity().attach(Category("a".into()));
+        db.new_entity().attach(Category("b".into()));
+
+        let mut values = db.distinct_values::<Category>().unwrap();
+        values.sort();
+        assert_eq!(values, vec![Category("a".into()), Category("b".into())]);
+    }
+
+    #[test]
+    fn one_of() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        enum Status {
+            Open,
+            Pending,
+            Closed,
+        }
+
+        let db = Ecs::open_in_memory().unwrap();
+        let open = db.new_entity().attach(Status::Open);
+        let pending = db.new_entity().attach(Status::Pending);
+        db.new_entity().attach(Status::Closed);
+
+        let found = |filter: OneOf<Status>| db.find(filter).map(|e| e.id()).collect::<Vec<_>>();
+
+        assert_eq!(
+            found(OneOf(vec![Status::Open, Status::Pending])),
+            vec![open.id(), pending.id()]
+        );
+        assert_eq!(found(OneOf(vec![Status::Pending])), vec![pending.id()]);
+        assert_eq!(found(OneOf(vec![])), vec![]);
+
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(storage = "null")]
+        struct Flag;
+
+        let flagged = db.new_entity().attach(Flag);
+        assert_eq!(
+            db.find(OneOf(vec![Flag]))
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+            vec![flagged.id()]
+        );
+    }
+
+    #[test]
+    fn data_is_null() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Note(Option<String>);
+
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(storage = "null")]
+        struct Flag;
+
+        let db = Ecs::open_in_memory().unwrap();
+        let empty = db.new_entity().attach(Note(None));
+        let set = db.new_entity().attach(Note(Some("Hello".into())));
+        let flagged = db.new_entity().attach((A, Flag));
+
+        let ids = |entities: Vec<Entity>| entities.iter().map(|e| e.id()).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(db.query::<Entity, DataIsNull<Note>>().collect()),
+            vec![empty.id()]
+        );
+        assert_eq!(
+            ids(db.query::<Entity, DataIsNotNull<Note>>().collect()),
+            vec![set.id()]
+        );
+        assert_eq!(
+            ids(db.query::<Entity, DataIsNull<Flag>>().collect()),
+            vec![flagged.id()]
+        );
+        assert!(db.query::<Entity, DataIsNotNull<Flag>>().next().is_none());
+
+        // Reads back from the NULL
+        assert!(flagged.try_component::<Flag>().unwrap().is_some());
+        assert_eq!(db.query::<Flag, ()>().count(), 1);
+    }
+
+    #[test]
+    fn null_storage_round_trip() {
+        #[derive(Debug, Default, PartialEq, Serialize, Deserialize, Component)]
+        #[component(storage = "null")]
+        struct Session {
+            #[serde(skip)]
+            hits: u32,
+            #[serde(skip)]
+            label: Option<String>,
+        }
+
+        #[derive(Debug, Default, PartialEq, Serialize, Deserialize, Component)]
+        #[component(storage = "null")]
+        enum State {
+            #[default]
+            Idle,
+            Busy,
+        }
+
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach((
+            Session {
+                hits: 3,
+                label: Some("cached".into()),
+            },
+            State::Busy,
+        ));
+
+        let is_null = |component: &str| -> bool {
+            db.raw_sql()
+                .query_row(
+                    "select data is null from components where entity = ?1 and component = ?2",
+                    rusqlite::params![e.id(), component],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(is_null(Session::NAME));
+        assert!(is_null(State::NAME));
+
+        // Nothing but the presence is stored, values come from `Default`
+        assert_eq!(e.component::<Session>(), Some(Session::default()));
+        assert_eq!(e.component::<State>(), Some(State::Idle));
+    }
+
+    #[test]
+    fn like() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Name(String);
+
+        let db = Ecs::open_in_memory().unwrap();
+        let alice = db.new_entity().attach(Name("Alice".into()));
+        let alicia = db.new_entity().attach(Name("Alicia".into()));
+        db.new_entity().attach(Name("Bob".into()));
+
+        let found = |pattern: &str| {
+            db.find(Like::<Name>::new(pattern))
+                .map(|e| e.id())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(found("Ali%"), vec![alice.id(), alicia.id()]);
+        assert_eq!(found("%cia"), vec![alicia.id()]);
+        assert_eq!(found("Alice"), vec![alice.id()]);
+        assert_eq!(found("Carol%"), vec![]);
+    }
+
+    #[test]
+    fn timestamp_range() {
+        use super::Timestamp;
+        use chrono::{TimeZone, Utc};
+
+        let at = |secs: i64, nanos: u32| Timestamp(Utc.timestamp_opt(secs, nanos).unwrap());
+
+        let db = Ecs::open_in_memory().unwrap();
+        let early = db.new_entity().attach(at(1_000, 0));
+        let middle = db.new_entity().attach(at(2_000, 500_000_000));
+        let late = db.new_entity().attach(at(2_001, 0));
+        db.new_entity().attach(Timestamp::default());
+
+        let found =
+            |range: std::ops::Range<Timestamp>| db.find(range).map(|e| e.id()).collect::<Vec<_>>();
+
+        assert_eq!(
+            found(at(1_500, 0)..at(3_000, 0)),
+            vec![middle.id(), late.id()]
+        );
+        // The end of a `Range` is excluded, the end of a `RangeInclusive` isn't
+        assert_eq!(found(at(2_000, 0)..at(2_001, 0)), vec![middle.id()]);
+        assert_eq!(
+            db.find(at(2_000, 0)..=at(2_001, 0))
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+            vec![middle.id(), late.id()]
+        );
+        assert_eq!(
+            found(at(0, 0)..at(2_000, 600_000_000)),
+            vec![early.id(), middle.id()]
+        );
+        assert_eq!(
+            middle.component::<Timestamp>(),
+            Some(at(2_000, 500_000_000))
+        );
+        assert!(Timestamp::now() > at(2_001, 0));
+    }
+
+    #[test]
+    fn created_at_order_and_filter() {
+        use crate::query::ir::OrderBy;
+
+        let db = Ecs::open_in_memory().unwrap();
+        let first = db.new_entity().attach(A);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let second = db.new_entity().attach(A);
+
+        let created = |e: Entity| e.component::<CreatedAt>().unwrap().0;
+        assert!(created(first) < created(second));
+
+        let ordered = |db: &Ecs| {
+            Query::<EntityId, A>::new(db)
+                .order_by_created_at()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ordered(&db), vec![first.id(), second.id()]);
+
+        let since = |t| {
+            db.find(CreatedAt::since(t))
+                .map(|e| e.id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(since(created(second)), vec![second.id()]);
+        assert_eq!(since(created(first)), vec![first.id(), second.id()]);
+
+        // Ordering follows CreatedAt, not entity ids
+        first.attach(CreatedAt(created(second) + chrono::Duration::seconds(1)));
+        assert_eq!(ordered(&db), vec![second.id(), first.id()]);
+        assert_eq!(
+            Query::<EntityId, A>::new(&db)
+                .order_by_created_at()
+                .reverse_iter()
+                .collect::<Vec<_>>(),
+            vec![first.id(), second.id()]
+        );
+
+        // Pages continue in CreatedAt order
+        let third = db.new_entity().attach(A);
+        third.attach(CreatedAt(created(second)));
+        let paged = |order| {
+            let mut seen = Vec::new();
+            let mut cursor = None;
+            loop {
+                let mut query = Query::<EntityId, A>::new(&db).order_by(order);
+                if let Some(cursor) = cursor {
+                    query = query.after(cursor);
+                }
+                let page = query.page(1);
+                seen.extend(page.items);
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break seen,
+                }
+            }
+        };
+        assert_eq!(
+            paged(OrderBy::CreatedAt),
+            vec![second.id(), third.id(), first.id()]
+        );
+        assert_eq!(
+            paged(OrderBy::CreatedAtDesc),
+            vec![first.id(), third.id(), second.id()]
+        );
+    }
+
+    #[test]
+    fn ascending_entity_order() {
+        let db = Ecs::open_in_memory().unwrap();
+        for id in [500, 200, 400, 100, 300] {
+            db.entity(id).attach(ComponentWithData(1));
+        }
+
+        assert_eq!(
+            db.query::<EntityId, ComponentWithData>()
+                .collect::<Vec<_>>(),
+            vec![100, 200, 300, 400, 500]
+        );
+        assert_eq!(
+            db.find(ComponentWithData(1))
+                .map(|e| e.id())
+                .collect::<Vec<_>>(),
+            vec![100, 200, 300, 400, 500]
+        );
+        assert_eq!(
+            Query::<EntityId, ComponentWithData>::new(&db)
+                .reverse_iter()
+                .collect::<Vec<_>>(),
+            vec![500, 400, 300, 200, 100]
+        );
+    }
+
+    #[test]
+    fn rowid_and_unordered_queries() {
+        use crate::query::ir::OrderBy;
+
+        let db = Ecs::open_in_memory().unwrap();
+        let first = db.new_entity().attach(A).id();
+        // Imported with a lower id than the first entity
+        let second = db.entity(first - 50).attach(A).id();
+        let third = db.new_entity().attach(A).id();
+
+        let ordered = |order| {
+            Query::<EntityId, A>::new(&db)
+                .order_by(order)
+                .iter()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ordered(OrderBy::Asc), vec![second, first, third]);
+        assert_eq!(ordered(OrderBy::Rowid), vec![first, second, third]);
+        assert_eq!(
+            Query::<EntityId, A>::new(&db)
+                .order_by(OrderBy::Rowid)
+                .reverse_iter()
+                .collect::<Vec<_>>(),
+            vec![third, second, first]
+        );
+
+        let mut unordered = ordered(OrderBy::None);
+        unordered.sort();
+        assert_eq!(unordered, vec![second, first, third]);
+
+        // `after` continues in rowid order, not by entity id
+        let after = |order, cursor| {
+            Query::<EntityId, A>::new(&db)
+                .order_by(order)
+                .after(cursor)
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(after(OrderBy::Rowid, first), vec![second, third]);
+        assert_eq!(after(OrderBy::Rowid, second), vec![third]);
+        assert_eq!(after(OrderBy::RowidDesc, third), vec![second, first]);
+        assert_eq!(after(OrderBy::RowidDesc, second), vec![first]);
+
+        let paged = |order| {
+            let mut seen = Vec::new();
+            let mut cursor = None;
+            loop {
+                let mut query = Query::<EntityId, A>::new(&db).order_by(order);
+                if let Some(cursor) = cursor {
+                    query = query.after(cursor);
+                }
+                let page = query.page(1);
+                seen.extend(page.items);
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break seen,
+                }
+            }
+        };
+        assert_eq!(paged(OrderBy::Rowid), vec![first, second, third]);
+        assert_eq!(paged(OrderBy::RowidDesc), vec![third, second, first]);
+        assert_eq!(paged(OrderBy::None), vec![second, first, third]);
+    }
+
+    #[test]
+    fn rename_component() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        #[component(name = "ecsdb::test::OldName")]
+        struct Old(u64);
+
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        #[component(name = "ecsdb::test::NewName")]
+        struct New(u64);
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(Old(1)).id();
+        let b = db.new_entity().attach(Old(2)).id();
+        db.attach_resource(Old(3));
+
+        assert_eq!(db.rename_component(Old::NAME, New::NAME).unwrap(), 3);
+        assert_eq!(db.entity(a).component::<New>(), Some(New(1)));
+        assert_eq!(db.entity(b).component::<New>(), Some(New(2)));
+        assert_eq!(db.resource::<New>(), Some(New(3)));
+        assert_eq!(db.query::<Old, ()>().count(), 0);
+        assert_eq!(db.query::<New, ()>().count(), 3);
+
+        db.new_entity().attach(Old(4));
+        assert!(matches!(
+            db.rename_component(Old::NAME, New::NAME),
+            Err(super::Error::ComponentExists(_))
+        ));
+        assert_eq!(db.query::<Old, ()>().count(), 1);
+    }
+
+    #[test]
+    fn rename_component_moves_aliases_and_mask_bits() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        #[component(name = "ecsdb::test::OldName")]
+        struct Old(u64);
+
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        #[component(name = "ecsdb::test::NewName")]
+        struct New(u64);
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.set_component_naming(crate::ComponentNaming::StripPrefix("ecsdb::test::".into()));
+        db.register_component::<Old>();
+        let e = db.new_entity().attach((A, Old(1))).id();
+        db.enable_component_mask::<(A, Old)>();
+
+        db.rename_component(Old::NAME, New::NAME).unwrap();
+        assert_eq!(db.component_names().unwrap().len(), 2);
+        assert!(
+            db.component_names()
+                .unwrap()
+                .contains(&New::NAME.to_owned())
+        );
+        assert_eq!(db.query::<Old, ()>().count(), 0);
+
+        // `New` took over the mask bit of `Old`
+        let (sql, _) = Query::<EntityId, With<(A, New)>>::new(&db).to_sql();
+        assert!(sql.contains("component_masks"), "{sql}");
+        assert_eq!(
+            db.query::<EntityId, With<(A, New)>>().collect::<Vec<_>>(),
+            [e]
+        );
+
+        // The alias of `Old` is gone, `Old` is stored under its full name again
+        let aliases: i64 = db
+            .raw_sql()
+            .query_row("select count(*) from component_aliases", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(aliases, 0);
+        db.entity(e).attach(Old(2));
+        assert!(
+            db.component_names()
+                .unwrap()
+                .contains(&Old::NAME.to_owned())
+        );
+    }
+
+    #[test]
+    fn remove_component_everywhere() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach((A, ComponentWithData(1)));
+        let b = db.new_entity().attach((A, B));
+        let c = db.new_entity().attach(A);
+        let d = db.new_entity().attach(B);
+
+        assert_eq!(db.remove_component_everywhere::<A>().unwrap(), 3);
+        assert_eq!(db.query::<EntityId, A>().count(), 0);
+        assert_eq!(db.remove_component_everywhere::<A>().unwrap(), 0);
+
+        assert_eq!(
+            a.component::<ComponentWithData>(),
+            Some(ComponentWithData(1))
+        );
+        assert!(b.has::<B>());
+        assert!(!c.has::<A>());
+        assert!(d.has::<B>());
+
+        assert_eq!(db.remove_component_everywhere_named(B::NAME).unwrap(), 2);
+        assert_eq!(db.query::<EntityId, B>().count(), 0);
+
+        let index_exists = |name: &str| {
+            db.raw_sql()
+                .prepare("select 1 from sqlite_schema where type = 'index' and name = ?1")
+                .unwrap()
+                .exists([name])
+                .unwrap()
+        };
+        db.raw_sql()
+            .execute_batch(&format!(
+                "create index a_data on components (data) where component = '{a}';
+                 create index b_data on components (data) where component = '{b}';
+                 create index b_naming_a on components (entity) where component = '{b}' and data = '{a}';",
+                a = A::NAME,
+                b = B::NAME
+            ))
+            .unwrap();
+        db.remove_component_everywhere::<A>().unwrap();
+        assert!(!index_exists("a_data"));
+        assert!(index_exists("b_data"));
+        assert!(index_exists("b_naming_a"));
+        assert!(index_exists("components_component_idx"));
+    }
+
+    #[test]
+    fn detach_many() {
+        let db = Ecs::open_in_memory().unwrap();
+        let entities = (0..500)
+            .map(|_| db.new_entity().attach((A, B)).id())
+            .collect::<Vec<_>>();
+
+        assert_eq!(db.detach_many::<A>(&entities[100..300]).unwrap(), 200);
+        assert_eq!(db.query::<EntityId, A>().count(), 300);
+        assert_eq!(db.query::<EntityId, B>().count(), 500);
+        assert!(!db.entity(entities[100]).has::<A>());
+        assert!(db.entity(entities[300]).has::<A>());
+
+        assert_eq!(db.detach_many::<A>(&entities[100..300]).unwrap(), 0);
+    }
+
+    #[test]
+    fn entity_or_create() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.entity_or_create(ComponentWithData(1));
+        let b = db.entity_or_create(ComponentWithData(1));
+        let c = db.entity_or_create(ComponentWithData(2));
+
+        assert_eq!(a.id(), b.id());
+        assert_ne!(a.id(), c.id());
+        assert_eq!(db.query::<Entity, ComponentWithData>().count(), 2);
+    }
+
+    #[test]
+    fn singleton() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.singleton(A);
+        assert_eq!(db.singleton(A).id(), a.id());
+        assert_eq!(db.query::<Entity, A>().count(), 1);
+
+        let b = db.singleton(ComponentWithData(1));
+        assert_eq!(db.singleton(ComponentWithData(2)).id(), b.id());
+
+        db.new_entity().attach(ComponentWithData(3));
+        assert!(matches!(
+            db.try_singleton(ComponentWithData(4)),
+            Err(super::Error::NotASingleton(_))
+        ));
+    }
+
+    #[test]
+    fn aggregate() {
+        use super::Agg;
+
+        let db = Ecs::open_in_memory().unwrap();
+        assert_eq!(db.aggregate::<ComponentWithData>(Agg::Sum).unwrap(), None);
+
+        // Resources aren't aggregated
+        db.attach_resource(ComponentWithData(100));
+        assert_eq!(db.aggregate::<ComponentWithData>(Agg::Sum).unwrap(), None);
+
+        db.new_entity().attach(ComponentWithData(1));
+        db.new_entity().attach(ComponentWithData(2));
+        db.new_entity().attach(ComponentWithData(6));
+        db.new_entity().attach(A);
+
+        assert_eq!(
+            db.aggregate::<ComponentWithData>(Agg::Sum).unwrap(),
+            Some(9.0)
+        );
+        assert_eq!(
+            db.aggregate::<ComponentWithData>(Agg::Avg).unwrap(),
+            Some(3.0)
+        );
+        assert_eq!(
+            db.aggregate::<ComponentWithData>(Agg::Min).unwrap(),
+            Some(1.0)
+        );
+        assert_eq!(
+            db.aggregate::<ComponentWithData>(Agg::Max).unwrap(),
+            Some(6.0)
+        );
+        assert_eq!(
+            db.aggregate::<ComponentWithData>(Agg::Count).unwrap(),
+            Some(3.0)
+        );
     }
 }
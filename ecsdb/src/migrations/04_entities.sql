@@ -0,0 +1,28 @@
+insert
+or ignore into entities (id)
+select distinct
+    entity
+from
+    components;
+
+-- New entity ids start at 100
+insert into
+    sqlite_sequence (name, seq)
+select
+    'entities',
+    99
+where
+    not exists (
+        select
+            1
+        from
+            sqlite_sequence
+        where
+            name = 'entities'
+    );
+
+update sqlite_sequence
+set
+    seq = max(seq, 99)
+where
+    name = 'entities';
@@ -0,0 +1,29 @@
+-- Entities predating the column take their `ecsdb::CreatedAt`, if any
+update entities
+set
+    created_at = coalesce(
+        (
+            select
+                json_extract (data, '$')
+            from
+                components
+            where
+                entity = entities.id
+                and component = 'ecsdb::CreatedAt'
+        ),
+        strftime ('%Y-%m-%dT%H:%M:%fZ')
+    )
+where
+    created_at is null;
+
+create trigger if not exists entities_created_at_trigger
+after insert on entities
+for each row
+when new.created_at is null
+begin
+    update entities
+    set
+        created_at = strftime ('%Y-%m-%dT%H:%M:%fZ')
+    where
+        id = new.id;
+end;
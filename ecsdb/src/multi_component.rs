@@ -0,0 +1,143 @@
+use ecsdb_derive::with_infallible;
+use rusqlite::params;
+use tracing::debug;
+
+use crate::{Component, Entity, Error};
+
+/// A component an entity can have many values of, e.g. tags. Attach values via
+/// [`Entity::attach_multi`] and read them via [`Entity::components_of`].
+///
+/// The values are stored in a separate table, with a presence row in
+/// `components`, so filters like [`With<C>`](crate::query::With) work as for
+/// other components. Detaching `C` removes all values.
+pub trait MultiComponent: Component {}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Appends `component` to the values of `C`
+    #[tracing::instrument(name = "attach_multi", level = "debug", skip_all)]
+    pub fn try_attach_multi<C: MultiComponent>(self, component: C) -> Result<Self, Error> {
+        let data = self
+            .0
+            .with_storage_context(self.id(), || C::to_rusqlite(&component))?;
+        let name = self.0.stored_component_name(C::NAME);
+
+        let tx = self.0.transaction()?;
+        let attached = tx.execute(
+            "insert into components (entity, component, data) values (?1, ?2, null) on conflict do nothing",
+            params![self.id(), name],
+        )?;
+        tx.execute(
+            "insert into multi_components (entity, component, data) values (?1, ?2, ?3)",
+            params![self.id(), name, data],
+        )?;
+        tx.commit()?;
+
+        debug!(entity = self.id(), component = C::NAME, "attached");
+
+        if attached > 0 {
+            self.0.run_attach_hooks(C::NAME, self.id());
+        }
+
+        Ok(self)
+    }
+
+    /// All values of `C`, in the order they were attached
+    pub fn try_components_of<C: MultiComponent>(self) -> Result<Vec<C>, Error> {
+        let mut stmt = self.0.conn.prepare_cached(
+            "select data from multi_components where entity = ?1 and component = ?2 order by rowid",
+        )?;
+
+        stmt.query_and_then(
+            params![self.id(), self.0.stored_component_name(C::NAME)],
+            |row| {
+                let data = rusqlite::types::ToSqlOutput::Borrowed(row.get_ref("data")?);
+                Ok(self
+                    .0
+                    .with_storage_context(self.id(), || C::from_rusqlite(&data))?)
+            },
+        )?
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::MultiComponent;
+    use crate::query::{Query, With};
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Component)]
+    struct Tag(String);
+
+    impl MultiComponent for Tag {}
+
+    #[test]
+    fn attach_multi() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db
+            .new_entity()
+            .attach(A)
+            .attach_multi(Tag("one".into()))
+            .attach_multi(Tag("two".into()))
+            .attach_multi(Tag("three".into()));
+        let b = db.new_entity().attach(A).attach_multi(Tag("other".into()));
+        db.new_entity().attach(A);
+
+        assert_eq!(
+            a.components_of::<Tag>(),
+            vec![Tag("one".into()), Tag("two".into()), Tag("three".into())]
+        );
+        assert_eq!(b.components_of::<Tag>(), vec![Tag("other".into())]);
+
+        assert_eq!(
+            db.query::<EntityId, With<Tag>>().collect::<Vec<_>>(),
+            vec![a.id(), b.id()]
+        );
+
+        // The presence row isn't read as a value
+        assert_eq!(a.component::<Tag>(), None);
+        assert!(matches!(
+            Query::<Tag>::new(&db)
+                .strict()
+                .try_iter()
+                .map(|tags| tags.count()),
+            Ok(0)
+        ));
+
+        a.detach::<Tag>();
+        assert_eq!(a.components_of::<Tag>(), vec![]);
+
+        b.destroy();
+        assert_eq!(b.components_of::<Tag>(), vec![]);
+    }
+
+    #[test]
+    fn rename_multi_component() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Component)]
+        #[component(name = "ecsdb::test::Label")]
+        struct Label(String);
+
+        impl MultiComponent for Label {}
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        let e = db
+            .new_entity()
+            .attach_multi(Tag("one".into()))
+            .attach_multi(Tag("two".into()))
+            .id();
+
+        assert_eq!(db.rename_component(Tag::NAME, Label::NAME).unwrap(), 1);
+        let e = db.entity(e);
+        assert_eq!(e.components_of::<Tag>(), vec![]);
+        assert_eq!(
+            e.components_of::<Label>(),
+            vec![Label("one".into()), Label("two".into())]
+        );
+    }
+}
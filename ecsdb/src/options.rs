@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::{Ecs, Error};
+
+/// Pragmas applied when opening a database via [`crate::Ecs::open_with`] or
+/// [`crate::Ecs::from_rusqlite_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcsOptions {
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+    pub foreign_keys: bool,
+    /// How long SQLite waits for a lock before failing with `SQLITE_BUSY`.
+    /// `None` keeps the connection's setting.
+    pub busy_timeout: Option<Duration>,
+    /// Retries [`crate::Entity::attach`] and [`crate::Entity::destroy`] on
+    /// `SQLITE_BUSY`. See [`crate::Ecs::with_retry`] for other operations.
+    pub retry: Option<RetryPolicy>,
+    /// Records execution count and duration of every query, see
+    /// [`crate::Ecs::query_stats`]
+    pub query_stats: bool,
+}
+
+impl Default for EcsOptions {
+    fn default() -> Self {
+        Self {
+            journal_mode: JournalMode::Wal,
+            synchronous: Synchronous::Full,
+            foreign_keys: false,
+            busy_timeout: None,
+            retry: None,
+            query_stats: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+impl JournalMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "delete",
+            JournalMode::Truncate => "truncate",
+            JournalMode::Persist => "persist",
+            JournalMode::Memory => "memory",
+            JournalMode::Wal => "wal",
+            JournalMode::Off => "off",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl Synchronous {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "off",
+            Synchronous::Normal => "normal",
+            Synchronous::Full => "full",
+            Synchronous::Extra => "extra",
+        }
+    }
+}
+
+impl EcsOptions {
+    pub(crate) fn apply(&self, conn: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+        conn.pragma_update(None, "journal_mode", self.journal_mode.as_str())?;
+        conn.pragma_update(None, "synchronous", self.synchronous.as_str())?;
+        conn.pragma_update(None, "foreign_keys", self.foreign_keys)?;
+        if let Some(timeout) = self.busy_timeout {
+            conn.busy_timeout(timeout)?;
+        }
+        Ok(())
+    }
+}
+
+/// Exponential backoff for operations failing with `SQLITE_BUSY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the initial attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each following one
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Runs `f` until it doesn't fail with `SQLITE_BUSY`. Each attempt runs
+    /// in its own transaction, so a failed attempt doesn't leave partial
+    /// writes behind.
+    pub(crate) fn run<T>(
+        &self,
+        ecs: &Ecs,
+        mut f: impl FnMut() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut attempt = || {
+            let tx = ecs.transaction()?;
+            let result = f()?;
+            tx.commit()?;
+            Ok(result)
+        };
+
+        let mut backoff = self.initial_backoff.min(self.max_backoff);
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Err(e) if is_busy(&e) && retries < self.max_retries => {
+                    debug!(retries, ?backoff, "database busy, retrying");
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2).min(self.max_backoff);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_busy(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::Database(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::DatabaseBusy,
+                ..
+            },
+            _
+        ))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ecs;
+
+    #[test]
+    fn synchronous_off() {
+        let options = EcsOptions {
+            journal_mode: JournalMode::Memory,
+            synchronous: Synchronous::Off,
+            ..Default::default()
+        };
+
+        let ecs = Ecs::from_rusqlite_with(rusqlite::Connection::open_in_memory().unwrap(), options)
+            .unwrap();
+
+        let mut synchronous = None;
+        ecs.raw_sql()
+            .pragma_query(None, "synchronous", |row| {
+                synchronous = Some(row.get::<_, i64>(0)?);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(synchronous, Some(0));
+
+        let mut journal_mode = None;
+        ecs.raw_sql()
+            .pragma_query(None, "journal_mode", |row| {
+                journal_mode = Some(row.get::<_, String>(0)?);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(journal_mode.as_deref(), Some("memory"));
+    }
+
+    #[test]
+    fn retry_on_busy() {
+        use crate::{self as ecsdb, Component};
+        use std::time::Duration;
+
+        #[derive(Debug, serde::Serialize, serde::Deserialize, Component)]
+        struct A;
+
+        #[derive(Debug, serde::Serialize, serde::Deserialize, Component)]
+        struct B;
+
+        let path = crate::test_util::TempDb::new("retry");
+
+        let options = EcsOptions {
+            busy_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let db = Ecs::open_with(&path, options).unwrap();
+        let retrying = Ecs::open_with(
+            &path,
+            EcsOptions {
+                retry: Some(RetryPolicy {
+                    max_retries: 10,
+                    initial_backoff: Duration::from_millis(10),
+                    ..Default::default()
+                }),
+                ..options
+            },
+        )
+        .unwrap();
+
+        let e = retrying.new_entity().attach(A);
+
+        // Hold the write lock on another connection
+        let blocker = rusqlite::Connection::open(&path).unwrap();
+        blocker.execute_batch("begin immediate").unwrap();
+
+        assert!(is_busy(&db.entity(e.id()).try_attach(B).unwrap_err()));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            blocker.execute_batch("commit").unwrap();
+        });
+
+        e.attach(B);
+        assert!(e.has::<(A, B)>());
+        release.join().unwrap();
+
+        db.close().unwrap();
+        retrying.close().unwrap();
+    }
+
+    #[test]
+    fn retry_attempts_roll_back() {
+        use crate::{self as ecsdb, Component, EntityId};
+        use std::time::Duration;
+
+        #[derive(Debug, serde::Serialize, serde::Deserialize, Component)]
+        struct A;
+
+        let busy = || {
+            Error::Database(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                None,
+            ))
+        };
+
+        let db = Ecs::open_in_memory().unwrap();
+        // Doubling the initial backoff would overflow without the cap
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::MAX,
+            max_backoff: Duration::from_millis(1),
+        };
+
+        let mut attempts = 0;
+        policy
+            .run(&db, || {
+                attempts += 1;
+                db.new_entity().try_attach(A)?;
+                if attempts < 3 { Err(busy()) } else { Ok(()) }
+            })
+            .unwrap();
+        assert_eq!(attempts, 3);
+        assert_eq!(db.query::<EntityId, A>().count(), 1);
+
+        let mut attempts = 0;
+        let result = policy.run(&db, || -> Result<(), Error> {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(is_busy(&result.unwrap_err()));
+        assert_eq!(attempts, 4);
+    }
+
+    #[test]
+    fn foreign_keys() {
+        let options = EcsOptions {
+            foreign_keys: true,
+            ..Default::default()
+        };
+
+        let ecs = Ecs::from_rusqlite_with(rusqlite::Connection::open_in_memory().unwrap(), options)
+            .unwrap();
+
+        let foreign_keys: bool = ecs
+            .raw_sql()
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+    }
+}
@@ -0,0 +1,97 @@
+use ecsdb_derive::with_infallible;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{self as ecsdb, Component, Ecs, Entity, Error};
+
+/// Marks a template entity registered via [`Ecs::register_prefab`].
+///
+/// Templates are regular entities and show up in queries matching their
+/// components. Use `Without<Prefab>` to exclude them.
+#[derive(Serialize, Deserialize, Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Prefab(pub String);
+
+#[with_infallible]
+impl Ecs {
+    /// Registers a template entity under `name`, replacing any previous
+    /// template of the same name.
+    pub fn try_register_prefab<'a>(
+        &'a self,
+        name: &str,
+        f: impl FnOnce(Entity<'a>) -> Entity<'a>,
+    ) -> Result<Entity<'a>, Error> {
+        if let Some(previous) = self.try_prefab(name)? {
+            debug!(name, entity = previous.id(), "replacing prefab");
+            previous.try_destroy()?;
+        }
+
+        let template = self.new_entity().try_attach(Prefab(name.to_owned()))?;
+        Ok(f(template))
+    }
+
+    pub fn try_prefab<'a>(&'a self, name: &str) -> Result<Option<Entity<'a>>, Error> {
+        Ok(self.try_find(Prefab(name.to_owned()))?.next())
+    }
+
+    /// Clones the template registered under `name`, minus the [`Prefab`]
+    /// marker.
+    pub fn try_spawn_prefab<'a>(&'a self, name: &str) -> Result<Option<Entity<'a>>, Error> {
+        let Some(template) = self.try_prefab(name)? else {
+            return Ok(None);
+        };
+
+        let entity = template.try_clone_entity()?.try_detach::<Prefab>()?;
+        Ok(Some(entity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::query::Without;
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Goblin;
+
+    #[test]
+    fn spawn_prefab() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        ecs.register_prefab("goblin", |e| e.attach(Goblin).attach(Health(10)));
+
+        let a = ecs.spawn_prefab("goblin").unwrap();
+        let b = ecs.spawn_prefab("goblin").unwrap();
+
+        assert_ne!(a.id(), b.id());
+        for e in [a, b] {
+            assert!(e.has::<Goblin>());
+            assert_eq!(e.component::<Health>(), Some(Health(10)));
+            assert!(!e.has::<super::Prefab>());
+        }
+
+        assert_eq!(
+            ecs.query::<EntityId, (Goblin, Without<super::Prefab>)>()
+                .collect::<Vec<_>>(),
+            vec![a.id(), b.id()]
+        );
+
+        assert!(ecs.spawn_prefab("orc").is_none());
+    }
+
+    #[test]
+    fn register_prefab_replaces() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        ecs.register_prefab("goblin", |e| e.attach(Health(10)));
+        ecs.register_prefab("goblin", |e| e.attach(Health(20)));
+
+        assert_eq!(
+            ecs.spawn_prefab("goblin").unwrap().component::<Health>(),
+            Some(Health(20))
+        );
+        assert_eq!(ecs.query::<EntityId, super::Prefab>().count(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use ecsdb_derive::with_infallible;
+
+use super::{QueryFilterValue, ir};
+use crate::{Ecs, Entity, EntityId, Error};
+
+/// Composes a filter at runtime, e.g. from user input. Conditions are
+/// combined via `and`.
+///
+/// ```
+/// # use ecsdb::query::FilterBuilder;
+/// let filter = FilterBuilder::new()
+///     .with("app::Task")
+///     .or(vec![
+///         FilterBuilder::new().with("app::Urgent"),
+///         FilterBuilder::new().without("app::Done"),
+///     ]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterBuilder(Vec<ir::FilterExpression>);
+
+impl FilterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, component: &str) -> Self {
+        self.0.push(ir::FilterExpression::with_component(component));
+        self
+    }
+
+    pub fn without(mut self, component: &str) -> Self {
+        self.0
+            .push(ir::FilterExpression::without_component(component));
+        self
+    }
+
+    pub fn entity(mut self, entity: EntityId) -> Self {
+        self.0.push(ir::FilterExpression::entity(entity));
+        self
+    }
+
+    /// Matches if `component` is stored with exactly `value`
+    pub fn with_data(mut self, component: &str, value: rusqlite::types::Value) -> Self {
+        self.0
+            .push(ir::FilterExpression::with_component_data(component, value));
+        self
+    }
+
+    /// Matches if the JSON-stored `component` equals `value`
+    pub fn with_json(self, component: &str, value: &serde_json::Value) -> Self {
+        let value = match value {
+            serde_json::Value::Null => rusqlite::types::Value::Null,
+            other => rusqlite::types::Value::Text(other.to_string()),
+        };
+        self.with_data(component, value)
+    }
+
+    /// Matches if `component`'s data lies in `start..=end`. Pass
+    /// `Value::Null` for an open bound.
+    pub fn data_range(
+        mut self,
+        component: &str,
+        start: rusqlite::types::Value,
+        end: rusqlite::types::Value,
+    ) -> Self {
+        self.0.push(ir::FilterExpression::WithComponentDataRange {
+            component: component.to_owned(),
+            start,
+            end,
+            end_inclusive: true,
+        });
+        self
+    }
+
+    /// Matches if any of `alternatives` matches. Never matches if
+    /// `alternatives` is empty.
+    pub fn or(mut self, alternatives: Vec<FilterBuilder>) -> Self {
+        self.0.push(ir::FilterExpression::or(
+            alternatives.into_iter().map(FilterBuilder::build),
+        ));
+        self
+    }
+
+    pub fn build(self) -> ir::FilterExpression {
+        ir::FilterExpression::And(self.0)
+    }
+}
+
+impl QueryFilterValue for FilterBuilder {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        self.clone().build()
+    }
+}
+
+#[with_infallible]
+impl Ecs {
+    pub fn try_query_dyn<'a>(
+        &'a self,
+        filter: FilterBuilder,
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        self.try_find(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::FilterBuilder;
+    use crate::query::{Or, With, Without};
+    use crate::{self as ecsdb, Component, Ecs, Entity, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct C(u32);
+
+    #[test]
+    fn query_dyn() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        ecs.new_entity().attach(A);
+        ecs.new_entity().attach((A, B));
+        ecs.new_entity().attach((A, C(1)));
+        ecs.new_entity().attach((B, C(2)));
+        ecs.new_entity().attach(C(3));
+
+        let ids = |filter: FilterBuilder| ecs.query_dyn(filter).map(Entity::id).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(FilterBuilder::new().with(A::NAME).without(B::NAME)),
+            ecs.query::<EntityId, (With<A>, Without<B>)>()
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            ids(FilterBuilder::new().or(vec![
+                FilterBuilder::new().with(B::NAME),
+                FilterBuilder::new().with(A::NAME).with(C::NAME),
+            ])),
+            ecs.query::<EntityId, Or<(With<B>, With<(A, C)>)>>()
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            ids(FilterBuilder::new().with(C::NAME).or(vec![
+                FilterBuilder::new().with_json(C::NAME, &serde_json::json!(1)),
+                FilterBuilder::new().with_json(C::NAME, &serde_json::json!(3)),
+            ])),
+            ecs.find(C(1))
+                .chain(ecs.find(C(3)))
+                .map(|e| e.id())
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(ids(FilterBuilder::new()).len(), 5);
+        assert!(ids(FilterBuilder::new().or(vec![])).is_empty());
+        assert!(ids(FilterBuilder::new().with(A::NAME).or(vec![])).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use ecsdb_derive::with_infallible;
+
+use super::{Query, QueryFilter, ir::SqlParameters};
+use crate::{Ecs, EntityId, Error};
+
+/// Memoizes the entity ids matching filter `F`. The query is only re-run
+/// when the database changed, either through another connection (detected via
+/// [`Ecs::data_version`]) or through this one. Inside a transaction, the
+/// query always runs.
+pub struct QueryCache<F = ()> {
+    version: Option<(i64, i64)>,
+    entities: Vec<EntityId>,
+    filter: PhantomData<F>,
+}
+
+impl<F> Default for QueryCache<F> {
+    fn default() -> Self {
+        Self {
+            version: None,
+            entities: Vec::new(),
+            filter: PhantomData,
+        }
+    }
+}
+
+impl<F> QueryCache<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forces the next [`QueryCache::get`] to re-run the query
+    pub fn invalidate(&mut self) {
+        self.version = None;
+    }
+}
+
+#[with_infallible]
+impl<F: QueryFilter> QueryCache<F> {
+    pub fn try_get(&mut self, ecs: &Ecs) -> Result<&[EntityId], Error> {
+        // A rollback doesn't lower `total_changes`, so results read inside a
+        // transaction aren't kept
+        let version = if ecs.raw_sql().is_autocommit() {
+            Some((ecs.data_version()?, ecs.total_changes()?))
+        } else {
+            None
+        };
+
+        if version.is_none() || self.version != version {
+            self.entities = Query::<EntityId, F>::new(ecs).try_iter()?.collect();
+            self.version = version;
+        }
+
+        Ok(&self.entities)
+    }
+}
+
+/// Entity ids of the most recently run queries, keyed by SQL and parameter
+/// values. Enabled via [`Ecs::with_query_cache`]. All entries are dropped
+/// once the database changed, see [`QueryCache`].
+pub(crate) struct ResultCache {
+    capacity: usize,
+    state: Mutex<ResultCacheState>,
+}
+
+#[derive(Default)]
+struct ResultCacheState {
+    version: Option<(i64, i64)>,
+    tick: u64,
+    /// Key to last use and entity ids
+    entries: HashMap<String, (u64, Vec<EntityId>)>,
+}
+
+impl ResultCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn key(sql: &str, params: &SqlParameters) -> String {
+        let mut key = sql.to_owned();
+        for (name, value) in params {
+            match value.to_sql() {
+                Ok(value) => key.push_str(&format!("\n{name} = {value:?}")),
+                Err(e) => key.push_str(&format!("\n{name} = <{e}>")),
+            }
+        }
+        key
+    }
+
+    pub(crate) fn get(&self, version: (i64, i64), key: &str) -> Option<Vec<EntityId>> {
+        let mut state = self.state.lock().unwrap();
+        if state.version != Some(version) {
+            state.entries.clear();
+            state.version = Some(version);
+            return None;
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        let (last_used, entities) = state.entries.get_mut(key)?;
+        *last_used = tick;
+        Some(entities.clone())
+    }
+
+    pub(crate) fn insert(&self, version: (i64, i64), key: String, entities: Vec<EntityId>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.version != Some(version) {
+            state.entries.clear();
+            state.version = Some(version);
+        }
+
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            let least_recent = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recent) = least_recent {
+                state.entries.remove(&least_recent);
+            }
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(key, (tick, entities));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use std::ops::ControlFlow;
+
+    use super::QueryCache;
+    use crate::query::{Query, With};
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[test]
+    fn query_cache() {
+        let path = crate::test_util::TempDb::new("query-cache");
+        let db = Ecs::open(&path).unwrap();
+        let other = Ecs::open(&path).unwrap();
+
+        let a = db.new_entity().attach(A);
+
+        let mut cache = QueryCache::<With<A>>::new();
+        assert_eq!(cache.get(&db), &[a.id()]);
+
+        let version = db.data_version().unwrap();
+        assert!(!db.changed_since(version).unwrap());
+
+        // Written through another connection
+        let b = other.new_entity().attach(A).id();
+        assert!(db.changed_since(version).unwrap());
+        assert_eq!(cache.get(&db), &[a.id(), b]);
+
+        // Written through this connection
+        a.detach::<A>();
+        assert_eq!(cache.get(&db), &[b]);
+
+        db.close().unwrap();
+        other.close().unwrap();
+    }
+
+    #[test]
+    fn result_cache() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct B;
+
+        let options = crate::EcsOptions {
+            query_stats: true,
+            ..Default::default()
+        };
+        let db = Ecs::from_rusqlite_with(rusqlite::Connection::open_in_memory().unwrap(), options)
+            .unwrap()
+            .with_query_cache(1);
+        let a = db.new_entity().attach((A, B)).id();
+
+        let runs = |db: &Ecs| db.query_stats().iter().map(|s| s.count).sum::<u64>();
+
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a]);
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a]);
+        assert_eq!(runs(&db), 1);
+
+        // Evicts `A`
+        assert_eq!(db.query::<EntityId, B>().collect::<Vec<_>>(), [a]);
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a]);
+        assert_eq!(runs(&db), 3);
+
+        // Changes invalidate the cache
+        let b = db.new_entity().attach(A).id();
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a, b]);
+        assert_eq!(runs(&db), 4);
+
+        // Lazily stepped queries are served from the cache as well
+        let mut stepped = Vec::new();
+        Query::<EntityId, A>::new(&db).for_each_while(|id| {
+            stepped.push(id);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(stepped, [a, b]);
+        assert_eq!(runs(&db), 4);
+    }
+
+    #[test]
+    fn result_cache_after_rollback() {
+        let db = Ecs::open_in_memory().unwrap().with_query_cache(4);
+        let a = db.new_entity().attach(A).id();
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a]);
+
+        let mut cache = QueryCache::<With<A>>::new();
+        assert_eq!(cache.get(&db), &[a]);
+
+        db.raw_sql().execute_batch("begin").unwrap();
+        let b = db.new_entity().attach(A).id();
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a, b]);
+        assert_eq!(cache.get(&db), &[a, b]);
+        db.raw_sql().execute_batch("rollback").unwrap();
+
+        assert_eq!(db.query::<EntityId, A>().collect::<Vec<_>>(), [a]);
+        assert_eq!(cache.get(&db), &[a]);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     marker::PhantomData,
 };
 
@@ -7,16 +7,47 @@ use rusqlite::ToSql;
 
 use crate::EntityId;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderBy {
     Asc,
     Desc,
+    /// Oldest first by [`CreatedAt`](crate::CreatedAt), ties broken by entity
+    CreatedAt,
+    /// Newest first by [`CreatedAt`](crate::CreatedAt)
+    CreatedAtDesc,
+    /// In the order the entities' oldest remaining components were attached
+    /// (by `components.rowid`), independent of entity ids
+    Rowid,
+    /// Most recently attached first, see [`OrderBy::Rowid`]
+    RowidDesc,
+    /// Unspecified order, skipping the sort.
+    /// [`Query::after`](super::Query::after) compares entity ids, pages are
+    /// fetched in ascending id order.
+    None,
+}
+
+impl OrderBy {
+    pub fn reverse(self) -> Self {
+        match self {
+            OrderBy::Asc => OrderBy::Desc,
+            OrderBy::Desc => OrderBy::Asc,
+            OrderBy::CreatedAt => OrderBy::CreatedAtDesc,
+            OrderBy::CreatedAtDesc => OrderBy::CreatedAt,
+            OrderBy::Rowid => OrderBy::RowidDesc,
+            OrderBy::RowidDesc => OrderBy::Rowid,
+            OrderBy::None => OrderBy::None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Query {
     pub filter: FilterExpression,
     pub order_by: OrderBy,
+    /// Keyset cursor: only return entities after this one in `order_by`
+    /// direction
+    pub after: Option<EntityId>,
+    pub limit: Option<usize>,
 }
 
 pub(crate) type Sql = String;
@@ -24,13 +55,62 @@ pub(crate) type SqlParameters = Vec<(String, Box<dyn ToSql>)>;
 
 impl Query {
     pub(crate) fn into_sql(self) -> (Sql, SqlParameters) {
-        let mut select = self.filter.simplify().sql_query();
-        let order_by = match self.order_by {
-            OrderBy::Asc => "order by entity asc",
-            OrderBy::Desc => "order by entity desc",
+        // Orderings by a key other than the entity id continue after the
+        // cursor entity's key, with the id as tie breaker
+        let (filter, keyset_after) = match (self.after, &self.order_by) {
+            (None, _) => (self.filter, None),
+            (Some(after), OrderBy::Asc | OrderBy::None) => (
+                FilterExpression::and([self.filter, FilterExpression::EntityIdGreaterThan(after)]),
+                None,
+            ),
+            (Some(after), OrderBy::Desc) => (
+                FilterExpression::and([self.filter, FilterExpression::EntityIdLessThan(after)]),
+                None,
+            ),
+            (Some(after), OrderBy::CreatedAt | OrderBy::Rowid) => (self.filter, Some((after, ">"))),
+            (Some(after), OrderBy::CreatedAtDesc | OrderBy::RowidDesc) => {
+                (self.filter, Some((after, "<")))
+            }
         };
 
-        select.sql = format!("{} {}", select.sql, order_by);
+        // The sort key of `entity`. Entities without `CreatedAt` sort first,
+        // as '' is less than any timestamp, same as NULL would, but doesn't
+        // break the keyset comparison.
+        let sort_key = |entity: &str| match self.order_by {
+            OrderBy::CreatedAt | OrderBy::CreatedAtDesc => format!(
+                "coalesce((select data from components c3 where c3.entity = {entity} and c3.component = 'ecsdb::CreatedAt'), '')"
+            ),
+            _ => format!("(select min(rowid) from components c3 where c3.entity = {entity})"),
+        };
+
+        let mut select = filter.simplify().merge_with_components().sql_query();
+        let key = sort_key("matches.entity");
+        let keyset = match keyset_after {
+            Some((after, op)) => {
+                select.placeholders.push((":after".into(), Box::new(after)));
+                format!(
+                    "where ({key}, entity) {op} ({}, :after)",
+                    sort_key(":after")
+                )
+            }
+            None => String::new(),
+        };
+        select.sql = match self.order_by {
+            OrderBy::Asc => format!("{} order by entity asc", select.sql),
+            OrderBy::Desc => format!("{} order by entity desc", select.sql),
+            OrderBy::CreatedAt | OrderBy::Rowid => format!(
+                "select entity from ({}) matches {keyset} order by {key} asc, entity asc",
+                select.sql
+            ),
+            OrderBy::CreatedAtDesc | OrderBy::RowidDesc => format!(
+                "select entity from ({}) matches {keyset} order by {key} desc, entity desc",
+                select.sql
+            ),
+            OrderBy::None => select.sql,
+        };
+        if let Some(limit) = self.limit {
+            select.sql = format!("{} limit {limit}", select.sql);
+        }
 
         (select.sql, select.placeholders)
     }
@@ -39,20 +119,55 @@ impl Query {
 #[derive(Debug, Clone, PartialEq)]
 pub enum FilterExpression {
     None,
+    /// Matches nothing
+    False,
 
     And(Vec<FilterExpression>),
     Or(Vec<FilterExpression>),
 
     EntityId(EntityId),
+    EntityIdGreaterThan(EntityId),
+    EntityIdLessThan(EntityId),
     WithComponent(String),
     WithoutComponent(String),
+    /// Entity has all of the listed components. Produced by
+    /// [`FilterExpression::merge_with_components`].
+    WithComponents(Vec<String>),
+    /// Entity has all components whose bits are set. Produced by
+    /// [`FilterExpression::use_component_mask`].
+    WithComponentMask(i64),
 
     WithComponentData(String, rusqlite::types::Value),
+    /// Component data is SQL `NULL` or JSON `null`
+    WithComponentDataNull(String),
+    /// Component data is neither SQL `NULL` nor JSON `null`
+    WithComponentDataNotNull(String),
+    /// Component data is any of the listed values. Matches nothing if empty.
+    WithComponentDataIn(String, Vec<rusqlite::types::Value>),
+    /// Component data matches the SQL `like` pattern
+    WithComponentDataLike {
+        component: String,
+        pattern: String,
+    },
+    /// Component data lies between `start` and `end`, either of which may be
+    /// `NULL` for an open bound. `start` is always inclusive.
     WithComponentDataRange {
         component: String,
         start: rusqlite::types::Value,
         end: rusqlite::types::Value,
+        end_inclusive: bool,
+    },
+    /// Component data matches the raw SQL expression `sql_expr`, which
+    /// references `params` as `?1`, `?2`, ... See [`super::RawDataFilter`].
+    WithComponentDataSql {
+        component: String,
+        sql_expr: String,
+        params: Vec<rusqlite::types::Value>,
     },
+    /// A filter value that couldn't be converted, e.g. of an encrypted
+    /// component. Queries containing it fail with
+    /// [`Error::InvalidFilter`](crate::Error::InvalidFilter).
+    Invalid(String),
 }
 
 impl FilterExpression {
@@ -89,47 +204,247 @@ impl FilterExpression {
     pub fn simplify(self) -> Self {
         use FilterExpression::*;
 
+        fn push_deduplicated(exprs: &mut Vec<FilterExpression>, expr: FilterExpression) {
+            if !exprs.contains(&expr) {
+                exprs.push(expr)
+            }
+        }
+
         match self {
             Or(exprs) => {
-                let exprs = exprs
-                    .into_iter()
-                    .filter(|e| *e != None)
-                    // Flatten nested `Or`
-                    .flat_map(|e| match e {
-                        Or(exprs) => exprs,
-                        other => vec![other],
-                    })
-                    .map(Self::simplify);
+                let mut simplified = Vec::new();
+                for expr in exprs.into_iter().map(Self::simplify) {
+                    match expr {
+                        // `None` matches everything, so does the whole `Or`
+                        None => return None,
+                        False => continue,
+                        // Flatten nested `Or`
+                        Or(exprs) => exprs
+                            .into_iter()
+                            .for_each(|e| push_deduplicated(&mut simplified, e)),
+                        other => push_deduplicated(&mut simplified, other),
+                    }
+                }
 
-                let mut deduplicated = Vec::new();
-                for expr in exprs {
-                    if !deduplicated.contains(&expr) {
-                        deduplicated.push(expr)
+                match simplified.len() {
+                    // An empty disjunction matches nothing
+                    0 => False,
+                    1 => simplified.pop().unwrap(),
+                    _ => Or(simplified),
+                }
+            }
+            And(exprs) => {
+                let mut simplified = Vec::new();
+                for expr in exprs.into_iter().map(Self::simplify) {
+                    match expr {
+                        None => continue,
+                        False => return False,
+                        // Flatten nested `And`
+                        And(exprs) => exprs
+                            .into_iter()
+                            .for_each(|e| push_deduplicated(&mut simplified, e)),
+                        other => push_deduplicated(&mut simplified, other),
                     }
                 }
 
-                Or(deduplicated)
+                match simplified.len() {
+                    0 => None,
+                    1 => simplified.pop().unwrap(),
+                    _ => And(simplified),
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl FilterExpression {
+    /// Fails with the reason of the first `Invalid` expression
+    pub(crate) fn validate(&self) -> Result<(), crate::Error> {
+        match self {
+            FilterExpression::Invalid(reason) => Err(crate::Error::InvalidFilter(reason.clone())),
+            FilterExpression::And(exprs) | FilterExpression::Or(exprs) => {
+                exprs.iter().try_for_each(Self::validate)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Components every matching entity must carry, taken from the
+    /// `WithComponent`s and `WithComponents` at the top level and inside
+    /// `And`s. Branches of an `Or` don't require anything.
+    pub fn required_components(&self) -> Vec<&str> {
+        use FilterExpression::*;
+
+        match self {
+            WithComponent(c) => vec![c.as_str()],
+            WithComponents(components) => components.iter().map(String::as_str).collect(),
+            And(exprs) => exprs.iter().flat_map(Self::required_components).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl FilterExpression {
+    /// Replaces every component name found in `aliases` by the alias it's
+    /// stored under, see [`crate::ComponentNaming`]
+    pub fn resolve_aliases(self, aliases: &HashMap<String, String>) -> Self {
+        use FilterExpression::*;
+
+        let resolve = |c: String| aliases.get(&c).cloned().unwrap_or(c);
+
+        match self {
+            And(exprs) => And(exprs
+                .into_iter()
+                .map(|e| e.resolve_aliases(aliases))
+                .collect()),
+            Or(exprs) => Or(exprs
+                .into_iter()
+                .map(|e| e.resolve_aliases(aliases))
+                .collect()),
+            WithComponent(c) => WithComponent(resolve(c)),
+            WithoutComponent(c) => WithoutComponent(resolve(c)),
+            WithComponents(components) => {
+                WithComponents(components.into_iter().map(resolve).collect())
+            }
+            WithComponentData(c, value) => WithComponentData(resolve(c), value),
+            WithComponentDataNull(c) => WithComponentDataNull(resolve(c)),
+            WithComponentDataNotNull(c) => WithComponentDataNotNull(resolve(c)),
+            WithComponentDataIn(c, values) => WithComponentDataIn(resolve(c), values),
+            WithComponentDataLike { component, pattern } => WithComponentDataLike {
+                component: resolve(component),
+                pattern,
+            },
+            WithComponentDataRange {
+                component,
+                start,
+                end,
+                end_inclusive,
+            } => WithComponentDataRange {
+                component: resolve(component),
+                start,
+                end,
+                end_inclusive,
+            },
+            WithComponentDataSql {
+                component,
+                sql_expr,
+                params,
+            } => WithComponentDataSql {
+                component: resolve(component),
+                sql_expr,
+                params,
+            },
+            other @ (None
+            | False
+            | FilterExpression::EntityId(_)
+            | EntityIdGreaterThan(_)
+            | EntityIdLessThan(_)
+            | WithComponentMask(_)
+            | Invalid(_)) => other,
+        }
+    }
+}
+
+/// Minimum number of adjacent `WithComponent`s in an `And` to merge into a
+/// single `WithComponents`
+const MERGE_WITH_COMPONENTS_THRESHOLD: usize = 3;
+
+impl FilterExpression {
+    /// Optimization pass rewriting runs of adjacent `WithComponent` inside an
+    /// `And` into a single `WithComponents`, which needs one subquery instead
+    /// of one correlated subquery per component.
+    pub fn merge_with_components(self) -> Self {
+        use FilterExpression::*;
+
+        fn flush(exprs: &mut Vec<FilterExpression>, run: &mut Vec<String>) {
+            if run.len() >= MERGE_WITH_COMPONENTS_THRESHOLD {
+                exprs.push(WithComponents(std::mem::take(run)));
+            } else {
+                exprs.extend(run.drain(..).map(WithComponent));
             }
+        }
+
+        match self {
             And(exprs) => {
-                let exprs = exprs
-                    .into_iter()
-                    .filter(|e| *e != None)
-                    // Flatten nested `And`
-                    .flat_map(|e| match e {
-                        And(exprs) => exprs,
-                        other => vec![other],
-                    })
-                    .map(Self::simplify);
+                let mut merged = Vec::with_capacity(exprs.len());
+                let mut run = Vec::new();
+                for expr in exprs {
+                    match expr.merge_with_components() {
+                        WithComponent(c) if run.contains(&c) => {}
+                        WithComponent(c) => run.push(c),
+                        other => {
+                            flush(&mut merged, &mut run);
+                            merged.push(other);
+                        }
+                    }
+                }
+                flush(&mut merged, &mut run);
+
+                And(merged)
+            }
+            Or(exprs) => Or(exprs.into_iter().map(Self::merge_with_components).collect()),
+            other => other,
+        }
+    }
+}
+
+/// Minimum number of components for which a `WithComponentMask` replaces the
+/// `WithComponent`s
+const COMPONENT_MASK_THRESHOLD: usize = 2;
+
+impl FilterExpression {
+    /// Optimization pass folding the `WithComponent`s and `WithComponents`
+    /// inside an `And` into a single `WithComponentMask`, for all components
+    /// with a bit in `bits`. See [`crate::Ecs::enable_component_mask`].
+    pub fn use_component_mask(self, bits: &HashMap<String, u32>) -> Self {
+        use FilterExpression::*;
+
+        let mask_of = |components: &[String]| {
+            components
+                .iter()
+                .try_fold(0i64, |mask, c| Some(mask | 1 << bits.get(c)?))
+        };
 
-                let mut deduplicated = Vec::new();
+        match self {
+            And(exprs) => {
+                let mut mask = 0;
+                let mut masked = Vec::new();
+                let mut rest = Vec::with_capacity(exprs.len());
                 for expr in exprs {
-                    if !deduplicated.contains(&expr) {
-                        deduplicated.push(expr)
+                    match expr {
+                        WithComponent(c) if bits.contains_key(&c) => {
+                            mask |= 1 << bits[&c];
+                            masked.push(WithComponent(c));
+                        }
+                        WithComponents(components) => match mask_of(&components) {
+                            Some(m) => {
+                                mask |= m;
+                                masked.push(WithComponents(components));
+                            }
+                            Option::None => rest.push(WithComponents(components)),
+                        },
+                        other => rest.push(other.use_component_mask(bits)),
                     }
                 }
 
-                And(deduplicated)
+                if mask.count_ones() as usize >= COMPONENT_MASK_THRESHOLD {
+                    rest.insert(0, WithComponentMask(mask));
+                } else {
+                    masked.append(&mut rest);
+                    rest = masked;
+                }
+
+                And(rest)
             }
+            Or(exprs) => Or(exprs
+                .into_iter()
+                .map(|e| e.use_component_mask(bits))
+                .collect()),
+            WithComponents(components) => match mask_of(&components) {
+                Some(mask) => WithComponentMask(mask),
+                Option::None => WithComponents(components),
+            },
             other => other,
         }
     }
@@ -153,12 +468,38 @@ impl FilterExpression {
     fn where_clause(&self) -> SqlFragment<Where> {
         match self {
             FilterExpression::None => SqlFragment::new("true", []),
+            FilterExpression::False => SqlFragment::new("false", []),
 
             FilterExpression::WithComponent(c) => SqlFragment::new(
                 "(select true from components c2 where c2.entity = components.entity and c2.component = ?1)",
                 [("?1", Box::new(c.to_owned()) as _)],
             ),
 
+            FilterExpression::WithComponents(components) => {
+                let placeholders = (1..=components.len())
+                    .map(|n| format!(":component_{n}_"))
+                    .collect::<Vec<_>>();
+
+                let sql = format!(
+                    "entity in (select entity from components where component in ({}) group by entity having count(distinct component) = {})",
+                    placeholders.join(", "),
+                    components.len()
+                );
+
+                SqlFragment::new(
+                    &sql,
+                    placeholders
+                        .iter()
+                        .zip(components)
+                        .map(|(p, c)| (p.as_str(), Box::new(c.to_owned()) as _)),
+                )
+            }
+
+            FilterExpression::WithComponentMask(mask) => SqlFragment::new(
+                "entity in (select entity from component_masks where mask & :mask = :mask)",
+                [(":mask", Box::new(*mask) as _)],
+            ),
+
             FilterExpression::WithoutComponent(c) => SqlFragment::new(
                 "(select true from components c2 where c2.entity = components.entity and c2.component = ?1) is null",
                 [("?1", Box::new(c.to_owned()) as _)],
@@ -168,6 +509,14 @@ impl FilterExpression {
                 SqlFragment::new("entity = ?1", [("?1", Box::new(*id) as _)])
             }
 
+            FilterExpression::EntityIdGreaterThan(id) => {
+                SqlFragment::new("entity > ?1", [("?1", Box::new(*id) as _)])
+            }
+
+            FilterExpression::EntityIdLessThan(id) => {
+                SqlFragment::new("entity < ?1", [("?1", Box::new(*id) as _)])
+            }
+
             FilterExpression::WithComponentData(component, data) => {
                 if matches!(data, rusqlite::types::Value::Null) {
                     SqlFragment::new(
@@ -185,26 +534,96 @@ impl FilterExpression {
                 }
             }
 
+            FilterExpression::WithComponentDataNull(component) => SqlFragment::new(
+                "(select true from components c2 where c2.entity = components.entity and c2.component = ?1 and (c2.data is null or c2.data = 'null'))",
+                [("?1", Box::new(component.to_owned()) as _)],
+            ),
+
+            FilterExpression::WithComponentDataNotNull(component) => SqlFragment::new(
+                "(select true from components c2 where c2.entity = components.entity and c2.component = ?1 and c2.data is not null and c2.data != 'null')",
+                [("?1", Box::new(component.to_owned()) as _)],
+            ),
+
+            FilterExpression::WithComponentDataIn(_, values) if values.is_empty() => {
+                SqlFragment::new("false", [])
+            }
+
+            FilterExpression::WithComponentDataIn(component, values) => {
+                use rusqlite::types::Value;
+
+                // `null in (...)` is never true, so NULL members get their
+                // own branch, like in `WithComponentData`
+                let has_null = values.contains(&Value::Null);
+                let values = values
+                    .iter()
+                    .filter(|v| **v != Value::Null)
+                    .collect::<Vec<_>>();
+
+                let placeholders = (1..=values.len())
+                    .map(|n| format!(":value_{n}_"))
+                    .collect::<Vec<_>>();
+
+                let mut conditions = Vec::new();
+                if !placeholders.is_empty() {
+                    conditions.push(format!("data in ({})", placeholders.join(", ")));
+                }
+                if has_null {
+                    conditions.push("data is null".to_owned());
+                }
+
+                let sql = format!(
+                    "entity in (select entity from components where component = :component and ({}))",
+                    conditions.join(" or ")
+                );
+
+                SqlFragment::new(
+                    &sql,
+                    std::iter::once((":component", Box::new(component.to_owned()) as _)).chain(
+                        placeholders
+                            .iter()
+                            .zip(values)
+                            .map(|(p, v)| (p.as_str(), Box::new(v.clone()) as _)),
+                    ),
+                )
+            }
+
+            FilterExpression::WithComponentDataLike { component, pattern } => SqlFragment::new(
+                "(select true from components c2 where c2.entity = components.entity and c2.component = :component and velodb_extract_data(c2.data) like :pattern)",
+                [
+                    (":component", Box::new(component.to_owned()) as _),
+                    (":pattern", Box::new(pattern.to_owned()) as _),
+                ],
+            ),
+
             FilterExpression::WithComponentDataRange {
                 component,
                 start,
                 end,
+                end_inclusive,
             } => {
                 use rusqlite::types::Value;
 
                 let (range_filter_condition, mut params) = match (start, end) {
                     (Value::Null, Value::Null) => ("c2.data is null", vec![]),
                     (Value::Null, end) => (
-                        "velodb_extract_data(c2.data) <= velodb_extract_data(?2)",
+                        if *end_inclusive {
+                            "velodb_extract_sortable_data(c2.data) <= velodb_extract_sortable_data(?2)"
+                        } else {
+                            "velodb_extract_sortable_data(c2.data) < velodb_extract_sortable_data(?2)"
+                        },
                         vec![("?2", Box::new(end.to_owned()) as _)],
                     ),
                     (start, Value::Null) => (
-                        "velodb_extract_data(c2.data) >= velodb_extract_data(?2)",
+                        "velodb_extract_sortable_data(c2.data) >= velodb_extract_sortable_data(?2)",
                         vec![("?2", Box::new(start.to_owned()) as _)],
                     ),
 
                     (start, end) => (
-                        "velodb_extract_data(c2.data) between velodb_extract_data(?2) and velodb_extract_data(?3)",
+                        if *end_inclusive {
+                            "velodb_extract_sortable_data(c2.data) between velodb_extract_sortable_data(?2) and velodb_extract_sortable_data(?3)"
+                        } else {
+                            "velodb_extract_sortable_data(c2.data) >= velodb_extract_sortable_data(?2) and velodb_extract_sortable_data(c2.data) < velodb_extract_sortable_data(?3)"
+                        },
                         vec![
                             ("?2", Box::new(start.to_owned()) as _),
                             ("?3", Box::new(end.to_owned()) as _),
@@ -213,11 +632,42 @@ impl FilterExpression {
                 };
 
                 let sql = format!(
-                    "(select true from components c2 where c2.entity = components.entity and c2.component = ?component and {range_filter_condition})"
+                    "(select true from components c2 where c2.entity = components.entity and c2.component = :component and {range_filter_condition})"
                 );
-                params.push(("?component", Box::new(component.to_owned()) as _));
+                params.push((":component", Box::new(component.to_owned()) as _));
                 SqlFragment::new(&sql, params)
             }
+
+            FilterExpression::WithComponentDataSql {
+                component,
+                sql_expr,
+                params,
+            } => {
+                let placeholders = (1..=params.len())
+                    .map(|n| format!(":param_{n}_"))
+                    .collect::<Vec<_>>();
+
+                let sql_expr = replace_placeholders(sql_expr, |token| {
+                    let n = token.strip_prefix('?')?.parse::<usize>().ok()?;
+                    placeholders.get(n.checked_sub(1)?).cloned()
+                });
+
+                let sql = format!(
+                    "entity in (select entity from components where component = :component and ({sql_expr}))"
+                );
+
+                SqlFragment::new(
+                    &sql,
+                    std::iter::once((":component", Box::new(component.to_owned()) as _)).chain(
+                        placeholders
+                            .iter()
+                            .zip(params)
+                            .map(|(p, v)| (p.as_str(), Box::new(v.to_owned()) as _)),
+                    ),
+                )
+            }
+            // Rejected by `validate` before running
+            FilterExpression::Invalid(_) => SqlFragment::new("false", []),
             FilterExpression::And(exprs) => Self::combine_exprs("and", exprs),
             FilterExpression::Or(exprs) => Self::combine_exprs("or", exprs),
         }
@@ -227,7 +677,11 @@ impl FilterExpression {
         let mut exprs = exprs.iter().map(|e| e.where_clause());
 
         let Some(fragment) = exprs.next() else {
-            return FilterExpression::None.where_clause();
+            // The identity of `or` is false, the one of `and` true
+            return match via {
+                "or" => FilterExpression::False.where_clause(),
+                _ => FilterExpression::None.where_clause(),
+            };
         };
 
         let mut last_placeholder = 0;
@@ -295,13 +749,7 @@ impl<T> SqlFragment<T> {
             .map(|(p, _)| (p.to_owned(), fun(p.to_owned())))
             .collect();
 
-        for (idx, (a, _)) in mappings.iter().enumerate() {
-            self.sql = self.sql.replace(a, &format!(":{idx}:"));
-        }
-
-        for (idx, (_, b)) in mappings.iter().enumerate() {
-            self.sql = self.sql.replace(&format!(":{idx}:"), b);
-        }
+        self.sql = replace_placeholders(&self.sql, |token| mappings.get(token).cloned());
 
         for (placeholder, _value) in self.placeholders.iter_mut() {
             *placeholder = mappings[placeholder].clone();
@@ -311,6 +759,42 @@ impl<T> SqlFragment<T> {
     }
 }
 
+/// Replaces the placeholders (`?1`, `:name`) in `sql` for which `replace`
+/// returns `Some`. String literals and quoted identifiers are copied as is, so
+/// e.g. `'12:15'` isn't mistaken for the placeholder `:15`.
+fn replace_placeholders(sql: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            // A doubled quote escapes it, which reads as two adjacent literals
+            '\'' | '"' | '`' => {
+                let end = chars
+                    .find(|(_, q)| *q == c)
+                    .map_or(sql.len(), |(i, q)| i + q.len_utf8());
+                out.push_str(&sql[start..end]);
+            }
+            '?' | ':' => {
+                let mut end = start + 1;
+                while let Some((i, _)) =
+                    chars.next_if(|(_, n)| n.is_ascii_alphanumeric() || *n == '_')
+                {
+                    end = i + 1;
+                }
+
+                let token = &sql[start..end];
+                match replace(token) {
+                    Some(replacement) => out.push_str(&replacement),
+                    None => out.push_str(token),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
 impl<T: std::fmt::Debug> std::fmt::Debug for SqlFragment<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct(&format!("SqlFragment<{}>", std::any::type_name::<T>()))
@@ -340,7 +824,8 @@ impl<T: std::fmt::Debug> std::fmt::Debug for SqlFragment<T> {
 mod test {
     use insta::assert_debug_snapshot;
 
-    use crate::query::ir::FilterExpression;
+    use crate::query::ir::{FilterExpression, Select, SqlFragment};
+    use crate::{Ecs, EntityId};
 
     fn cases() -> Vec<FilterExpression> {
         vec![
@@ -446,4 +931,234 @@ mod test {
             });
         }
     }
+
+    fn nested_case() -> FilterExpression {
+        let mut expr = FilterExpression::with_component("ecsdb::Foo");
+        for _ in 0..5 {
+            expr = FilterExpression::and([
+                FilterExpression::none(),
+                FilterExpression::or([expr]),
+                FilterExpression::without_component("ecsdb::Bar"),
+            ]);
+        }
+        expr
+    }
+
+    #[test]
+    fn simplify_nested() {
+        let expr = nested_case();
+
+        let simplified = expr.clone().simplify().sql_query();
+        insta::assert_debug_snapshot!(simplified);
+
+        let unsimplified = expr.sql_query();
+        assert!(unsimplified.sql.len() > 2 * simplified.sql.len());
+        assert!(unsimplified.placeholders.len() > simplified.placeholders.len());
+    }
+
+    fn run(ecs: &Ecs, fragment: SqlFragment<Select>) -> Vec<EntityId> {
+        let mut stmt = ecs.conn.prepare(&fragment.sql).unwrap();
+        let params: Vec<(&str, &dyn rusqlite::ToSql)> = fragment
+            .placeholders
+            .iter()
+            .map(|(p, v)| (p.as_str(), v.as_ref()))
+            .collect();
+        let mut ids = stmt
+            .query_map(&params[..], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        ids.sort();
+        ids
+    }
+
+    fn test_db() -> Ecs {
+        let ecs = Ecs::open_in_memory().unwrap();
+        ecs.conn
+            .execute_batch(
+                r#"
+                insert into components (entity, component, data) values
+                    (100, 'ecsdb::Foo', null),
+                    (101, 'ecsdb::Foo', null),
+                    (101, 'ecsdb::Bar', null),
+                    (102, 'ecsdb::Bar', null),
+                    (103, 'ecsdb::Test', null),
+                    (104, 'ecsdb::Foo', null),
+                    (104, 'ecsdb::Bar', null),
+                    (104, 'ecsdb::Test', null),
+                    (105, 'ecsdb::Foo', null),
+                    (105, 'ecsdb::Test', null);
+                "#,
+            )
+            .unwrap();
+        ecs
+    }
+
+    #[test]
+    fn simplify_preserves_results() {
+        let ecs = test_db();
+
+        let mut cases = cases();
+        cases.push(nested_case());
+        cases.push(FilterExpression::or([
+            FilterExpression::none(),
+            FilterExpression::with_component("ecsdb::Foo"),
+        ]));
+        cases.push(FilterExpression::or([
+            FilterExpression::False,
+            FilterExpression::with_component("ecsdb::Foo"),
+        ]));
+        cases.push(FilterExpression::and([
+            FilterExpression::False,
+            FilterExpression::with_component("ecsdb::Foo"),
+        ]));
+        cases.push(FilterExpression::or([]));
+        cases.push(FilterExpression::and([
+            FilterExpression::or([]),
+            FilterExpression::with_component("ecsdb::Foo"),
+        ]));
+
+        for case in cases {
+            assert_eq!(
+                run(&ecs, case.clone().sql_query()),
+                run(&ecs, case.clone().simplify().sql_query()),
+                "{case:?}"
+            );
+        }
+    }
+
+    fn with_components(components: &[&str]) -> FilterExpression {
+        FilterExpression::and(
+            components
+                .iter()
+                .map(|c| FilterExpression::with_component(c)),
+        )
+    }
+
+    #[test]
+    fn merge_with_components() {
+        let merged = FilterExpression::and([
+            FilterExpression::entity(42),
+            with_components(&["A", "B", "C"]),
+            FilterExpression::without_component("D"),
+            with_components(&["E", "F"]),
+        ])
+        .simplify()
+        .merge_with_components();
+
+        assert_eq!(
+            merged,
+            FilterExpression::and([
+                FilterExpression::entity(42),
+                FilterExpression::WithComponents(vec!["A".into(), "B".into(), "C".into()]),
+                FilterExpression::without_component("D"),
+                FilterExpression::with_component("E"),
+                FilterExpression::with_component("F"),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_with_components_plan() {
+        fn plan(ecs: &Ecs, fragment: SqlFragment<Select>) -> Vec<String> {
+            let mut stmt = ecs
+                .conn
+                .prepare(&format!("explain query plan {}", fragment.sql))
+                .unwrap();
+            let params: Vec<(&str, &dyn rusqlite::ToSql)> = fragment
+                .placeholders
+                .iter()
+                .map(|(p, v)| (p.as_str(), v.as_ref()))
+                .collect();
+            stmt.query_map(&params[..], |row| row.get("detail"))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        }
+
+        let ecs = test_db();
+        let expr = with_components(&["A", "B", "C", "D", "E"]);
+
+        let before = plan(&ecs, expr.clone().sql_query());
+        let after = plan(&ecs, expr.merge_with_components().sql_query());
+
+        let correlated = |plan: &[String]| {
+            plan.iter()
+                .filter(|step| step.contains("CORRELATED"))
+                .count()
+        };
+        assert_eq!(correlated(&before), 5, "{before:#?}");
+        assert_eq!(correlated(&after), 0, "{after:#?}");
+    }
+
+    #[test]
+    fn use_component_mask() {
+        let bits = [("A", 0), ("B", 1), ("C", 2)]
+            .into_iter()
+            .map(|(c, bit)| (c.to_string(), bit))
+            .collect();
+
+        let masked = FilterExpression::and([
+            FilterExpression::entity(42),
+            FilterExpression::WithComponents(vec!["A".into(), "B".into()]),
+            FilterExpression::without_component("C"),
+            FilterExpression::with_component("C"),
+            FilterExpression::with_component("D"),
+        ])
+        .use_component_mask(&bits);
+
+        assert_eq!(
+            masked,
+            FilterExpression::and([
+                FilterExpression::WithComponentMask(0b111),
+                FilterExpression::entity(42),
+                FilterExpression::without_component("C"),
+                FilterExpression::with_component("D"),
+            ])
+        );
+
+        // A single masked component isn't worth it
+        let single = FilterExpression::and([
+            FilterExpression::with_component("A"),
+            FilterExpression::with_component("D"),
+        ]);
+        assert_eq!(single.clone().use_component_mask(&bits), single);
+    }
+
+    #[test]
+    fn merge_with_components_preserves_results() {
+        let ecs = test_db();
+
+        let cases = [
+            with_components(&["ecsdb::Foo", "ecsdb::Bar", "ecsdb::Test"]),
+            with_components(&["ecsdb::Foo", "ecsdb::Test", "ecsdb::Missing"]),
+            FilterExpression::and([
+                with_components(&["ecsdb::Foo", "ecsdb::Test", "ecsdb::Foo"]),
+                FilterExpression::without_component("ecsdb::Bar"),
+            ]),
+            FilterExpression::or([
+                with_components(&["ecsdb::Foo", "ecsdb::Bar", "ecsdb::Test"]),
+                FilterExpression::entity(102),
+            ]),
+        ];
+
+        for case in cases {
+            let optimized = case.clone().simplify().merge_with_components();
+            assert_eq!(
+                run(&ecs, case.clone().sql_query()),
+                run(&ecs, optimized.sql_query()),
+                "{case:?}"
+            );
+        }
+
+        assert_eq!(
+            run(
+                &ecs,
+                with_components(&["ecsdb::Foo", "ecsdb::Bar", "ecsdb::Test"])
+                    .merge_with_components()
+                    .sql_query()
+            ),
+            vec![104]
+        );
+    }
 }
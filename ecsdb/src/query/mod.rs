@@ -1,16 +1,36 @@
 use ecsdb_derive::with_infallible;
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::{Entity, EntityId, component::Bundle};
 
 use super::Component;
 use std::marker::PhantomData;
+use std::ops::ControlFlow;
+
+pub mod builder;
+pub use builder::FilterBuilder;
+
+pub mod cache;
+pub use cache::QueryCache;
 
 pub mod ir;
 
+pub mod stats;
+pub use stats::QueryStat;
+
+pub mod watcher;
+pub use watcher::{QueryChanges, QueryWatcher};
+
 pub trait QueryData {
     type Output<'a>: Sized;
     fn from_entity<'a>(e: Entity<'a>) -> Option<Self::Output<'a>>;
+
+    /// Like [`Self::from_entity`], but returns deserialization errors instead
+    /// of panicking
+    fn try_from_entity<'a>(e: Entity<'a>) -> Result<Option<Self::Output<'a>>, crate::Error> {
+        Ok(Self::from_entity(e))
+    }
+
     fn filter_expression() -> ir::FilterExpression;
 }
 
@@ -30,6 +50,13 @@ pub struct Without<C>(PhantomData<C>);
 /// Matches if any of the filters in `F` match
 pub struct Or<F>(F);
 
+/// Matches if Entity has `C` with null data, either SQL `NULL` (as written by
+/// [`NullStorage`](crate::component::NullStorage)) or JSON `null`
+pub struct DataIsNull<C>(PhantomData<C>);
+
+/// Matches if Entity has `C` with data other than SQL `NULL` or JSON `null`
+pub struct DataIsNotNull<C>(PhantomData<C>);
+
 pub trait QueryFilterValue: Sized {
     fn filter_expression(&self) -> ir::FilterExpression;
 }
@@ -42,6 +69,12 @@ where
     pub(crate) data: PhantomData<D>,
     pub(crate) filter: PhantomData<F>,
     pub(crate) filter_value: V,
+    pub(crate) include_deleted: bool,
+    pub(crate) after: Option<EntityId>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) order_by: ir::OrderBy,
+    pub(crate) strict: bool,
+    pub(crate) prefetch_count: bool,
 }
 
 impl<'a, C, F> Query<'a, C, F, ()> {
@@ -51,6 +84,12 @@ impl<'a, C, F> Query<'a, C, F, ()> {
             data: PhantomData,
             filter: PhantomData,
             filter_value: (),
+            include_deleted: false,
+            after: None,
+            limit: None,
+            order_by: ir::OrderBy::Asc,
+            strict: false,
+            prefetch_count: false,
         }
     }
 }
@@ -62,8 +101,64 @@ impl<'a, C, F, V> Query<'a, C, F, V> {
             data: PhantomData,
             filter: PhantomData,
             filter_value,
+            include_deleted: false,
+            after: None,
+            limit: None,
+            order_by: ir::OrderBy::Asc,
+            strict: false,
+            prefetch_count: false,
         }
     }
+
+    /// Include entities marked as [`Deleted`](crate::soft_delete::Deleted),
+    /// which are excluded by default.
+    pub fn include_deleted(mut self) -> Self {
+        self.include_deleted = true;
+        self
+    }
+
+    /// Only return entities after `entity` in iteration order, e.g. with a
+    /// greater id, or a smaller one for the `reverse` iterators. For
+    /// [`OrderBy::Rowid`](ir::OrderBy::Rowid) `entity` must still have a
+    /// component to compare against. The
+    /// [`CreatedAt`](crate::CreatedAt) orderings compare by
+    /// `(CreatedAt, entity id)`.
+    pub fn after(mut self, entity: EntityId) -> Self {
+        self.after = Some(entity);
+        self
+    }
+
+    /// Return at most `limit` entities
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Iterate in order of [`CreatedAt`](crate::CreatedAt) instead of by
+    /// entity id
+    pub fn order_by_created_at(self) -> Self {
+        self.order_by(ir::OrderBy::CreatedAt)
+    }
+
+    /// Iterate in `order` instead of by entity id
+    pub fn order_by(mut self, order: ir::OrderBy) -> Self {
+        self.order_by = order;
+        self
+    }
+
+    /// Fail the query when a component can't be deserialized. By default,
+    /// such entities are skipped with a warning.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Count the matches with an extra query first, to allocate the result
+    /// buffer at its final size. Pays off for very large result sets.
+    pub fn prefetch_count(mut self) -> Self {
+        self.prefetch_count = true;
+        self
+    }
 }
 
 #[with_infallible]
@@ -73,35 +168,117 @@ where
     F: QueryFilter,
     V: QueryFilterValue,
 {
+    /// Matching entities, by ascending entity id unless set otherwise via
+    /// [`Self::order_by`]
     pub fn try_iter(
         &self,
     ) -> Result<impl Iterator<Item = D::Output<'a>> + 'a + use<'a, D, F, V>, crate::Error> {
-        Ok(self.try_entities()?.filter_map(|e| D::from_entity(e)))
+        self.read_data::<D, _>(self.try_entities()?)
+    }
+
+    /// Like [`Self::try_iter`], but also yields the entity
+    pub fn try_iter_entities_with(
+        &self,
+    ) -> Result<
+        impl Iterator<Item = (Entity<'a>, D::Output<'a>)> + 'a + use<'a, D, F, V>,
+        crate::Error,
+    > {
+        self.read_data::<(Entity<'a>, D), _>(self.try_entities()?)
     }
 
+    /// Like [`Self::try_iter`] in reverse order, i.e. by descending entity id
+    /// by default
     pub fn try_reverse_iter(
         &self,
     ) -> Result<impl Iterator<Item = D::Output<'a>> + 'a + use<'a, D, F, V>, crate::Error> {
-        Ok(self
-            .try_reverse_entities()?
-            .filter_map(|e| D::from_entity(e)))
+        self.read_data::<D, _>(self.try_reverse_entities()?)
     }
 
     pub fn try_entities(
         &self,
     ) -> Result<impl Iterator<Item = Entity<'a>> + 'a + use<'a, D, F, V>, crate::Error> {
-        let mut query = self.as_sql_query();
-
-        query.order_by = ir::OrderBy::Asc;
-        self.ecs.fetch::<Entity>(query)
+        let query = self.as_sql_query();
+        self.ecs.fetch::<Entity>(query, self.capacity_hint()?)
     }
 
     pub fn try_reverse_entities(
         &self,
     ) -> Result<impl Iterator<Item = Entity<'a>> + 'a + use<'a, D, F, V>, crate::Error> {
         let mut query = self.as_sql_query();
-        query.order_by = ir::OrderBy::Desc;
-        self.ecs.fetch::<Entity>(query)
+        query.order_by = query.order_by.reverse();
+        self.ecs.fetch::<Entity>(query, self.capacity_hint()?)
+    }
+
+    /// Number of matching entities, ignoring the order
+    pub fn try_count(&self) -> Result<usize, crate::Error> {
+        let mut query = self.as_sql_query();
+        query.order_by = ir::OrderBy::None;
+        self.ecs.count_entities(query)
+    }
+
+    /// Fetches the next `size` entities, starting after the cursor set via
+    /// [`Self::after`]. Pass [`Page::next_cursor`] to `after` to get the
+    /// following page.
+    pub fn try_page(&self, size: usize) -> Result<Page<D::Output<'a>>, crate::Error> {
+        let mut query = self.as_sql_query();
+        query.limit = Some(size);
+        // Without a stable order, the cursor could skip entities
+        if query.order_by == ir::OrderBy::None {
+            query.order_by = ir::OrderBy::Asc;
+        }
+
+        let entities = self.ecs.fetch::<Entity>(query, size)?.collect::<Vec<_>>();
+        let next_cursor = match entities.last() {
+            Some(last) if entities.len() == size => Some(last.id()),
+            _ => None,
+        };
+
+        Ok(Page {
+            items: self.read_data::<D, _>(entities.into_iter())?.collect(),
+            next_cursor,
+        })
+    }
+
+    /// Calls `f` with each result until it returns [`ControlFlow::Break`].
+    /// Unlike [`Self::try_iter`], rows are stepped one at a time, so nothing
+    /// past the break is read.
+    pub fn try_for_each_while(
+        &self,
+        f: impl FnMut(D::Output<'a>) -> ControlFlow<()>,
+    ) -> Result<(), crate::Error> {
+        let mut f = f;
+        for eid in self.ecs.fetch_entity_ids_lazy(self.as_sql_query())? {
+            let entity = Entity::with_id(self.ecs, eid?);
+            let data = match D::try_from_entity(entity) {
+                Ok(Some(data)) => data,
+                Ok(None) => continue,
+                Err(e) if self.strict => return Err(e),
+                Err(error) => {
+                    warn!(entity = entity.id(), %error, "skipping entity");
+                    continue;
+                }
+            };
+
+            if f(data).is_break() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects all results into a `Vec` that doesn't borrow the [`crate::Ecs`],
+    /// allowing to modify the database while holding on to the results.
+    ///
+    /// Only available for `D` whose output doesn't borrow the `Ecs`, i.e.
+    /// owned components and [`EntityId`]. Use [`EntityId`] instead of
+    /// [`Entity`].
+    pub fn try_collect_owned<O>(&self) -> Result<Vec<O>, crate::Error>
+    where
+        for<'x> D: QueryData<Output<'x> = O>,
+        O: 'static,
+    {
+        Ok(self.try_iter()?.collect())
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
@@ -110,17 +287,148 @@ where
             D::filter_expression(),
             F::filter_expression(),
             self.filter_value.filter_expression(),
+            // Spares databases without soft-deleted entities the subquery
+            if self.include_deleted || !self.ecs.any_soft_deleted() {
+                ir::FilterExpression::none()
+            } else {
+                ir::FilterExpression::without_component(crate::soft_delete::Deleted::NAME)
+            },
         ]);
 
+        let aliases = &self.ecs.registry.aliases;
+        let filter = if aliases.is_empty() {
+            filter
+        } else {
+            filter.resolve_aliases(aliases)
+        };
+
+        let bits = &self.ecs.registry.component_bits;
+        let filter = if bits.is_empty() {
+            filter
+        } else {
+            filter.simplify().use_component_mask(bits)
+        };
+
         trace!(?filter);
 
         ir::Query {
             filter,
-            order_by: ir::OrderBy::Asc,
+            order_by: self.order_by,
+            after: self.after,
+            limit: self.limit,
+        }
+    }
+}
+
+impl<'a, D, F, V> Query<'a, D, F, V>
+where
+    D: QueryData + 'a,
+    F: QueryFilter,
+    V: QueryFilterValue,
+{
+    fn capacity_hint(&self) -> Result<usize, crate::Error> {
+        if self.prefetch_count {
+            self.try_count()
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn read_data<Q: QueryData + 'a, I: Iterator<Item = Entity<'a>>>(
+        &self,
+        entities: I,
+    ) -> Result<ReadData<'a, Q, I>, crate::Error> {
+        if self.strict {
+            let items = entities
+                .map(|e| Q::try_from_entity(e))
+                .filter_map(Result::transpose)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(ReadData::Strict(items.into_iter()))
+        } else {
+            Ok(ReadData::Lenient(entities, PhantomData))
+        }
+    }
+
+    /// The SQL generated for this query, plus a debug rendering of its
+    /// placeholder values
+    pub fn to_sql(&self) -> (String, Vec<String>) {
+        let (sql, placeholders) = self.as_sql_query().into_sql();
+        let placeholders = placeholders
+            .iter()
+            .map(|(name, value)| match value.to_sql() {
+                Ok(value) => format!("{name} = {value:?}"),
+                Err(e) => format!("{name} = <{e}>"),
+            })
+            .collect();
+
+        (sql, placeholders)
+    }
+
+    /// Runs `explain query plan` for this query and returns the plan, one
+    /// step per line, indented by depth.
+    pub fn explain(&self) -> Result<String, crate::Error> {
+        let (sql, placeholders) = self.as_sql_query().into_sql();
+        let mut stmt = self
+            .ecs
+            .conn
+            .prepare(&format!("explain query plan {sql}"))?;
+        let params: Box<[(&str, &dyn rusqlite::ToSql)]> = placeholders
+            .iter()
+            .map(|(p, v)| (p.as_str(), v.as_ref()))
+            .collect();
+
+        let mut depths = std::collections::HashMap::new();
+        let mut plan = String::new();
+        let mut rows = stmt.query(&params[..])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get("id")?;
+            let parent: i64 = row.get("parent")?;
+            let detail: String = row.get("detail")?;
+
+            let depth = depths.get(&parent).map_or(0, |d| d + 1);
+            depths.insert(id, depth);
+
+            plan.push_str(&"  ".repeat(depth));
+            plan.push_str(&detail);
+            plan.push('\n');
         }
+
+        Ok(plan)
     }
 }
 
+/// Iterator over the [`QueryData`] of a [`Query`]. Strict queries are read
+/// eagerly so that errors surface before iteration starts.
+enum ReadData<'a, D: QueryData + 'a, I> {
+    Lenient(I, PhantomData<D>),
+    Strict(std::vec::IntoIter<D::Output<'a>>),
+}
+
+impl<'a, D: QueryData + 'a, I: Iterator<Item = Entity<'a>>> Iterator for ReadData<'a, D, I> {
+    type Item = D::Output<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ReadData::Strict(items) => items.next(),
+            ReadData::Lenient(entities, _) => entities.find_map(|e| match D::try_from_entity(e) {
+                Ok(data) => data,
+                Err(error) => {
+                    warn!(entity = e.id(), %error, "skipping entity");
+                    None
+                }
+            }),
+        }
+    }
+}
+
+/// A page of results from [`Query::page`]
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page, `None` if this was the last one
+    pub next_cursor: Option<EntityId>,
+}
+
 impl QueryData for () {
     type Output<'a> = ();
 
@@ -164,6 +472,43 @@ impl<C: Component> QueryData for C {
         e.component::<C>()
     }
 
+    fn try_from_entity<'a>(e: Entity<'a>) -> Result<Option<Self::Output<'a>>, crate::Error> {
+        e.try_component::<C>()
+    }
+
+    fn filter_expression() -> ir::FilterExpression {
+        ir::FilterExpression::with_component(C::component_name())
+    }
+}
+
+/// [`QueryData`] holding the raw stored data of component `C`, deserialized
+/// only when [`Lazy::get`] is called. Useful when iterating over large
+/// components of which only a few are needed.
+pub struct Lazy<'a, C>(crate::DynComponent<'a>, PhantomData<C>);
+
+impl<'a, C: Component> Lazy<'a, C> {
+    /// Deserializes the component
+    pub fn get(&self) -> Result<C, crate::component::StorageError> {
+        self.0.as_typed()
+    }
+
+    /// The raw stored data
+    pub fn raw(&self) -> &crate::DynComponent<'a> {
+        &self.0
+    }
+}
+
+impl<C: Component> QueryData for Lazy<'_, C> {
+    type Output<'a> = Lazy<'a, C>;
+
+    fn from_entity<'a>(e: Entity<'a>) -> Option<Self::Output<'a>> {
+        e.dyn_component(C::NAME).map(|c| Lazy(c, PhantomData))
+    }
+
+    fn try_from_entity<'a>(e: Entity<'a>) -> Result<Option<Self::Output<'a>>, crate::Error> {
+        Ok(e.try_dyn_component(C::NAME)?.map(|c| Lazy(c, PhantomData)))
+    }
+
     fn filter_expression() -> ir::FilterExpression {
         ir::FilterExpression::with_component(C::component_name())
     }
@@ -227,12 +572,59 @@ impl<C: Component> QueryFilter for Without<C> {
     }
 }
 
+impl<C: Component> QueryFilter for DataIsNull<C> {
+    fn filter_expression() -> ir::FilterExpression {
+        ir::FilterExpression::WithComponentDataNull(C::component_name().to_owned())
+    }
+}
+
+impl<C: Component> QueryFilter for DataIsNotNull<C> {
+    fn filter_expression() -> ir::FilterExpression {
+        ir::FilterExpression::WithComponentDataNotNull(C::component_name().to_owned())
+    }
+}
+
 impl QueryFilterValue for () {
     fn filter_expression(&self) -> ir::FilterExpression {
         ir::FilterExpression::None
     }
 }
 
+/// Lifts a type-level [`QueryFilter`] into a [`QueryFilterValue`] so it can be
+/// combined with runtime filter values, e.g. `(FilterValueWrapper::<Or<(With<A>,
+/// With<B>)>>::default(), entity_id)`.
+pub struct FilterValueWrapper<F>(PhantomData<F>);
+
+impl<F: QueryFilter> Default for FilterValueWrapper<F> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<F: QueryFilter> QueryFilterValue for FilterValueWrapper<F> {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        F::filter_expression()
+    }
+}
+
+impl<C> QueryFilterValue for With<C>
+where
+    With<C>: QueryFilter,
+{
+    fn filter_expression(&self) -> ir::FilterExpression {
+        <Self as QueryFilter>::filter_expression()
+    }
+}
+
+impl<C> QueryFilterValue for Without<C>
+where
+    Without<C>: QueryFilter,
+{
+    fn filter_expression(&self) -> ir::FilterExpression {
+        <Self as QueryFilter>::filter_expression()
+    }
+}
+
 impl QueryFilterValue for ir::FilterExpression {
     fn filter_expression(&self) -> ir::FilterExpression {
         self.clone()
@@ -260,76 +652,213 @@ impl<V: QueryFilterValue> QueryFilterValue for &[V] {
     }
 }
 
+/// `component` as the value of a filter, or why it can't be one
+fn filter_value<C: Component>(component: &C) -> Result<rusqlite::types::Value, String> {
+    use rusqlite::types::ToSqlOutput;
+
+    match C::to_rusqlite(component) {
+        Ok(ToSqlOutput::Borrowed(v)) => Ok(v.to_owned().into()),
+        Ok(ToSqlOutput::Owned(v)) => Ok(v),
+        Ok(other) => unreachable!("{other:?}"),
+        Err(e) => Err(format!("{}: {e}", C::NAME)),
+    }
+}
+
 impl<C: Component> QueryFilterValue for C {
     fn filter_expression(&self) -> ir::FilterExpression {
-        use rusqlite::types::ToSqlOutput;
-
-        let value = match C::to_rusqlite(self).unwrap() {
-            ToSqlOutput::Borrowed(v) => v.to_owned().into(),
-            ToSqlOutput::Owned(v) => v,
-            other => unreachable!("{other:?}"),
+        let value = match filter_value(self) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
         };
 
         ir::FilterExpression::with_component_data(C::component_name(), value)
     }
 }
 
-impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::Range<C> {
+/// Matches entities matching any of the filter values, which can be of
+/// different types. Usually built via [`crate::any!`]. An empty `AnyValue`
+/// matches nothing.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnyValue(pub Vec<ir::FilterExpression>);
+
+impl AnyValue {
+    pub fn push(&mut self, value: impl QueryFilterValue) {
+        self.0.push(value.filter_expression());
+    }
+}
+
+impl QueryFilterValue for AnyValue {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        if self.0.is_empty() {
+            return ir::FilterExpression::False;
+        }
+
+        ir::FilterExpression::or(self.0.iter().cloned())
+    }
+}
+
+/// Builds an [`AnyValue`](crate::query::AnyValue) matching entities that
+/// match any of the given filter values, e.g.
+/// `db.find(any![A(1), B("x".into())])`
+#[macro_export]
+macro_rules! any {
+    ($($value:expr),* $(,)?) => {
+        $crate::query::AnyValue(::std::vec![
+            $($crate::query::QueryFilterValue::filter_expression(&$value)),*
+        ])
+    };
+}
+
+/// Matches entities where component `C` equals any of the given values, e.g.
+/// `OneOf(vec![Status::Open, Status::Pending])`. An empty `OneOf` matches
+/// nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneOf<C>(pub Vec<C>);
+
+impl<C: Component> QueryFilterValue for OneOf<C> {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        let values = match self.0.iter().map(filter_value).collect() {
+            Ok(values) => values,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
+        };
+
+        ir::FilterExpression::WithComponentDataIn(C::component_name().to_owned(), values)
+    }
+}
+
+/// Matches entities where the string data of component `C` matches a SQL
+/// `like` pattern, e.g. `Like::<Name>::new("Ali%")`. `%` matches any sequence
+/// of characters, `_` a single one. Matching is case-insensitive for ASCII.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Like<C>(pub String, PhantomData<C>);
+
+impl<C> Like<C> {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self(pattern.into(), PhantomData)
+    }
+}
+
+impl<C: Component> QueryFilterValue for Like<C> {
     fn filter_expression(&self) -> ir::FilterExpression {
-        use rusqlite::types::ToSqlOutput;
+        ir::FilterExpression::WithComponentDataLike {
+            component: C::component_name().to_owned(),
+            pattern: self.0.clone(),
+        }
+    }
+}
+
+/// Escape hatch matching entities whose `component` data satisfies an
+/// arbitrary SQL expression, e.g. `json_extract(data, '$.n') % ?1 = 0`.
+///
+/// The expression is evaluated against a row of the `components` table, so
+/// it can refer to `data`. `params` are bound as `?1`, `?2`, ... and every
+/// param must be referenced.
+///
+/// `sql_expr` is pasted into the query verbatim. Never build it from
+/// untrusted input: pass values via `params` instead of interpolating them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawDataFilter {
+    pub component: String,
+    pub sql_expr: String,
+    pub params: Vec<rusqlite::types::Value>,
+}
 
-        let start = match C::to_rusqlite(&self.start).unwrap() {
-            ToSqlOutput::Borrowed(v) => v.to_owned().into(),
-            ToSqlOutput::Owned(v) => v,
-            other => unreachable!("{other:?}"),
+impl QueryFilterValue for RawDataFilter {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        ir::FilterExpression::WithComponentDataSql {
+            component: self.component.clone(),
+            sql_expr: self.sql_expr.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::Range<C> {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        let start = match filter_value(&self.start) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
         };
 
-        let end = match C::to_rusqlite(&self.end).unwrap() {
-            ToSqlOutput::Borrowed(v) => v.to_owned().into(),
-            ToSqlOutput::Owned(v) => v,
-            other => unreachable!("{other:?}"),
+        let end = match filter_value(&self.end) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
         };
 
         ir::FilterExpression::WithComponentDataRange {
             component: C::component_name().to_owned(),
             start,
             end,
+            end_inclusive: false,
         }
     }
 }
 
 impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::RangeTo<C> {
     fn filter_expression(&self) -> ir::FilterExpression {
-        use rusqlite::types::ToSqlOutput;
-
-        let end = match C::to_rusqlite(&self.end).unwrap() {
-            ToSqlOutput::Borrowed(v) => v.to_owned().into(),
-            ToSqlOutput::Owned(v) => v,
-            other => unreachable!("{other:?}"),
+        let end = match filter_value(&self.end) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
         };
 
         ir::FilterExpression::WithComponentDataRange {
             component: C::component_name().to_owned(),
             start: rusqlite::types::Value::Null,
             end,
+            end_inclusive: false,
         }
     }
 }
 
 impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::RangeFrom<C> {
     fn filter_expression(&self) -> ir::FilterExpression {
-        use rusqlite::types::ToSqlOutput;
-
-        let start = match C::to_rusqlite(&self.start).unwrap() {
-            ToSqlOutput::Borrowed(v) => v.to_owned().into(),
-            ToSqlOutput::Owned(v) => v,
-            other => unreachable!("{other:?}"),
+        let start = match filter_value(&self.start) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
         };
 
         ir::FilterExpression::WithComponentDataRange {
             component: C::component_name().to_owned(),
             start,
             end: rusqlite::types::Value::Null,
+            end_inclusive: true,
+        }
+    }
+}
+
+impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::RangeInclusive<C> {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        let start = match filter_value(self.start()) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
+        };
+
+        let end = match filter_value(self.end()) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
+        };
+
+        ir::FilterExpression::WithComponentDataRange {
+            component: C::component_name().to_owned(),
+            start,
+            end,
+            end_inclusive: true,
+        }
+    }
+}
+
+impl<C: QueryFilterValue + Component> QueryFilterValue for std::ops::RangeToInclusive<C> {
+    fn filter_expression(&self) -> ir::FilterExpression {
+        let end = match filter_value(&self.end) {
+            Ok(value) => value,
+            Err(reason) => return ir::FilterExpression::Invalid(reason),
+        };
+
+        ir::FilterExpression::WithComponentDataRange {
+            component: C::component_name().to_owned(),
+            start: rusqlite::types::Value::Null,
+            end,
+            end_inclusive: true,
         }
     }
 }
@@ -349,6 +878,15 @@ mod tuples {
                     Some(($($ts::from_entity(e)?,)+))
                 }
 
+                fn try_from_entity<'a>(e: Entity<'a>) -> Result<Option<Self::Output<'a>>, crate::Error> {
+                    Ok(Some(($(
+                        match $ts::try_from_entity(e)? {
+                            Some(data) => data,
+                            None => return Ok(None),
+                        },
+                    )+)))
+                }
+
 
                 fn filter_expression() -> ir::FilterExpression{
                     ir::FilterExpression::and([
@@ -457,4 +995,180 @@ mod tests {
         fn sys_b(query: Query<(A, Without<B>)>) {}
         fn sys_c(query: Query<Or<(A, B)>>) {}
     }
+
+    #[test]
+    fn keyset_pagination() {
+        let db = crate::Ecs::open_in_memory().unwrap();
+        let ids = (0..25)
+            .map(|_| db.new_entity().attach(A).id())
+            .collect::<Vec<_>>();
+        db.new_entity().attach(B);
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let mut query = Query::<EntityId, With<A>>::new(&db);
+            if let Some(cursor) = cursor {
+                query = query.after(cursor);
+            }
+
+            let page = query.page(10);
+            seen.extend(page.items);
+            pages += 1;
+
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(seen, ids);
+
+        let reversed = Query::<EntityId, With<A>>::new(&db)
+            .after(ids[10])
+            .limit(3)
+            .reverse_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(reversed, vec![ids[9], ids[8], ids[7]]);
+    }
+
+    #[test]
+    fn for_each_while_stops_early() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static READ: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+        impl QueryData for Counted {
+            type Output<'a> = EntityId;
+
+            fn from_entity<'a>(e: Entity<'a>) -> Option<Self::Output<'a>> {
+                READ.fetch_add(1, Ordering::SeqCst);
+                Some(e.id())
+            }
+
+            fn filter_expression() -> ir::FilterExpression {
+                ir::FilterExpression::with_component(A::NAME)
+            }
+        }
+
+        let db = crate::Ecs::open_in_memory().unwrap();
+        let ids = (0..100)
+            .map(|_| db.new_entity().attach(A).id())
+            .collect::<Vec<_>>();
+
+        let mut seen = Vec::new();
+        Query::<Counted>::new(&db).for_each_while(|id| {
+            seen.push(id);
+            if seen.len() == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        assert_eq!(seen, ids[..3]);
+        assert_eq!(READ.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn for_each_while_returns_errors() {
+        let db = crate::Ecs::open_in_memory().unwrap();
+        db.new_entity().attach(A);
+
+        // Malformed JSON paths only fail once a row is evaluated
+        let broken = Query::<EntityId, (), _>::with_filter(
+            &db,
+            RawDataFilter {
+                component: A::NAME.to_owned(),
+                sql_expr: "json_extract(data, '$[') is not null".to_owned(),
+                params: vec![],
+            },
+        );
+        assert!(matches!(
+            broken.try_for_each_while(|_| ControlFlow::Continue(())),
+            Err(crate::Error::Database(_))
+        ));
+    }
+
+    #[test]
+    fn raw_data_filter() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Counter {
+            n: u32,
+        }
+
+        let db = crate::Ecs::open_in_memory().unwrap();
+        for n in 0..6 {
+            db.new_entity().attach((A, Counter { n }));
+        }
+
+        let even = |sql_expr: &str, params: Vec<rusqlite::types::Value>| {
+            Query::<Counter, With<A>, _>::with_filter(
+                &db,
+                RawDataFilter {
+                    component: Counter::NAME.to_owned(),
+                    sql_expr: sql_expr.to_owned(),
+                    params,
+                },
+            )
+            .iter()
+            .map(|c| c.n)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(even("json_extract(data, '$.n') % 2 = 0", vec![]), [0, 2, 4]);
+        assert_eq!(
+            even(
+                "json_extract(data, '$.n') % ?1 = 0 and json_extract(data, '$.n') > ?2",
+                vec![2.into(), 0.into()]
+            ),
+            [2, 4]
+        );
+    }
+
+    #[test]
+    fn raw_data_filter_keeps_literals() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Label(String);
+
+        let db = crate::Ecs::open_in_memory().unwrap();
+        for label in [":1", "12:15", "?1", "other"] {
+            db.new_entity().attach((A, Label(label.to_owned())));
+        }
+
+        // Placeholder lookalikes in literals survive combining the filters
+        let labels = Query::<Label, With<A>, _>::with_filter(
+            &db,
+            RawDataFilter {
+                component: Label::NAME.to_owned(),
+                sql_expr:
+                    "json_extract(data, '$') in (':1', '12:15') or json_extract(data, '$') = ?1"
+                        .to_owned(),
+                params: vec!["?1".to_owned().into()],
+            },
+        )
+        .iter()
+        .map(|l| l.0)
+        .collect::<Vec<_>>();
+        assert_eq!(labels, [":1", "12:15", "?1"]);
+    }
+
+    #[test]
+    fn to_sql_and_explain() {
+        let db = crate::Ecs::open_in_memory().unwrap();
+        db.new_entity().attach(A);
+
+        let query = Query::<Entity, With<A>>::new(&db);
+        let (sql, placeholders) = query.to_sql();
+        assert!(sql.contains(
+            "(select true from components c2 where c2.entity = components.entity and c2.component ="
+        ));
+        assert!(placeholders.iter().any(|p| p.contains(A::NAME)));
+
+        let plan = query.explain().unwrap();
+        assert!(!plan.trim().is_empty());
+    }
 }
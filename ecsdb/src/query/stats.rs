@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Execution statistics of a single generated SQL query, see
+/// [`crate::Ecs::query_stats`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryStat {
+    pub sql: String,
+    pub count: u64,
+    pub total_duration: Duration,
+}
+
+/// Collects a [`QueryStat`] per distinct SQL string. Enabled via
+/// [`crate::EcsOptions::query_stats`].
+#[derive(Debug, Default)]
+pub(crate) struct QueryStats(Mutex<HashMap<String, QueryStat>>);
+
+impl QueryStats {
+    pub(crate) fn record(&self, sql: &str, duration: Duration) {
+        let mut stats = self.0.lock().unwrap();
+        let stat = stats.entry(sql.to_owned()).or_insert_with(|| QueryStat {
+            sql: sql.to_owned(),
+            count: 0,
+            total_duration: Duration::ZERO,
+        });
+        stat.count += 1;
+        stat.total_duration += duration;
+    }
+
+    /// All stats, by descending total duration
+    pub(crate) fn snapshot(&self) -> Vec<QueryStat> {
+        let mut stats = self.0.lock().unwrap().values().cloned().collect::<Vec<_>>();
+        stats.sort_by(|a, b| b.total_duration.cmp(&a.total_duration));
+        stats
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use ecsdb_derive::with_infallible;
+
+use super::{Query, QueryData, QueryFilter};
+use crate::{Ecs, EntityId, Error};
+
+/// Tracks which entities entered or left the result set of a query between
+/// calls to [`QueryWatcher::poll`]. The first poll reports every matching
+/// entity as added.
+pub struct QueryWatcher<D = EntityId, F = ()> {
+    previous: HashSet<EntityId>,
+    query: PhantomData<(D, F)>,
+}
+
+/// Result of [`QueryWatcher::poll`]. `added` and `removed` are sorted by id,
+/// `current` is in query order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryChanges {
+    pub added: Vec<EntityId>,
+    pub removed: Vec<EntityId>,
+    pub current: Vec<EntityId>,
+}
+
+impl QueryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl<D, F> Default for QueryWatcher<D, F> {
+    fn default() -> Self {
+        Self {
+            previous: HashSet::new(),
+            query: PhantomData,
+        }
+    }
+}
+
+impl<D, F> QueryWatcher<D, F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[with_infallible]
+impl<D, F> QueryWatcher<D, F>
+where
+    D: QueryData + 'static,
+    F: QueryFilter,
+{
+    pub fn try_poll(&mut self, ecs: &Ecs) -> Result<QueryChanges, Error> {
+        let current = Query::<D, F>::new(ecs)
+            .try_entities()?
+            .map(|e| e.id())
+            .collect::<Vec<_>>();
+        let current_set = current.iter().copied().collect::<HashSet<_>>();
+
+        let mut added = current_set
+            .difference(&self.previous)
+            .copied()
+            .collect::<Vec<_>>();
+        added.sort_unstable();
+
+        let mut removed = self
+            .previous
+            .difference(&current_set)
+            .copied()
+            .collect::<Vec<_>>();
+        removed.sort_unstable();
+
+        self.previous = current_set;
+
+        Ok(QueryChanges {
+            added,
+            removed,
+            current,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{QueryChanges, QueryWatcher};
+    use crate::query::With;
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[test]
+    fn query_watcher() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(A).id();
+        db.new_entity().attach(B);
+
+        let mut watcher = QueryWatcher::<A>::new();
+        assert_eq!(
+            watcher.poll(&db),
+            QueryChanges {
+                added: vec![a],
+                removed: vec![],
+                current: vec![a],
+            }
+        );
+        assert!(watcher.poll(&db).is_empty());
+
+        let b = db.new_entity().attach((A, B)).id();
+        let changes = watcher.poll(&db);
+        assert_eq!(changes.added, vec![b]);
+        assert!(changes.removed.is_empty());
+        assert_eq!(changes.current, vec![a, b]);
+
+        db.entity(a).destroy();
+        let changes = watcher.poll(&db);
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed, vec![a]);
+        assert_eq!(changes.current, vec![b]);
+
+        let mut filtered = QueryWatcher::<crate::EntityId, With<B>>::new();
+        assert_eq!(filtered.poll(&db).added, vec![b]);
+    }
+}
@@ -0,0 +1,162 @@
+use ecsdb_derive::with_infallible;
+use rusqlite::params;
+use tracing::debug;
+
+use crate::{Component, Ecs, Entity, EntityId, Error};
+
+/// Marks a component as storing the id of another entity, e.g. a
+/// `BelongsTo(EntityId)`. Register it via [`Ecs::register_reference`] to have
+/// it checked by [`Ecs::validate_references`] and followed by
+/// [`Entity::destroy_cascade`].
+pub trait Reference: Component {
+    /// JSON path of the referenced entity id in the stored data. `$` for
+    /// newtypes, `$.parent` for a struct field `parent`.
+    const PATH: &'static str = "$";
+}
+
+impl Ecs {
+    pub fn register_reference<R: Reference>(&mut self) {
+        self.registry.references.insert(R::NAME, R::PATH);
+    }
+}
+
+#[with_infallible]
+impl Ecs {
+    /// Returns `(entity, component)` for every registered [`Reference`]
+    /// component pointing at an entity that doesn't exist.
+    pub fn try_validate_references(&self) -> Result<Vec<(EntityId, String)>, Error> {
+        let mut stmt = self.conn.prepare_cached(
+            r#"
+            select entity, component from components
+            where component = ?1
+            and json_extract(data, ?2) not in (select id from entities)
+            order by entity
+            "#,
+        )?;
+
+        let mut dangling = Vec::new();
+        for (component, path) in self.registry.references.iter() {
+            let component = self.stored_component_name(component);
+            let rows = stmt.query_map(params![component, path], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            for row in rows {
+                dangling.push(row?);
+            }
+        }
+
+        dangling.sort();
+        Ok(dangling)
+    }
+
+    /// Entities with a registered [`Reference`] component pointing at `entity`
+    pub fn try_referencing<'a>(&'a self, entity: EntityId) -> Result<Vec<Entity<'a>>, Error> {
+        let mut stmt = self.conn.prepare_cached(
+            "select entity from components where component = ?1 and json_extract(data, ?2) = ?3",
+        )?;
+
+        let mut referencing = Vec::new();
+        for (component, path) in self.registry.references.iter() {
+            let component = self.stored_component_name(component);
+            let rows = stmt.query_map(params![component, path, entity], |row| {
+                row.get::<_, EntityId>(0)
+            })?;
+            for row in rows {
+                referencing.push(self.entity(row?));
+            }
+        }
+
+        Ok(referencing)
+    }
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    /// Destroys this entity and, recursively, all entities referencing it via
+    /// a registered [`Reference`] component.
+    #[tracing::instrument(name = "destroy_cascade", level = "debug")]
+    pub fn try_destroy_cascade(self) -> Result<(), Error> {
+        let mut stack = vec![self];
+        while let Some(entity) = stack.pop() {
+            stack.extend(
+                self.0
+                    .try_referencing(entity.id())?
+                    .into_iter()
+                    .filter(|e| e.id() != entity.id()),
+            );
+            entity.try_destroy()?;
+            debug!(entity = entity.id(), "destroyed");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Reference;
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct BelongsTo(EntityId);
+
+    impl Reference for BelongsTo {}
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Edge {
+        label: String,
+        target: EntityId,
+    }
+
+    impl Reference for Edge {
+        const PATH: &'static str = "$.target";
+    }
+
+    #[test]
+    fn validate_references() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_reference::<BelongsTo>();
+        db.register_reference::<Edge>();
+
+        let parent = db.new_entity().attach(A);
+        let child = db.new_entity().attach(BelongsTo(parent.id()));
+        let edge = db.new_entity().attach(Edge {
+            label: "to parent".into(),
+            target: parent.id(),
+        });
+        assert_eq!(db.validate_references(), vec![]);
+
+        parent.destroy();
+        assert_eq!(
+            db.validate_references(),
+            vec![
+                (child.id(), BelongsTo::NAME.to_owned()),
+                (edge.id(), Edge::NAME.to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn destroy_cascade() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_reference::<BelongsTo>();
+
+        let parent = db.new_entity().attach(A);
+        let child = db.new_entity().attach(BelongsTo(parent.id()));
+        let grandchild = db.new_entity().attach(BelongsTo(child.id()));
+        let other = db.new_entity().attach(A);
+
+        parent.destroy_cascade();
+
+        assert!(!parent.exists());
+        assert!(!child.exists());
+        assert!(!grandchild.exists());
+        assert!(other.exists());
+        assert_eq!(db.validate_references(), vec![]);
+    }
+}
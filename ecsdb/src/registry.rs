@@ -0,0 +1,390 @@
+use std::{any::TypeId, collections::HashMap};
+
+use ecsdb_derive::with_infallible;
+use tracing::debug;
+
+use crate::{
+    Component, Ecs, Error,
+    component::{Bundle, ComponentType},
+};
+
+/// How [`Ecs::register_component`] names components in the database instead
+/// of their [`Component::NAME`], e.g. to get shorter names independent of
+/// the Rust module structure. Registering an aliased component renames its
+/// stored rows to the alias and records the mapping in the
+/// `component_aliases` table, so the rename can be undone. Both the alias
+/// and `NAME` are accepted wherever a component is referred to by name, e.g.
+/// in [`crate::query::ComponentName`] or [`crate::Entity::dyn_component`].
+#[derive(Debug, Clone, Default)]
+pub enum ComponentNaming {
+    /// No aliases
+    #[default]
+    FullPath,
+    /// Alias names starting with the prefix by the rest of the name
+    StripPrefix(String),
+    /// Alias every name by the result of the function
+    Map(fn(&str) -> String),
+}
+
+impl ComponentNaming {
+    fn alias(&self, name: &str) -> Option<String> {
+        let alias = match self {
+            ComponentNaming::FullPath => return None,
+            ComponentNaming::StripPrefix(prefix) => name.strip_prefix(prefix.as_str())?.to_owned(),
+            ComponentNaming::Map(f) => f(name),
+        };
+
+        (!alias.is_empty() && alias != name).then_some(alias)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct ComponentRegistry {
+    types: HashMap<&'static str, (TypeId, &'static str)>,
+    /// Component name to JSON path of the referenced entity id, see
+    /// [`crate::Reference`]
+    pub(crate) references: HashMap<&'static str, &'static str>,
+    /// Component name to bit in the presence mask, see
+    /// [`Ecs::enable_component_mask`]
+    pub(crate) component_bits: HashMap<String, u32>,
+    /// Component name to the alias it's stored under, see [`ComponentNaming`]
+    pub(crate) aliases: HashMap<String, String>,
+    /// Alias to component name, the reverse of `aliases`
+    aliased: HashMap<String, String>,
+    naming: ComponentNaming,
+    strict: bool,
+}
+
+#[with_infallible]
+impl Ecs {
+    /// Records `C` as the owner of `C::NAME`. Fails if a different type
+    /// already claimed the same name, e.g. via `#[component(name = "...")]`.
+    /// Registering the same type twice is a no-op.
+    pub fn try_register_component<C: Component>(&mut self) -> Result<(), Error> {
+        let component = ComponentType::of::<C>();
+        match self.registry.types.get(component.name) {
+            Some((type_id, _)) if *type_id == component.type_id => Ok(()),
+            Some((_, registered)) => Err(Error::ComponentNameConflict {
+                name: component.name,
+                registered,
+                conflicting: component.type_name,
+            }),
+            None => {
+                if let Some(alias) = self.registry.naming.alias(component.name) {
+                    self.try_alias_component(component.name, alias)?;
+                }
+
+                self.registry
+                    .types
+                    .insert(component.name, (component.type_id, component.type_name));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Ecs {
+    /// Sets how components registered from now on are aliased
+    pub fn set_component_naming(&mut self, naming: ComponentNaming) {
+        self.registry.naming = naming;
+    }
+
+    /// The name component `name` is stored under: its alias if it has one,
+    /// otherwise `name` itself. Aliases map to themselves.
+    pub(crate) fn stored_component_name<'n>(&'n self, name: &'n str) -> &'n str {
+        self.registry
+            .aliases
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or(name)
+    }
+
+    /// The [`Component::NAME`] of the component stored as `stored`, e.g. to
+    /// look up hooks registered for it
+    pub(crate) fn unaliased_component_name<'n>(&'n self, stored: &'n str) -> &'n str {
+        self.registry
+            .aliased
+            .get(stored)
+            .map(String::as_str)
+            .unwrap_or(stored)
+    }
+
+    /// Renames the stored rows of `component` to `alias`. Fails if `alias`
+    /// is already used as the name or alias of another component.
+    fn try_alias_component(&mut self, component: &str, alias: String) -> Result<(), Error> {
+        let current = self.stored_component_name(component).to_owned();
+        if current == alias {
+            return Ok(());
+        }
+
+        if self.registry.aliased.contains_key(&alias)
+            || self.registry.aliases.contains_key(&alias)
+            || self.registry.types.contains_key(alias.as_str())
+        {
+            return Err(Error::ComponentExists(alias));
+        }
+
+        let tx = self.transaction()?;
+        let taken = tx
+            .prepare("select 1 from components where component = ?1")?
+            .exists([&alias])?;
+        if taken {
+            return Err(Error::ComponentExists(alias));
+        }
+
+        // Mask bits first, so the mask trigger moves the bits along
+        if !self.registry.component_bits.is_empty() {
+            tx.execute(
+                "update component_bits set component = ?1 where component = ?2",
+                [&alias, &current],
+            )?;
+        }
+        let renamed = tx.execute(
+            "update components set component = ?1 where component = ?2",
+            [&alias, &current],
+        )?;
+        tx.execute(
+            "update multi_components set component = ?1 where component = ?2",
+            [&alias, &current],
+        )?;
+        tx.execute(
+            "delete from component_aliases where component = ?1",
+            [component],
+        )?;
+        tx.execute(
+            "insert into component_aliases (alias, component) values (?1, ?2)",
+            [alias.as_str(), component],
+        )?;
+        tx.commit()?;
+        debug!(component, alias, renamed, "aliased component");
+
+        self.registry.component_bits = crate::component_mask::load_component_bits(&self.conn)?;
+        self.registry
+            .set_aliases(load_component_aliases(&self.conn)?);
+        Ok(())
+    }
+}
+
+impl ComponentRegistry {
+    /// Replaces the aliases by `aliases`, component name to alias
+    pub(crate) fn set_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.aliased = aliases
+            .iter()
+            .map(|(component, alias)| (alias.clone(), component.clone()))
+            .collect();
+        self.aliases = aliases;
+    }
+}
+
+impl Ecs {
+    /// In strict mode, attaching a component that wasn't registered via
+    /// [`Ecs::register_component`], or whose type differs from the
+    /// registered one, fails.
+    pub fn set_strict_components(&mut self, strict: bool) {
+        self.registry.strict = strict;
+    }
+
+    pub(crate) fn check_registered<B: Bundle>(&self) -> Result<(), Error> {
+        if !self.registry.strict {
+            return Ok(());
+        }
+
+        let types = B::component_types();
+        for name in B::COMPONENTS {
+            let Some((type_id, registered)) = self.registry.types.get(name) else {
+                return Err(Error::UnregisteredComponent(name.to_string()));
+            };
+
+            // A bundle not reporting the type can't be checked
+            let conflicting = match types.iter().find(|t| t.name == *name) {
+                Some(t) if t.type_id == *type_id => continue,
+                Some(t) => t.type_name,
+                None => "an unknown type",
+            };
+
+            return Err(Error::ComponentNameConflict {
+                name,
+                registered,
+                conflicting,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Component name to alias, see [`ComponentNaming`]
+pub(crate) fn load_component_aliases(
+    conn: &rusqlite::Connection,
+) -> Result<HashMap<String, String>, Error> {
+    let mut stmt = conn.prepare("select component, alias from component_aliases")?;
+    let aliases = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::query::{ComponentName, Query};
+    use crate::{self as ecsdb, Component, ComponentNaming, Ecs, EntityId, Error};
+
+    mod a {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(name = "test::Shared")]
+        pub struct Shared;
+    }
+
+    mod b {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(name = "test::Shared")]
+        pub struct Shared(pub u32);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Other;
+
+    #[test]
+    fn register_conflicting_names() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_component::<a::Shared>();
+        db.register_component::<a::Shared>();
+
+        assert!(matches!(
+            db.try_register_component::<b::Shared>(),
+            Err(Error::ComponentNameConflict {
+                name: "test::Shared",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn strict_components() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_component::<a::Shared>();
+
+        // Not checked outside strict mode
+        db.new_entity().attach(b::Shared(1));
+
+        db.set_strict_components(true);
+        let entity = db.new_entity().attach(a::Shared);
+        assert!(matches!(
+            entity.try_attach(b::Shared(1)),
+            Err(Error::ComponentNameConflict { .. })
+        ));
+        assert!(matches!(
+            entity.try_attach(Other),
+            Err(Error::UnregisteredComponent(_))
+        ));
+    }
+
+    #[test]
+    fn strict_components_need_types() {
+        use crate::component::{BundleComponent, StorageError};
+        use rusqlite::types::{ToSqlOutput, Value};
+
+        // Doesn't report its type, so it can't be told apart from `Other`
+        struct Raw;
+        impl BundleComponent for Raw {
+            const NAME: &'static str = Other::NAME;
+
+            fn to_rusqlite<'a>(&'a self) -> Result<Option<ToSqlOutput<'a>>, StorageError> {
+                Ok(Some(ToSqlOutput::Owned(Value::Null)))
+            }
+
+            fn from_bundle_data(
+                data: Option<&ToSqlOutput<'_>>,
+            ) -> Result<Option<Self>, StorageError> {
+                Ok(data.map(|_| Raw))
+            }
+        }
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_component::<Other>();
+        let entity = db.new_entity().attach((Raw,));
+
+        db.set_strict_components(true);
+        assert!(matches!(
+            entity.try_attach((Raw,)),
+            Err(Error::ComponentNameConflict {
+                conflicting: "an unknown type",
+                ..
+            })
+        ));
+        entity.attach(Other);
+    }
+
+    #[test]
+    fn component_naming() {
+        let mut db = Ecs::open_in_memory().unwrap();
+        let existing = db.new_entity().attach(Other);
+
+        db.set_component_naming(ComponentNaming::Map(|name| {
+            name.rsplit("::").next().unwrap().to_lowercase()
+        }));
+        db.register_component::<Other>();
+        assert_ne!(Other::NAME, "other");
+
+        // Existing and new rows are stored under the alias
+        let entity = db.new_entity().attach(Other);
+        assert_eq!(db.component_names().unwrap().as_ref(), ["other".to_owned()]);
+        assert!(existing.has::<Other>());
+        assert_eq!(
+            db.query::<EntityId, Other>().collect::<Vec<_>>(),
+            vec![existing.id(), entity.id()]
+        );
+
+        let by_alias = Query::<EntityId, (), _>::with_filter(&db, ComponentName("other".into()));
+        assert_eq!(
+            by_alias.iter().collect::<Vec<_>>(),
+            vec![existing.id(), entity.id()]
+        );
+        assert!(entity.dyn_component("other").is_some());
+        assert!(entity.dyn_component(Other::NAME).is_some());
+
+        // The alias table maps back to the Rust name
+        let component: String = db
+            .raw_sql()
+            .query_row(
+                "select component from component_aliases where alias = 'other'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(component, Other::NAME);
+
+        entity.detach_named("other");
+        assert!(!entity.has::<Other>());
+
+        db.remove_component_everywhere_named(Other::NAME).unwrap();
+        assert!(!existing.has::<Other>());
+    }
+
+    #[test]
+    fn aliases_cant_shadow_other_components() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(name = "taken")]
+        struct Taken;
+
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        #[component(name = "test::taken")]
+        struct Shadowing;
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        db.register_component::<Taken>();
+        db.set_component_naming(ComponentNaming::StripPrefix("test::".into()));
+
+        assert!(matches!(
+            db.try_register_component::<Shadowing>(),
+            Err(Error::ComponentExists(alias)) if alias == "taken"
+        ));
+    }
+}
@@ -1,8 +1,9 @@
 use std::ops::{Deref, DerefMut};
 
 use ecsdb_derive::with_infallible;
+use tracing::warn;
 
-use crate::{Component, Ecs, Error};
+use crate::{Bundle, Component, Ecs, Error, ReadOnlySystemParam, SystemParam};
 
 #[with_infallible]
 impl Ecs {
@@ -12,9 +13,18 @@ impl Ecs {
 
     pub fn try_resource_mut<'a, R: Component + Default>(
         &'a mut self,
-    ) -> Result<impl DerefMut<Target = R> + 'a, Error> {
+    ) -> Result<ResourceProxy<'a, R>, Error> {
         let resource = self.try_resource()?.unwrap_or_default();
-        Ok(ResourceProxy(self, resource))
+        Ok(ResourceProxy(self, Some(resource)))
+    }
+
+    /// Entry-style access to a resource, modeled on `HashMap::entry`. Unlike
+    /// [`Ecs::resource_mut`] this doesn't require `R: Default`.
+    pub fn try_resource_entry<'a, R: Component>(
+        &'a mut self,
+    ) -> Result<ResourceEntry<'a, R>, Error> {
+        let resource = self.try_resource()?;
+        Ok(ResourceEntry(self, resource))
     }
 
     pub fn try_attach_resource<R: Component>(&self, resource: R) -> Result<(), Error> {
@@ -22,38 +32,137 @@ impl Ecs {
         Ok(())
     }
 
+    /// Attaches all resources in the bundle `B`, e.g. a tuple of resources,
+    /// in a single transaction
+    pub fn try_attach_resources<B: Bundle>(&self, resources: B) -> Result<(), Error> {
+        let tx = self.transaction()?;
+        self.world_entity().try_attach(resources)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn try_has_resource<R: Component>(&self) -> Result<bool, Error> {
+        self.world_entity().try_has::<R>()
+    }
+
     pub fn try_detach_resource<R: Component>(&self) -> Result<(), Error> {
         self.world_entity().try_detach::<R>()?;
         Ok(())
     }
 }
 
-pub struct ResourceProxy<'a, R: Component + Default>(&'a mut Ecs, R);
+/// System parameter reading resource `R`. Systems taking a `Res<R>` are
+/// skipped while `R` doesn't exist. Use `Option<Res<R>>` to run them anyway.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Res<R>(pub R);
+
+impl<R> Deref for Res<R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<R: Component> SystemParam for Res<R> {
+    type Item<'world> = Res<R>;
+
+    fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world> {
+        let Some(resource) = Self::try_get_param(world, system) else {
+            panic!("Resource {} doesn't exist", R::NAME);
+        };
+
+        resource
+    }
+
+    fn try_get_param<'world>(world: &'world Ecs, _system: &str) -> Option<Self::Item<'world>> {
+        match world.try_resource::<R>() {
+            Ok(resource) => resource.map(Res),
+            Err(e) => {
+                warn!(resource = R::NAME, error = %e, "Failed to read resource");
+                None
+            }
+        }
+    }
+}
+
+impl<R: Component> ReadOnlySystemParam for Res<R> {}
+
+pub struct ResourceEntry<'a, R: Component>(&'a mut Ecs, Option<R>);
+
+impl<'a, R: Component> ResourceEntry<'a, R> {
+    pub fn or_insert(self, default: R) -> ResourceProxy<'a, R> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, f: impl FnOnce() -> R) -> ResourceProxy<'a, R> {
+        let ResourceEntry(ecs, resource) = self;
+        ResourceProxy(ecs, Some(resource.unwrap_or_else(f)))
+    }
+
+    /// Modifies the resource if it's already present
+    pub fn and_modify(mut self, f: impl FnOnce(&mut R)) -> Self {
+        if let Some(ref mut resource) = self.1 {
+            f(resource);
+        }
+        self
+    }
+}
+
+/// Writes the resource back on `drop`, unless written explicitly via
+/// [`ResourceProxy::commit`] or discarded via [`ResourceProxy::abandon`]
+pub struct ResourceProxy<'a, R: Component>(&'a mut Ecs, Option<R>);
+
+impl<'a, R: Component> ResourceProxy<'a, R> {
+    pub fn and_modify(mut self, f: impl FnOnce(&mut R)) -> Self {
+        f(self.deref_mut());
+        self
+    }
+
+    /// Discards the changes without writing the resource back
+    pub fn abandon(self) {
+        let mut proxy = self;
+        proxy.1 = None;
+    }
+}
 
-impl<'a, R: Component + Default> AsMut<R> for ResourceProxy<'a, R> {
+#[with_infallible]
+impl<'a, R: Component> ResourceProxy<'a, R> {
+    /// Writes the resource back now instead of on `drop`, returning any error
+    pub fn try_commit(self) -> Result<(), Error> {
+        let mut proxy = self;
+        match proxy.1.take() {
+            Some(resource) => proxy.0.try_attach_resource(resource),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, R: Component> AsMut<R> for ResourceProxy<'a, R> {
     fn as_mut(&mut self) -> &mut R {
-        &mut self.1
+        self.deref_mut()
     }
 }
 
-impl<'a, R: Component + Default> Deref for ResourceProxy<'a, R> {
+impl<'a, R: Component> Deref for ResourceProxy<'a, R> {
     type Target = R;
 
     fn deref(&self) -> &Self::Target {
-        &self.1
+        self.1.as_ref().expect("ResourceProxy already written back")
     }
 }
 
-impl<'a, R: Component + Default> DerefMut for ResourceProxy<'a, R> {
+impl<'a, R: Component> DerefMut for ResourceProxy<'a, R> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.1
+        self.1.as_mut().expect("ResourceProxy already written back")
     }
 }
 
-impl<'a, R: Component + Default> Drop for ResourceProxy<'a, R> {
+impl<'a, R: Component> Drop for ResourceProxy<'a, R> {
     fn drop(&mut self) {
-        let resource = std::mem::take(&mut self.1);
-        self.0.attach_resource(resource);
+        if let Some(resource) = self.1.take() {
+            self.0.attach_resource(resource);
+        }
     }
 }
 
@@ -67,6 +176,25 @@ mod tests {
     #[derive(Debug, Serialize, Deserialize, Component, PartialEq, Default)]
     struct TestResource(pub i32);
 
+    #[test]
+    fn attach_resources() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Volume(f32);
+
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Title(String);
+
+        let ecs = Ecs::open_in_memory().unwrap();
+        assert!(!ecs.has_resource::<TestResource>());
+
+        ecs.attach_resources((TestResource(1), Volume(0.5), Title("ecsdb".into())));
+
+        assert!(ecs.has_resource::<TestResource>());
+        assert_eq!(ecs.resource::<TestResource>(), Some(TestResource(1)));
+        assert_eq!(ecs.resource::<Volume>(), Some(Volume(0.5)));
+        assert_eq!(ecs.resource::<Title>(), Some(Title("ecsdb".into())));
+    }
+
     #[test]
     fn ecs_resource() {
         let mut ecs = Ecs::open_in_memory().unwrap();
@@ -92,4 +220,73 @@ mod tests {
 
         assert_eq!(ecs.resource::<TestResource>().unwrap(), TestResource(1234));
     }
+
+    #[test]
+    fn resource_entry() {
+        #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+        struct Counter {
+            name: String,
+            count: usize,
+        }
+
+        let mut ecs = Ecs::open_in_memory().unwrap();
+
+        ecs.resource_entry::<Counter>()
+            .or_insert_with(|| Counter {
+                name: "clicks".into(),
+                count: 0,
+            })
+            .and_modify(|c| c.count += 1);
+
+        assert_eq!(
+            ecs.resource::<Counter>(),
+            Some(Counter {
+                name: "clicks".into(),
+                count: 1
+            })
+        );
+
+        ecs.resource_entry::<Counter>()
+            .and_modify(|c| c.count += 1)
+            .or_insert(Counter {
+                name: "unused".into(),
+                count: 0,
+            });
+
+        assert_eq!(
+            ecs.resource::<Counter>(),
+            Some(Counter {
+                name: "clicks".into(),
+                count: 2
+            })
+        );
+    }
+
+    #[test]
+    fn resource_proxy_commit() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+
+        let mut proxy = ecs.resource_mut::<TestResource>();
+        proxy.0 = 7;
+        proxy.commit();
+
+        assert_eq!(ecs.resource::<TestResource>(), Some(TestResource(7)));
+    }
+
+    #[test]
+    fn resource_proxy_abandon() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.attach_resource(TestResource(1));
+
+        let mut proxy = ecs.resource_mut::<TestResource>();
+        proxy.0 = 2;
+        proxy.abandon();
+
+        assert_eq!(ecs.resource::<TestResource>(), Some(TestResource(1)));
+
+        ecs.resource_mut::<TestResource>().abandon();
+        ecs.detach_resource::<TestResource>();
+        ecs.resource_mut::<TestResource>().abandon();
+        assert!(!ecs.has_resource::<TestResource>());
+    }
 }
@@ -0,0 +1,207 @@
+use ecsdb_derive::with_infallible;
+use tracing::{debug, warn};
+
+use crate::{Ecs, Error};
+
+/// Guard for a named SQLite savepoint created via [`Ecs::savepoint`].
+/// Dropping it rolls back every change made since it was created, unless
+/// [`Savepoint::release`] was called.
+///
+/// Savepoints nest. The outermost savepoint behaves like a transaction:
+/// releasing it commits. Operations running in their own transaction, like
+/// [`crate::Entity::destroy`], nest inside the active savepoint.
+#[must_use = "dropping a Savepoint rolls it back"]
+pub struct Savepoint<'a> {
+    ecs: &'a Ecs,
+    name: String,
+    finished: bool,
+}
+
+#[with_infallible]
+impl Ecs {
+    #[tracing::instrument(name = "savepoint", level = "debug", skip(self))]
+    pub fn try_savepoint(&self, name: &str) -> Result<Savepoint<'_>, Error> {
+        let name = quote_identifier(name);
+        self.conn.execute_batch(&format!("savepoint {name}"))?;
+        debug!(name, "savepoint created");
+
+        Ok(Savepoint {
+            ecs: self,
+            name,
+            finished: false,
+        })
+    }
+}
+
+#[with_infallible]
+impl<'a> Savepoint<'a> {
+    /// Keeps the changes made since the savepoint was created
+    pub fn try_release(self) -> Result<(), Error> {
+        let mut this = self;
+        this.ecs
+            .conn
+            .execute_batch(&format!("release {}", this.name))?;
+        this.finished = true;
+        debug!(name = this.name, "savepoint released");
+        Ok(())
+    }
+
+    /// Discards the changes made since the savepoint was created. Same as
+    /// dropping the guard, but reports errors.
+    pub fn try_rollback(self) -> Result<(), Error> {
+        let mut this = self;
+        this.rollback_and_release()?;
+        this.finished = true;
+        Ok(())
+    }
+}
+
+impl<'a> Savepoint<'a> {
+    fn rollback_and_release(&self) -> Result<(), Error> {
+        self.ecs.conn.execute_batch(&format!(
+            "rollback to {name}; release {name}",
+            name = self.name
+        ))?;
+        debug!(name = self.name, "savepoint rolled back");
+        Ok(())
+    }
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        if let Err(e) = self.rollback_and_release() {
+            warn!(name = self.name, error = %e, "Failed to roll back savepoint");
+        }
+    }
+}
+
+/// Transaction for operations spanning multiple statements. Begins an
+/// immediate transaction, or a savepoint if a transaction or [`Savepoint`] is
+/// already active, so that these operations nest. Rolled back on drop unless
+/// committed.
+pub(crate) struct Transaction<'a> {
+    conn: &'a rusqlite::Connection,
+    nested: bool,
+    finished: bool,
+}
+
+impl Ecs {
+    pub(crate) fn transaction(&self) -> Result<Transaction<'_>, Error> {
+        let nested = !self.conn.is_autocommit();
+        self.conn.execute_batch(if nested {
+            "savepoint ecsdb_transaction"
+        } else {
+            "begin immediate"
+        })?;
+
+        Ok(Transaction {
+            conn: &self.conn,
+            nested,
+            finished: false,
+        })
+    }
+}
+
+impl Transaction<'_> {
+    pub(crate) fn commit(mut self) -> Result<(), Error> {
+        self.conn.execute_batch(if self.nested {
+            "release ecsdb_transaction"
+        } else {
+            "commit"
+        })?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for Transaction<'_> {
+    type Target = rusqlite::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let rollback = if self.nested {
+            "rollback to ecsdb_transaction; release ecsdb_transaction"
+        } else {
+            "rollback"
+        };
+        if let Err(e) = self.conn.execute_batch(rollback) {
+            warn!(error = %e, "Failed to roll back transaction");
+        }
+    }
+}
+
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{self as ecsdb, Component, Ecs};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct B;
+
+    #[test]
+    fn nested_savepoints() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(A);
+
+        let txn = db.savepoint("txn");
+        let a = db.new_entity().attach(A);
+
+        let speculative = db.savepoint("speculative \"edit\"");
+        e.attach(B);
+        let b = db.new_entity().attach(B);
+        drop(speculative);
+
+        txn.release();
+
+        assert!(a.exists());
+        assert!(!e.has::<B>());
+        assert!(!b.exists());
+
+        let txn = db.savepoint("txn");
+        e.attach(B);
+        txn.rollback();
+        assert!(!e.has::<B>());
+    }
+
+    #[test]
+    fn transactions_nest_in_savepoints() {
+        let db = Ecs::open_in_memory().unwrap();
+        let a = db.new_entity().attach(A);
+        let b = db.new_entity().attach((A, B));
+
+        let txn = db.savepoint("txn");
+        a.destroy();
+        assert!(!a.exists());
+        drop(txn);
+        assert!(a.exists());
+
+        let txn = db.savepoint("txn");
+        b.destroy();
+        db.spawn_batch([A, A]);
+        txn.release();
+        assert!(!b.exists());
+        assert_eq!(db.query::<crate::EntityId, A>().count(), 3);
+        assert!(db.raw_sql().is_autocommit());
+    }
+}
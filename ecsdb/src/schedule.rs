@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use crate::{BoxedSystem, Ecs, IntoSystem, LastRun, System, system};
+use crate::{BoxedSystem, Ecs, IntoSystem, LastRun, ParallelSystem, System, system};
 
 use tracing::{debug, debug_span, instrument, warn};
 
@@ -13,6 +13,7 @@ enum SystemStatus {
 #[derive(Default)]
 pub struct Schedule {
     systems: Vec<(BoxedSystem, Box<dyn SchedulingMode>, SystemStatus)>,
+    parallel: Vec<(BoxedSystem, Box<dyn SchedulingMode>)>,
 }
 
 impl Schedule {
@@ -34,6 +35,21 @@ impl Schedule {
         self
     }
 
+    /// Adds `system` to the parallel stage. Parallel systems scheduled by
+    /// `mode` run on [`Schedule::tick`] after the sequential ones, each on its
+    /// own thread with a read-only connection to the database. Databases
+    /// without a file (in-memory) run them sequentially instead.
+    pub fn add_parallel<Marker, S, M>(&mut self, system: S, mode: M) -> &mut Self
+    where
+        S: IntoSystem<Marker>,
+        S::System: ParallelSystem + 'static,
+        M: SchedulingMode,
+    {
+        self.parallel
+            .push((system.into_boxed_system(), Box::new(mode)));
+        self
+    }
+
     pub fn remove<Marker, S>(&mut self, system: S) -> &mut Self
     where
         S: IntoSystem<Marker>,
@@ -96,9 +112,138 @@ impl Schedule {
             results.push((system.name(), result));
         }
 
+        results.extend(self.tick_parallel(ecs));
+
         Ok(results)
     }
 
+    fn tick_parallel<'a>(&'a self, ecs: &Ecs) -> Vec<(Cow<'a, str>, TickResult)> {
+        if self.parallel.is_empty() {
+            return vec![];
+        }
+
+        // Scheduling and parameters are checked on the main connection, only
+        // systems that can run get a worker
+        let scheduled = self
+            .parallel
+            .iter()
+            .map(|(system, mode)| {
+                let _span = debug_span!("system", name = %system.name()).entered();
+                ecs.get_or_create_system_entity(&system.name());
+
+                if !mode.should_run(ecs, &system.name()) {
+                    debug!("skipping");
+                    false
+                } else if !system.can_run(ecs) {
+                    warn!("Skipping, parameters unavailable");
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let systems = self
+            .parallel
+            .iter()
+            .zip(&scheduled)
+            .filter(|(_, scheduled)| **scheduled)
+            .map(|((system, _), _)| system)
+            .collect::<Vec<_>>();
+
+        // Whether `system` ran. The worker's snapshot may lack parameters the
+        // main connection had, so they're checked again.
+        let run = |system: &BoxedSystem, conn: &Ecs| -> Result<bool, anyhow::Error> {
+            if !system.can_run(conn) {
+                warn!(system = %system.name(), "Skipping, parameters unavailable");
+                return Ok(false);
+            }
+            system.run_system(conn).map(|()| true)
+        };
+
+        // Open every connection before running any system
+        let workers = match systems
+            .iter()
+            .map(|_| ecs.open_read_only())
+            .collect::<Result<Option<Vec<_>>, _>>()
+        {
+            Ok(workers) => workers,
+            Err(e) => {
+                warn!(error = %e, "Failed to open read-only connections");
+                return self
+                    .parallel
+                    .iter()
+                    .zip(&scheduled)
+                    .map(|((system, _), scheduled)| {
+                        let result = if *scheduled {
+                            TickResult::Error(anyhow::anyhow!("{e}"))
+                        } else {
+                            TickResult::NotScheduled
+                        };
+                        (system.name(), result)
+                    })
+                    .collect();
+            }
+        };
+
+        let started_at = chrono::Utc::now();
+        let outcomes: Vec<Result<bool, anyhow::Error>> = match workers {
+            None => {
+                warn!("Database has no file, running parallel systems sequentially");
+                systems.iter().map(|system| run(system, ecs)).collect()
+            }
+            Some(workers) => std::thread::scope(|scope| {
+                let handles = systems
+                    .iter()
+                    .zip(workers)
+                    .map(|(system, worker)| {
+                        scope.spawn(move || -> Result<bool, anyhow::Error> {
+                            let _span = debug_span!("system", name = %system.name()).entered();
+                            run(system, &worker)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err(anyhow::anyhow!("System panicked")))
+                    })
+                    .collect()
+            }),
+        };
+
+        let mut outcomes = outcomes.into_iter();
+        self.parallel
+            .iter()
+            .zip(scheduled)
+            .map(|((system, _), scheduled)| {
+                let outcome = if scheduled {
+                    outcomes.next().expect("one outcome per scheduled system")
+                } else {
+                    Ok(false)
+                };
+
+                let result = match outcome {
+                    Ok(true) => {
+                        ecs.get_or_create_system_entity(&system.name())
+                            .attach(LastRun(started_at));
+                        TickResult::Ok
+                    }
+                    Ok(false) => TickResult::NotScheduled,
+                    Err(e) => {
+                        warn!(system = %system.name(), error = %e, "System failed");
+                        TickResult::Error(e)
+                    }
+                };
+
+                (system.name(), result)
+            })
+            .collect()
+    }
+
     /// Run a single system by name, bypassing its scheduling mode.
     ///
     /// Returns [`UnknownSystemError`] if no system with the given name exists
@@ -332,4 +477,89 @@ mod test {
         // Third system should be skipped due to Manually scheduling
         assert!(matches!(results[2].1, TickResult::NotScheduled));
     }
+
+    #[test]
+    fn parallel_systems() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::query::{Query, With};
+
+        #[derive(Serialize, Deserialize, Component)]
+        struct Item;
+
+        static COUNTS: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+
+        macro_rules! defsys {
+            ($sys:ident, $n:expr) => {
+                fn $sys(query: Query<crate::EntityId, With<Item>>) {
+                    COUNTS[$n].fetch_add(query.iter().count(), Ordering::SeqCst);
+                }
+            };
+        }
+
+        defsys!(count_a, 0);
+        defsys!(count_b, 1);
+        defsys!(count_c, 2);
+
+        let path = crate::test_util::TempDb::new("parallel");
+        let ecs = Ecs::open(&path).unwrap();
+        for _ in 0..5 {
+            ecs.new_entity().attach(Item);
+        }
+
+        let mut schedule = Schedule::new();
+        schedule.add_parallel(count_a, Always);
+        schedule.add_parallel(count_b, Always);
+        schedule.add_parallel(count_c, Once);
+
+        let results = schedule.tick(&ecs).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|(_, r)| matches!(r, TickResult::Ok)));
+
+        for count in &COUNTS {
+            assert_eq!(count.load(Ordering::SeqCst), 5);
+        }
+
+        assert!(
+            ecs.system_entity(&system_name(count_a))
+                .unwrap()
+                .component::<LastRun>()
+                .is_some()
+        );
+
+        // `count_c` is scheduled `Once`
+        let results = schedule.tick(&ecs).unwrap();
+        assert!(matches!(results[2].1, TickResult::NotScheduled));
+        assert_eq!(COUNTS[0].load(Ordering::SeqCst), 10);
+        assert_eq!(COUNTS[2].load(Ordering::SeqCst), 5);
+
+        ecs.close().unwrap();
+    }
+
+    #[test]
+    fn parallel_systems_without_params() {
+        use crate::Res;
+
+        #[derive(Serialize, Deserialize, Component)]
+        struct Config;
+
+        fn needs_config(_config: Res<Config>) {}
+
+        let path = crate::test_util::TempDb::new("parallel_without_params");
+        let ecs = Ecs::open(&path).unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.add_parallel(needs_config, Always);
+
+        let results = schedule.tick(&ecs).unwrap();
+        assert!(matches!(results[0].1, TickResult::NotScheduled));
+        assert!(
+            ecs.system_entity(&system_name(needs_config))
+                .unwrap()
+                .component::<LastRun>()
+                .is_none()
+        );
+
+        ecs.close().unwrap();
+    }
 }
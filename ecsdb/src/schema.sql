@@ -9,6 +9,94 @@ create unique index if not exists components_entity_component_unqiue_idx on comp
 
 create index if not exists components_component_idx on components (component);
 
+-- Entity ids are allocated here. `autoincrement` guarantees ids are never reused.
+create table if not exists entities (id integer primary key autoincrement);
+
+-- Database-wide key/value metadata, e.g. the database id (`db_id`)
+create table if not exists meta (key text primary key, value) without rowid;
+
+-- Every entity with components is registered in `entities`, which is the source of
+-- truth for existence. Entities stay registered until destroyed.
+create trigger if not exists components_entities_insert_trigger
+after insert on components
+for each row
+begin
+    insert or ignore into entities (id) values (new.entity);
+end;
+
+-- Per-entity write counter for detecting concurrent edits, see `Entity::version`.
+-- Bumped on every inserted, changed or deleted component or `MultiComponent` value.
+-- Rows outlive their entity, so that the version never goes back.
+create table if not exists entity_versions (
+    entity integer primary key,
+    version integer not null
+);
+
+create trigger if not exists components_entity_versions_insert_trigger
+after insert on components
+for each row
+begin
+    insert into entity_versions (entity, version) values (new.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
+create trigger if not exists components_entity_versions_update_trigger
+after update on components
+for each row
+begin
+    insert into entity_versions (entity, version) values (new.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
+create trigger if not exists components_entity_versions_delete_trigger
+after delete on components
+for each row
+begin
+    insert into entity_versions (entity, version) values (old.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
+-- Values of `MultiComponent`s. `components` holds a presence row per entity and
+-- component, so that `With<C>` works as usual.
+create table if not exists multi_components (
+    entity integer not null,
+    component text not null,
+    data blob
+);
+
+create index if not exists multi_components_entity_component_idx on multi_components (entity, component);
+
+create trigger if not exists components_multi_components_delete_trigger
+after delete on components
+for each row
+begin
+    delete from multi_components where entity = old.entity and component = old.component;
+end;
+
+create trigger if not exists multi_components_entity_versions_insert_trigger
+after insert on multi_components
+for each row
+begin
+    insert into entity_versions (entity, version) values (new.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
+create trigger if not exists multi_components_entity_versions_update_trigger
+after update on multi_components
+for each row
+begin
+    insert into entity_versions (entity, version) values (new.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
+create trigger if not exists multi_components_entity_versions_delete_trigger
+after delete on multi_components
+for each row
+begin
+    insert into entity_versions (entity, version) values (old.entity, 1)
+    on conflict (entity) do update set version = version + 1;
+end;
+
 create view if not exists entity_components (entity, components) as
 select
     entity,
@@ -136,3 +224,10 @@ having
     count(*) filter (
         where component not in (select component from system_components)
     ) = 0;
+
+create table if not exists component_aliases (
+    alias text primary key,
+    component text not null
+) without rowid;
+
+create unique index if not exists component_aliases_component_idx on component_aliases (component);
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use ecsdb_derive::with_infallible;
+use rusqlite::{params, types::ToSqlOutput};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{DynComponent, Ecs, Entity, EntityId, Error};
+
+/// The full state of an entity: every non-system component as JSON.
+/// Components stored as blobs aren't representable and are skipped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub id: EntityId,
+    pub components: BTreeMap<String, serde_json::Value>,
+    /// Values of [`MultiComponent`](crate::MultiComponent)s, in the order
+    /// they were attached
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub multi_components: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    pub fn try_snapshot(self) -> Result<EntitySnapshot, Error> {
+        // Skips the presence rows of `MultiComponent`s, their values are read
+        // from `multi_components` below
+        let mut stmt = self.0.conn.prepare_cached(
+            r#"
+            select component, data from components
+            where entity = ?1 and component not in (select component from system_components)
+            and (data is not null or not exists (
+                select 1 from multi_components m
+                where m.entity = components.entity and m.component = components.component
+            ))
+            "#,
+        )?;
+
+        let mut components = BTreeMap::new();
+        let mut rows = stmt.query([self.id()])?;
+        while let Some(row) = rows.next()? {
+            let component: String = row.get(0)?;
+            let data = DynComponent(&component, ToSqlOutput::Owned(row.get(1)?));
+            match data.as_json() {
+                Some(value) => {
+                    components.insert(component, value);
+                }
+                None => warn!(entity = self.id(), %component, "Skipping non-JSON component"),
+            }
+        }
+
+        let mut stmt = self.0.conn.prepare_cached(
+            "select component, data from multi_components where entity = ?1 order by rowid",
+        )?;
+
+        let mut multi_components = BTreeMap::<_, Vec<_>>::new();
+        let mut rows = stmt.query([self.id()])?;
+        while let Some(row) = rows.next()? {
+            let component: String = row.get(0)?;
+            let data = DynComponent(&component, ToSqlOutput::Owned(row.get(1)?));
+            match data.as_json() {
+                Some(value) => multi_components.entry(component).or_default().push(value),
+                None => warn!(entity = self.id(), %component, "Skipping non-JSON component"),
+            }
+        }
+
+        Ok(EntitySnapshot {
+            id: self.id(),
+            components,
+            multi_components,
+        })
+    }
+}
+
+#[with_infallible]
+impl Ecs {
+    /// Spawns a new entity with the components of `snapshot`. The snapshot's
+    /// `id` isn't reused. Fails with [`Error::EmptyBundle`] if the snapshot
+    /// has no components.
+    pub fn try_spawn_snapshot<'a>(
+        &'a self,
+        snapshot: &EntitySnapshot,
+    ) -> Result<Entity<'a>, Error> {
+        if snapshot.components.is_empty() && snapshot.multi_components.is_empty() {
+            return Err(Error::EmptyBundle);
+        }
+
+        let tx = self.transaction()?;
+
+        let eid = self.allocate_entity_id()?;
+
+        {
+            let mut stmt =
+                tx.prepare("insert into components (entity, component, data) values (?1, ?2, ?3)")?;
+            for (component, value) in &snapshot.components {
+                let data = match value {
+                    serde_json::Value::Null => rusqlite::types::Value::Null,
+                    other => rusqlite::types::Value::Text(other.to_string()),
+                };
+                stmt.execute(params![eid, component, data])?;
+            }
+
+            let mut presence = tx.prepare(
+                "insert into components (entity, component, data) values (?1, ?2, null) on conflict do nothing",
+            )?;
+            let mut stmt = tx.prepare(
+                "insert into multi_components (entity, component, data) values (?1, ?2, ?3)",
+            )?;
+            for (component, values) in &snapshot.multi_components {
+                presence.execute(params![eid, component])?;
+                for value in values {
+                    let data = match value {
+                        serde_json::Value::Null => rusqlite::types::Value::Null,
+                        other => rusqlite::types::Value::Text(other.to_string()),
+                    };
+                    stmt.execute(params![eid, component, data])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+
+        debug!(entity = eid, snapshot = snapshot.id, "spawned snapshot");
+
+        Ok(self.entity(eid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::EntitySnapshot;
+    use crate::query::With;
+    use crate::{self as ecsdb, Component, Ecs, EntityId, MultiComponent};
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Marker;
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Component, PartialEq)]
+    struct Tag(String);
+
+    impl MultiComponent for Tag {}
+
+    #[test]
+    fn snapshot_round_trip() {
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db
+            .new_entity()
+            .attach(Marker)
+            .attach(Position { x: 1, y: 2 });
+
+        let snapshot = entity.snapshot();
+        assert_eq!(snapshot.id, entity.id());
+        assert_eq!(snapshot.components.len(), 2);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: EntitySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let spawned = db.spawn_snapshot(&restored);
+        assert_ne!(spawned.id(), entity.id());
+        assert_eq!(spawned.component::<Marker>(), Some(Marker));
+        assert_eq!(
+            spawned.component::<Position>(),
+            Some(Position { x: 1, y: 2 })
+        );
+        assert_eq!(spawned.snapshot().components, snapshot.components);
+    }
+
+    #[test]
+    fn snapshot_multi_components() {
+        let db = Ecs::open_in_memory().unwrap();
+        let entity = db
+            .new_entity()
+            .attach(Marker)
+            .attach_multi(Tag("one".into()))
+            .attach_multi(Tag("two".into()));
+
+        let snapshot = entity.snapshot();
+        assert_eq!(snapshot.components.len(), 1);
+        assert_eq!(
+            snapshot.multi_components[Tag::NAME],
+            [serde_json::json!("one"), serde_json::json!("two")]
+        );
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: EntitySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let spawned = db.spawn_snapshot(&restored);
+        assert_eq!(
+            spawned.components_of::<Tag>(),
+            vec![Tag("one".into()), Tag("two".into())]
+        );
+        assert_eq!(
+            db.query::<EntityId, With<Tag>>().collect::<Vec<_>>(),
+            vec![entity.id(), spawned.id()]
+        );
+
+        // Snapshots without multi components keep their format
+        let plain = db.new_entity().attach(Marker).snapshot();
+        assert!(
+            !serde_json::to_string(&plain)
+                .unwrap()
+                .contains("multi_components")
+        );
+    }
+
+    #[test]
+    fn spawn_empty_snapshot() {
+        let db = Ecs::open_in_memory().unwrap();
+        let empty = db.new_entity().attach(Marker).detach::<Marker>().snapshot();
+        assert!(empty.components.is_empty());
+
+        assert!(matches!(
+            db.try_spawn_snapshot(&empty),
+            Err(crate::Error::EmptyBundle)
+        ));
+        assert_eq!(db.all_entities().count(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+use ecsdb_derive::with_infallible;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{self as ecsdb, Component, Ecs, Entity, Error, query};
+
+/// Tombstone attached by [`Entity::soft_destroy`]. Entities carrying it are
+/// hidden from queries unless [`query::Query::include_deleted`] is used.
+#[derive(Serialize, Deserialize, Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[component(name = "ecsdb::Deleted")]
+pub struct Deleted(pub chrono::DateTime<chrono::Utc>);
+
+#[with_infallible]
+impl<'a> Entity<'a> {
+    #[tracing::instrument(name = "soft_destroy", level = "debug")]
+    pub fn try_soft_destroy(self) -> Result<Self, Error> {
+        self.try_attach(Deleted(chrono::Utc::now()))
+    }
+
+    #[tracing::instrument(name = "restore", level = "debug")]
+    pub fn try_restore(self) -> Result<Self, Error> {
+        self.try_detach::<Deleted>()
+    }
+
+    pub fn try_is_deleted(self) -> Result<bool, Error> {
+        self.try_has::<Deleted>()
+    }
+}
+
+#[with_infallible]
+impl Ecs {
+    /// Destroys all entities soft-deleted before `before`. Returns the
+    /// number of destroyed entities.
+    #[tracing::instrument(name = "purge_deleted", level = "debug", skip(self))]
+    pub fn try_purge_deleted(&self, before: chrono::DateTime<chrono::Utc>) -> Result<usize, Error> {
+        let expired = query::Query::<(Entity, Deleted)>::new(self)
+            .include_deleted()
+            .try_iter()?
+            .filter(|(_, Deleted(deleted_at))| *deleted_at < before)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+
+        for entity in &expired {
+            entity.try_destroy()?;
+        }
+
+        debug!(purged = expired.len());
+        Ok(expired.len())
+    }
+}
+
+impl Ecs {
+    /// Whether any entity is soft-deleted. Queries only filter out [`Deleted`]
+    /// entities if there are some. Assumes there are if the check fails.
+    pub(crate) fn any_soft_deleted(&self) -> bool {
+        self.conn
+            .prepare_cached("select exists (select 1 from components where component = ?1)")
+            .and_then(|mut stmt| {
+                stmt.query_row([self.stored_component_name(Deleted::NAME)], |row| {
+                    row.get(0)
+                })
+            })
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Deleted;
+    use crate::query::Query;
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct A;
+
+    #[test]
+    fn soft_destroy() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let a = ecs.new_entity().attach(A);
+        let b = ecs.new_entity().attach(A);
+
+        b.soft_destroy();
+        assert!(b.exists());
+        assert!(b.is_deleted());
+
+        assert_eq!(ecs.query::<EntityId, A>().collect::<Vec<_>>(), vec![a.id()]);
+        assert_eq!(
+            Query::<EntityId, A>::new(&ecs)
+                .include_deleted()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![a.id(), b.id()]
+        );
+
+        b.restore();
+        assert_eq!(
+            ecs.query::<EntityId, A>().collect::<Vec<_>>(),
+            vec![a.id(), b.id()]
+        );
+    }
+
+    #[test]
+    fn deleted_filter_only_when_needed() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let a = ecs.new_entity().attach(A);
+        let b = ecs.new_entity().attach(A);
+
+        let filters_deleted = || {
+            let (_, placeholders) = Query::<EntityId, A>::new(&ecs).to_sql();
+            placeholders.iter().any(|p| p.contains(Deleted::NAME))
+        };
+
+        assert!(!filters_deleted());
+
+        b.soft_destroy();
+        assert!(filters_deleted());
+        assert_eq!(ecs.query::<EntityId, A>().collect::<Vec<_>>(), vec![a.id()]);
+
+        b.restore();
+        assert!(!filters_deleted());
+    }
+
+    #[test]
+    fn purge_deleted() {
+        let ecs = Ecs::open_in_memory().unwrap();
+        let a = ecs.new_entity().attach(A);
+        let b = ecs.new_entity().attach(A).soft_destroy();
+
+        assert_eq!(
+            ecs.purge_deleted(chrono::Utc::now() - chrono::Duration::hours(1)),
+            0
+        );
+        assert!(b.exists());
+
+        assert_eq!(ecs.purge_deleted(chrono::Utc::now()), 1);
+        assert!(!b.exists());
+        assert!(a.exists());
+    }
+}
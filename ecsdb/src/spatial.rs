@@ -0,0 +1,333 @@
+use std::marker::PhantomData;
+
+use ecsdb_derive::with_infallible;
+use tracing::debug;
+
+use crate::{
+    Component, Ecs, Entity, EntityId, Error, Extension, extension::ComponentIndex,
+    savepoint::quote_identifier,
+};
+
+/// Spatial index over a 2D position component, backed by an SQLite R*Tree.
+///
+/// The component has to be stored as a JSON object with numeric `x` and `y`
+/// fields, e.g. `struct Position { x: f64, y: f64 }`. Like [`crate::fts::Fts`],
+/// the index and the triggers keeping it in sync persist in the database.
+///
+/// The R*Tree only stores `f32` bounds, so it's used to narrow down the
+/// candidates, which are then checked against the exact component data.
+pub struct SpatialIndex<C> {
+    component: PhantomData<fn() -> C>,
+}
+
+impl<C: Component> SpatialIndex<C> {
+    pub fn new() -> Self {
+        Self {
+            component: PhantomData,
+        }
+    }
+
+    fn table() -> String {
+        format!("rtree_{}", C::NAME)
+    }
+
+    /// Ids and exact positions of the entities whose R*Tree entry overlaps
+    /// the rectangle. Entries are rounded outwards, so this can include
+    /// entities slightly outside of it.
+    fn overlapping(
+        &self,
+        ecs: &Ecs,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    ) -> Result<Vec<(EntityId, f64, f64)>, Error> {
+        let mut stmt = ecs.conn.prepare_cached(&format!(
+            r#"
+            select r.id, json_extract(c.data, '$.x'), json_extract(c.data, '$.y')
+            from {} r
+            join components c on c.entity = r.id and c.component = ?5
+            where r.max_x >= ?1 and r.min_x <= ?3 and r.max_y >= ?2 and r.min_y <= ?4
+            order by r.id
+            "#,
+            quote_identifier(&Self::table())
+        ))?;
+
+        let rows = stmt
+            .query_map(
+                rusqlite::params![
+                    min_x,
+                    min_y,
+                    max_x,
+                    max_y,
+                    ecs.stored_component_name(C::NAME)
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    }
+
+    fn nearest_ids(&self, ecs: &Ecs, x: f64, y: f64, k: usize) -> Result<Vec<EntityId>, Error> {
+        let (count, bounds) = ecs.conn.query_row(
+            &format!(
+                "select count(*), min(min_x), min(min_y), max(max_x), max(max_y) from {}",
+                quote_identifier(&Self::table())
+            ),
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, usize>(0)?,
+                    (
+                        row.get::<_, Option<f64>>(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ),
+                ))
+            },
+        )?;
+
+        let (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) = bounds else {
+            return Ok(Vec::new());
+        };
+
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Search a square around (x, y), starting at the size expected to
+        // hold `k` entities, and double it until the `k` nearest are known to
+        // be inside: everything outside of the square is further away than
+        // its inscribed circle.
+        let distance_to_bounds = (min_x - x)
+            .max(x - max_x)
+            .max(0.0)
+            .hypot((min_y - y).max(y - max_y).max(0.0));
+        let span = (max_x - min_x).max(max_y - min_y);
+        let mut radius = distance_to_bounds.max(span * (k as f64 / count as f64).sqrt() / 2.0);
+
+        loop {
+            let covers_all = x - radius <= min_x
+                && x + radius >= max_x
+                && y - radius <= min_y
+                && y + radius >= max_y;
+
+            let mut found = self
+                .overlapping(ecs, x - radius, y - radius, x + radius, y + radius)?
+                .into_iter()
+                .map(|(id, px, py)| ((px - x).hypot(py - y), id))
+                .filter(|&(distance, _)| covers_all || distance <= radius)
+                .collect::<Vec<_>>();
+
+            if covers_all || found.len() >= k {
+                found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                return Ok(found.into_iter().take(k).map(|(_, id)| id).collect());
+            }
+
+            radius *= 2.0;
+        }
+    }
+}
+
+impl<C: Component> Default for SpatialIndex<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Clone for SpatialIndex<C> {
+    fn clone(&self) -> Self {
+        Self {
+            component: PhantomData,
+        }
+    }
+}
+
+impl<C: Component> std::fmt::Debug for SpatialIndex<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpatialIndex")
+            .field("component", &C::NAME)
+            .finish()
+    }
+}
+
+impl<C: Component> Extension for SpatialIndex<C> {
+    fn init(&mut self, ecs: &Ecs) -> Result<(), Error> {
+        let indexed = ComponentIndex {
+            table: Self::table(),
+            component: C::NAME,
+            module: "rtree(id, min_x, max_x, min_y, max_y)",
+            key: "id",
+            columns: "min_x, max_x, min_y, max_y",
+            values: "json_extract({data}, '$.x'), json_extract({data}, '$.x'), \
+                     json_extract({data}, '$.y'), json_extract({data}, '$.y')",
+        }
+        .create(ecs)?;
+
+        debug!(component = C::NAME, indexed, "created spatial index");
+        Ok(())
+    }
+}
+
+#[with_infallible]
+impl<C: Component> SpatialIndex<C> {
+    /// Entities positioned inside the rectangle, bounds included, in
+    /// ascending entity id order
+    pub fn try_within_bounds<'a>(
+        &self,
+        ecs: &'a Ecs,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        let ids = self
+            .overlapping(ecs, min_x, min_y, max_x, max_y)?
+            .into_iter()
+            .filter(|&(_, x, y)| (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y))
+            .map(|(id, _, _)| id)
+            .collect::<Vec<_>>();
+
+        Ok(ids.into_iter().map(|id| ecs.entity(id)))
+    }
+
+    /// The `k` entities closest to `(x, y)`, nearest first. Ties are broken
+    /// by entity id.
+    pub fn try_nearest<'a>(
+        &self,
+        ecs: &'a Ecs,
+        x: f64,
+        y: f64,
+        k: usize,
+    ) -> Result<impl Iterator<Item = Entity<'a>> + 'a, Error> {
+        let ids = self.nearest_ids(ecs, x, y, k)?;
+        Ok(ids.into_iter().map(|id| ecs.entity(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::SpatialIndex;
+    use crate::{self as ecsdb, Component, Ecs, EntityId};
+
+    #[derive(Debug, Serialize, Deserialize, Component)]
+    struct Position {
+        x: f64,
+        y: f64,
+    }
+
+    #[test]
+    fn spatial_queries() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        let origin = ecs.new_entity().attach(Position { x: 0.0, y: 0.0 }).id();
+
+        ecs.register_extension(SpatialIndex::<Position>::new())
+            .unwrap();
+
+        let near = ecs.new_entity().attach(Position { x: 1.0, y: 2.0 });
+        let edge = ecs.new_entity().attach(Position { x: 5.0, y: 5.0 });
+        let far = ecs.new_entity().attach(Position { x: 10.0, y: -3.0 });
+
+        let index = ecs.extension::<SpatialIndex<Position>>();
+        let within = |min_x, min_y, max_x, max_y| {
+            index
+                .within_bounds(&ecs, min_x, min_y, max_x, max_y)
+                .map(|e| e.id())
+                .collect::<Vec<EntityId>>()
+        };
+
+        assert_eq!(within(0.0, 0.0, 5.0, 5.0), [origin, near.id(), edge.id()]);
+        assert_eq!(within(0.5, 0.5, 4.0, 4.0), [near.id()]);
+        assert!(within(20.0, 20.0, 30.0, 30.0).is_empty());
+
+        let nearest = |x, y, k| {
+            index
+                .nearest(&ecs, x, y, k)
+                .map(|e| e.id())
+                .collect::<Vec<EntityId>>()
+        };
+        assert_eq!(nearest(9.0, -2.0, 2), [far.id(), edge.id()]);
+
+        far.attach(Position { x: 2.0, y: 2.0 });
+        near.detach::<Position>();
+        assert_eq!(within(0.5, 0.5, 4.0, 4.0), [far.id()]);
+    }
+
+    #[test]
+    fn bounds_are_exact() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.register_extension(SpatialIndex::<Position>::new())
+            .unwrap();
+
+        // Neither is representable as f32, the R*Tree rounds both outwards
+        let on_edge = ecs.new_entity().attach(Position { x: 0.1, y: 0.1 }).id();
+        let outside = ecs
+            .new_entity()
+            .attach(Position {
+                x: 0.1 + 1e-12,
+                y: 0.1,
+            })
+            .id();
+
+        let index = ecs.extension::<SpatialIndex<Position>>();
+        let within = |min_x, min_y, max_x, max_y| {
+            index
+                .within_bounds(&ecs, min_x, min_y, max_x, max_y)
+                .map(|e| e.id())
+                .collect::<Vec<EntityId>>()
+        };
+
+        assert_eq!(within(0.0, 0.0, 0.1, 0.1), [on_edge]);
+        assert_eq!(within(0.1, 0.1, 1.0, 1.0), [on_edge, outside]);
+        assert_eq!(within(0.1 + 1e-12, 0.0, 1.0, 1.0), [outside]);
+    }
+
+    #[test]
+    fn nearest_matches_full_scan() {
+        let mut ecs = Ecs::open_in_memory().unwrap();
+        ecs.register_extension(SpatialIndex::<Position>::new())
+            .unwrap();
+
+        let positions = (0..100)
+            .map(|i| Position {
+                x: (i * 37 % 101) as f64 * 0.7,
+                y: (i * 53 % 97) as f64 * -1.3,
+            })
+            .map(|p| {
+                let xy = (p.x, p.y);
+                (ecs.new_entity().attach(p).id(), xy)
+            })
+            .collect::<Vec<_>>();
+
+        let index = ecs.extension::<SpatialIndex<Position>>();
+        for (x, y, k) in [
+            (0.0, 0.0, 5),
+            (35.0, -60.0, 10),
+            (1000.0, 1000.0, 3),
+            (0.0, 0.0, 200),
+        ] {
+            let mut expected = positions
+                .iter()
+                .map(|&(id, (px, py))| ((px - x).hypot(py - y), id))
+                .collect::<Vec<_>>();
+            expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let expected = expected
+                .into_iter()
+                .take(k)
+                .map(|(_, id)| id)
+                .collect::<Vec<_>>();
+
+            let found = index
+                .nearest(&ecs, x, y, k)
+                .map(|e| e.id())
+                .collect::<Vec<EntityId>>();
+            assert_eq!(found, expected, "nearest({x}, {y}, {k})");
+        }
+
+        assert_eq!(index.nearest(&ecs, 0.0, 0.0, 0).count(), 0);
+    }
+}
@@ -7,41 +7,80 @@ pub(crate) fn add_regexp_function(db: &Connection) -> Result<()> {
         "velodb_extract_data",
         1,
         FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
-        move |ctx| {
+        |ctx| {
             assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+            extract_data(ctx.get_raw(0), false)
+        },
+    )?;
 
-            match ctx.get_raw(0) {
-                ValueRef::Null => Ok(Value::Null),
-                ValueRef::Integer(i) => Ok(Value::Integer(i)),
-                ValueRef::Real(r) => Ok(Value::Real(r)),
-                // Return NULL for BLOB - no JSON extraction possible
-                ValueRef::Blob(_blob) => Ok(Value::Null),
-                // JSON
-                ValueRef::Text(text) => {
-                    let value: serde_json::Value = serde_json::from_slice(text)
-                        .map_err(|e| Error::UserFunctionError(Box::new(e)))?;
-
-                    let sqlite_value = match value {
-                        serde_json::Value::Null => Value::Null,
-                        serde_json::Value::Bool(true) => Value::Integer(1),
-                        serde_json::Value::Bool(false) => Value::Integer(0),
-                        serde_json::Value::Number(n) => n
-                            .as_i64()
-                            .map(Value::Integer)
-                            .or(n.as_f64().map(Value::Real))
-                            .unwrap(),
-                        serde_json::Value::String(s) => Value::Text(s),
-                        array @ serde_json::Value::Array(_) => Value::Text(array.to_string()),
-                        obj @ serde_json::Value::Object(_) => Value::Text(obj.to_string()),
-                    };
-
-                    Ok(sqlite_value)
-                }
-            }
+    // Like `velodb_extract_data`, but with timestamps normalized by
+    // `canonical_datetime`. Only meant for range comparisons, other filters
+    // (e.g. `like`) see the strings as stored.
+    db.create_scalar_function(
+        "velodb_extract_sortable_data",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+            extract_data(ctx.get_raw(0), true)
         },
     )
 }
 
+fn extract_data(value: ValueRef, canonical_datetimes: bool) -> Result<Value> {
+    match value {
+        ValueRef::Null => Ok(Value::Null),
+        ValueRef::Integer(i) => Ok(Value::Integer(i)),
+        ValueRef::Real(r) => Ok(Value::Real(r)),
+        // Return NULL for BLOB - no JSON extraction possible
+        ValueRef::Blob(_blob) => Ok(Value::Null),
+        // JSON
+        ValueRef::Text(text) => {
+            let value: serde_json::Value =
+                serde_json::from_slice(text).map_err(|e| Error::UserFunctionError(Box::new(e)))?;
+
+            let sqlite_value = match value {
+                serde_json::Value::Null => Value::Null,
+                serde_json::Value::Bool(true) => Value::Integer(1),
+                serde_json::Value::Bool(false) => Value::Integer(0),
+                serde_json::Value::Number(n) => n
+                    .as_i64()
+                    .map(Value::Integer)
+                    .or(n.as_f64().map(Value::Real))
+                    .unwrap(),
+                serde_json::Value::String(s) if canonical_datetimes => {
+                    Value::Text(canonical_datetime(&s).unwrap_or(s))
+                }
+                serde_json::Value::String(s) => Value::Text(s),
+                array @ serde_json::Value::Array(_) => Value::Text(array.to_string()),
+                obj @ serde_json::Value::Object(_) => Value::Text(obj.to_string()),
+            };
+
+            Ok(sqlite_value)
+        }
+    }
+}
+
+/// Normalizes RFC 3339 timestamps to UTC with nine fractional digits, the
+/// format of [`crate::Timestamp`]. Timestamps written with different offsets
+/// or precisions then compare chronologically. Dates (`2024-01-31`) are
+/// already canonical and returned as-is.
+fn canonical_datetime(s: &str) -> Option<String> {
+    // Cheap check for `YYYY-MM-DDTHH:MM:SS` before trying to parse
+    let bytes = s.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+
+    let datetime = chrono::DateTime::parse_from_rfc3339(s).ok()?;
+    Some(
+        datetime
+            .with_timezone(&chrono::Utc)
+            .format("%Y-%m-%dT%H:%M:%S%.9fZ")
+            .to_string(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -57,4 +96,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn canonical_datetimes() -> Result<(), anyhow::Error> {
+        let db = crate::Ecs::open_in_memory()?;
+        let extract = |json: &str| -> Result<String, rusqlite::Error> {
+            db.raw_sql()
+                .query_row("select velodb_extract_sortable_data(?1)", [json], |row| {
+                    row.get(0)
+                })
+        };
+
+        assert_eq!(
+            extract(r#""2024-01-31T23:30:00-02:00""#)?,
+            "2024-02-01T01:30:00.000000000Z"
+        );
+        assert_eq!(
+            extract(r#""2024-02-01T01:30:00.5Z""#)?,
+            "2024-02-01T01:30:00.500000000Z"
+        );
+        assert_eq!(extract(r#""2024-01-31""#)?, "2024-01-31");
+        assert_eq!(extract(r#""not a date""#)?, "not a date");
+
+        let plain: String = db.raw_sql().query_row(
+            "select velodb_extract_data(?1)",
+            [r#""2024-01-31T23:30:00-02:00""#],
+            |row| row.get(0),
+        )?;
+        assert_eq!(plain, "2024-01-31T23:30:00-02:00");
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{self as ecsdb, Component, Ecs, Entity, query};
 
@@ -18,8 +18,19 @@ pub struct LastRun(pub chrono::DateTime<chrono::Utc>);
 pub trait System: Send + Sync {
     fn name(&self) -> Cow<'static, str>;
     fn run_system(&self, app: &Ecs) -> Result<(), anyhow::Error>;
+
+    /// Whether all inputs of the system are available. Systems that can't run
+    /// are skipped by [`Ecs::run_system`].
+    fn can_run(&self, _app: &Ecs) -> bool {
+        true
+    }
 }
 
+/// A [`System`] whose parameters only read, allowing it to run concurrently
+/// with other parallel systems on a separate read-only connection. See
+/// [`Schedule::add_parallel`](crate::Schedule::add_parallel).
+pub trait ParallelSystem: System {}
+
 pub trait IntoSystem<Marker>: Sized {
     type System: System;
     fn into_system(self) -> Self::System;
@@ -48,6 +59,10 @@ impl<'a, S: System> System for &'a S {
     fn run_system(&self, app: &Ecs) -> Result<(), anyhow::Error> {
         (*self).run_system(app)
     }
+
+    fn can_run(&self, app: &Ecs) -> bool {
+        (*self).can_run(app)
+    }
 }
 
 pub type BoxedSystem = Box<dyn System>;
@@ -60,6 +75,10 @@ impl System for BoxedSystem {
     fn run_system(&self, app: &Ecs) -> Result<(), anyhow::Error> {
         System::run_system(self.as_ref(), app)
     }
+
+    fn can_run(&self, app: &Ecs) -> bool {
+        System::can_run(self.as_ref(), app)
+    }
 }
 
 #[doc(hidden)]
@@ -98,31 +117,124 @@ where
     }
 
     fn run_system(&self, app: &Ecs) -> Result<(), anyhow::Error> {
-        SystemParamFunction::run_system(&self.system, F::Params::get_param(app, &self.name()))
-            .into_result()
+        let Some(params) = F::Params::try_get_param(app, &self.name()) else {
+            warn!(system = %self.name(), "Skipping system, parameters unavailable");
+            return Ok(());
+        };
+
+        SystemParamFunction::run_system(&self.system, params).into_result()
+    }
+
+    fn can_run(&self, app: &Ecs) -> bool {
+        F::Params::try_get_param(app, &self.name()).is_some()
+    }
+}
+
+impl<Marker, F> ParallelSystem for FunctionSystem<Marker, F>
+where
+    Marker: 'static,
+    F: SystemParamFunction<Marker>,
+    F::Params: ReadOnlySystemParam,
+{
+}
+
+/// A system with exclusive access to the [`Ecs`], e.g. to register components
+/// or extensions. Run via [`Ecs::run_exclusive_system`].
+pub trait ExclusiveSystem: Send + Sync {
+    fn name(&self) -> Cow<'static, str>;
+    fn run_exclusive(&self, app: &mut Ecs) -> Result<(), anyhow::Error>;
+}
+
+pub trait IntoExclusiveSystem<Marker>: Sized {
+    type System: ExclusiveSystem;
+    fn into_exclusive_system(self) -> Self::System;
+}
+
+impl<S: ExclusiveSystem> IntoExclusiveSystem<()> for S {
+    type System = S;
+
+    fn into_exclusive_system(self) -> Self::System {
+        self
+    }
+}
+
+impl<F, Out> IntoExclusiveSystem<(Out, FunctionSystemMarker)> for F
+where
+    F: Fn(&mut Ecs) -> Out + Send + Sync + 'static,
+    Out: SystemOutput + 'static,
+{
+    type System = ExclusiveFunctionSystem<F, Out>;
+
+    fn into_exclusive_system(self) -> Self::System {
+        ExclusiveFunctionSystem {
+            system: self,
+            output: PhantomData,
+        }
+    }
+}
+
+pub struct ExclusiveFunctionSystem<F, Out>
+where
+    F: 'static,
+{
+    system: F,
+    output: PhantomData<fn() -> Out>,
+}
+
+impl<F, Out> ExclusiveSystem for ExclusiveFunctionSystem<F, Out>
+where
+    F: Fn(&mut Ecs) -> Out + Send + Sync + 'static,
+    Out: SystemOutput,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(std::any::type_name::<F>())
+    }
+
+    fn run_exclusive(&self, app: &mut Ecs) -> Result<(), anyhow::Error> {
+        (self.system)(app).into_result()
     }
 }
 
 pub trait SystemParamFunction<Marker>: Send + Sync + 'static {
     type Params: SystemParam;
+    type Output: SystemOutput;
+
+    fn run_with_output(&self, param: <Self::Params as SystemParam>::Item<'_>) -> Self::Output;
+
     fn run_system(
         &self,
         param: <Self::Params as SystemParam>::Item<'_>,
-    ) -> Result<(), anyhow::Error>;
+    ) -> Result<(), anyhow::Error> {
+        self.run_with_output(param).into_result()
+    }
 }
 
 pub trait SystemOutput {
-    fn into_result(self) -> Result<(), anyhow::Error>;
+    /// Value handed to the caller of [`Ecs::run_system_with_output`]
+    type Ok;
+
+    fn into_output(self) -> Result<Self::Ok, anyhow::Error>;
+
+    fn into_result(self) -> Result<(), anyhow::Error>
+    where
+        Self: Sized,
+    {
+        self.into_output().map(|_| ())
+    }
 }
 
 impl SystemOutput for () {
-    fn into_result(self) -> Result<(), anyhow::Error> {
+    type Ok = ();
+
+    fn into_output(self) -> Result<(), anyhow::Error> {
         Ok(())
     }
 }
 
-impl SystemOutput for Result<(), anyhow::Error> {
-    fn into_result(self) -> Result<(), anyhow::Error> {
+impl<T> SystemOutput for Result<T, anyhow::Error> {
+    type Ok = T;
+
+    fn into_output(self) -> Result<T, anyhow::Error> {
         self
     }
 }
@@ -133,8 +245,10 @@ where
     Out: SystemOutput,
 {
     type Params = ();
-    fn run_system(&self, _app: ()) -> Result<(), anyhow::Error> {
-        self().into_result()
+    type Output = Out;
+
+    fn run_with_output(&self, _app: ()) -> Out {
+        self()
     }
 }
 
@@ -152,21 +266,28 @@ macro_rules! impl_system_function {
             Out: SystemOutput,
         {
             type Params = ($($param,)*);
+            type Output = Out;
 
             #[allow(non_snake_case)]
             #[allow(clippy::too_many_arguments)]
-            fn run_system(&self, p: SystemParamItem<($($param,)*)>) -> Result<(), anyhow::Error> {
+            fn run_with_output(&self, p: SystemParamItem<($($param,)*)>) -> Out {
                 let ($($param,)*) = p;
-                (&self)( $($param),*).into_result()
+                (&self)( $($param),*)
             }
         }
 
+        impl<$($param: ReadOnlySystemParam,)*> ReadOnlySystemParam for ($($param,)*) {}
+
         impl<$($param: SystemParam,)*> SystemParam for ($($param,)*) {
             type Item<'world> = ($($param::Item<'world>,)*);
 
             fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world> {
                 ($($param::get_param(world, system),)*)
             }
+
+            fn try_get_param<'world>(world: &'world Ecs, system: &str) -> Option<Self::Item<'world>> {
+                Some(($($param::try_get_param(world, system)?,)*))
+            }
         }
     };
 }
@@ -182,14 +303,38 @@ impl_system_function!(P1, P2, P3, P4, P5, P6, P7);
 pub trait SystemParam: Sized {
     type Item<'world>: SystemParam;
     fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world>;
+
+    /// Like [`Self::get_param`], but returns `None` instead of panicking if
+    /// the parameter isn't available, e.g. a missing [`Res`](crate::Res).
+    /// Systems with unavailable parameters are skipped.
+    fn try_get_param<'world>(world: &'world Ecs, system: &str) -> Option<Self::Item<'world>> {
+        Some(Self::get_param(world, system))
+    }
+}
+
+/// Yields `None` instead of skipping the system if `P` is unavailable
+impl<P: SystemParam> SystemParam for Option<P> {
+    type Item<'world> = Option<P::Item<'world>>;
+
+    fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world> {
+        P::try_get_param(world, system)
+    }
 }
 
+impl<P: ReadOnlySystemParam> ReadOnlySystemParam for Option<P> {}
+
+/// Marker for [`SystemParam`]s that don't need write access to the database.
+/// `&Ecs` and [`SystemEntity`] are deliberately excluded.
+pub trait ReadOnlySystemParam: SystemParam {}
+
 impl SystemParam for () {
     type Item<'world> = ();
 
     fn get_param<'world>(_world: &'world Ecs, _system: &str) -> Self::Item<'world> {}
 }
 
+impl ReadOnlySystemParam for () {}
+
 impl Ecs {
     #[deprecated(note = "use Ecs::run_system")]
     pub fn run<Marker, F: IntoSystem<Marker>>(&self, system: F) -> Result<(), anyhow::Error> {
@@ -210,20 +355,81 @@ impl Ecs {
 
         let system_entity = self.get_or_create_system_entity(&system.name());
 
+        if !system.can_run(self) {
+            warn!("Skipping, parameters unavailable");
+            return Ok(());
+        }
+
         info!("Running");
 
+        let started_at = chrono::Utc::now();
         if let Err(e) = system.run_system(self) {
             error!(?e);
             return Err(e);
         }
 
-        system_entity.attach(LastRun(chrono::Utc::now()));
+        system_entity.attach(LastRun(started_at));
 
         debug!(elapsed_ms = started.elapsed().as_millis(), "Finished",);
 
         Ok(())
     }
 
+    /// Like [`Self::run_system`] for function systems, but hands the value
+    /// returned by the system to the caller, e.g. `T` for a system returning
+    /// `Result<T, anyhow::Error>`.
+    #[instrument(level = "info", name = "run_system", skip_all, fields(name = std::any::type_name::<F>()))]
+    pub fn run_system_with_output<Marker, F, T>(&self, system: F) -> Result<T, anyhow::Error>
+    where
+        F: SystemParamFunction<Marker>,
+        F::Output: SystemOutput<Ok = T>,
+    {
+        let name = std::any::type_name::<F>();
+        let system_entity = self.get_or_create_system_entity(name);
+
+        info!("Running");
+
+        let Some(params) = F::Params::try_get_param(self, name) else {
+            anyhow::bail!("Parameters of system {name} unavailable");
+        };
+
+        let started_at = chrono::Utc::now();
+        let output = match system.run_with_output(params).into_output() {
+            Ok(output) => output,
+            Err(e) => {
+                error!(?e);
+                return Err(e);
+            }
+        };
+
+        system_entity.attach(LastRun(started_at));
+
+        Ok(output)
+    }
+
+    /// Runs an [`ExclusiveSystem`], e.g. a function taking `&mut Ecs`. No other
+    /// parameters are available to exclusive systems.
+    #[instrument(level = "info", name = "run_system", skip_all, fields(name = std::any::type_name::<S>()))]
+    pub fn run_exclusive_system<Marker, S: IntoExclusiveSystem<Marker>>(
+        &mut self,
+        system: S,
+    ) -> Result<(), anyhow::Error> {
+        let system = system.into_exclusive_system();
+
+        info!("Running");
+
+        let started_at = chrono::Utc::now();
+        if let Err(e) = system.run_exclusive(self) {
+            error!(?e);
+            return Err(e);
+        }
+
+        self.get_or_create_system_entity(&system.name())
+            .attach(LastRun(started_at));
+
+        Ok(())
+    }
+
     pub fn system_entities<'a>(&'a self) -> impl Iterator<Item = (String, Entity<'a>)> {
         self.query::<(Entity, Name), ()>()
             .map(|(e, name)| (name.0, e))
@@ -260,11 +466,15 @@ impl SystemParam for SystemEntity<'_> {
     type Item<'world> = SystemEntity<'world>;
 
     fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world> {
-        let Some(entity) = world.system_entity(system) else {
+        let Some(entity) = Self::try_get_param(world, system) else {
             panic!("Couldn't find SystemEntity for {system:?}. This should not happen.");
         };
 
-        SystemEntity(entity)
+        entity
+    }
+
+    fn try_get_param<'world>(world: &'world Ecs, system: &str) -> Option<Self::Item<'world>> {
+        world.system_entity(system).map(SystemEntity)
     }
 }
 
@@ -287,6 +497,8 @@ where
     }
 }
 
+impl<D, F> ReadOnlySystemParam for query::Query<'_, D, F> where F: query::QueryFilter + Default {}
+
 impl SystemParam for LastRun {
     type Item<'world> = LastRun;
 
@@ -300,6 +512,39 @@ impl SystemParam for LastRun {
     }
 }
 
+impl ReadOnlySystemParam for LastRun {}
+
+/// Timing of the current run, relative to the previous run recorded in
+/// [`LastRun`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub now: chrono::DateTime<chrono::Utc>,
+    /// `None` on the first run
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    /// Time since `last_run`, zero on the first run
+    pub delta: chrono::Duration,
+}
+
+impl SystemParam for Time {
+    type Item<'world> = Time;
+
+    fn get_param<'world>(world: &'world Ecs, system: &str) -> Self::Item<'world> {
+        let now = chrono::Utc::now();
+        let last_run = world
+            .system_entity(system)
+            .and_then(|entity| entity.component::<LastRun>())
+            .map(|LastRun(last_run)| last_run);
+
+        Time {
+            now,
+            last_run,
+            delta: last_run.map_or(chrono::Duration::zero(), |last_run| now - last_run),
+        }
+    }
+}
+
+impl ReadOnlySystemParam for Time {}
+
 impl AsRef<chrono::DateTime<chrono::Utc>> for LastRun {
     fn as_ref(&self) -> &chrono::DateTime<chrono::Utc> {
         &self.0
@@ -323,7 +568,7 @@ mod tests {
     use std::marker::PhantomData;
 
     use crate::query::With;
-    use crate::{Ecs, Entity, IntoSystem, System, SystemEntity, query};
+    use crate::{Ecs, Entity, IntoSystem, Res, System, SystemEntity, Time, query};
 
     #[test]
     fn run_system() {
@@ -427,6 +672,117 @@ mod tests {
         assert!(a.component::<Seen>().is_none());
     }
 
+    #[test]
+    fn run_system_with_output() {
+        let db = Ecs::open_in_memory().unwrap();
+        fn system(query: query::Query<Entity, With<A>>) -> Result<usize, anyhow::Error> {
+            let mut processed = 0;
+            for entity in query.try_iter()? {
+                entity.attach(Seen);
+                processed += 1;
+            }
+            Ok(processed)
+        }
+
+        db.new_entity().attach(A);
+        db.new_entity().attach((A, B));
+        db.new_entity().attach(B);
+
+        assert_eq!(db.run_system_with_output(system).unwrap(), 2);
+        db.run_system_with_output(|| ()).unwrap();
+
+        // The plain path discards the output
+        db.run_system(system).unwrap();
+    }
+
+    #[test]
+    fn time_param() {
+        let db = Ecs::open_in_memory().unwrap();
+        fn system(time: Time) -> Result<Time, anyhow::Error> {
+            Ok(time)
+        }
+
+        let first = db.run_system_with_output(system).unwrap();
+        assert_eq!(first.last_run, None);
+        assert_eq!(first.delta, chrono::Duration::zero());
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        let second = db.run_system_with_output(system).unwrap();
+        assert!(second.last_run.unwrap() >= first.now);
+        assert!(second.delta >= chrono::Duration::milliseconds(5));
+    }
+
+    #[test]
+    fn last_run_is_run_start() {
+        let db = Ecs::open_in_memory().unwrap();
+        fn slow() {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        let before = chrono::Utc::now();
+        db.run_system(slow).unwrap();
+        let after = chrono::Utc::now();
+
+        let crate::LastRun(last_run) = db
+            .system_entity(&IntoSystem::into_system(slow).name())
+            .unwrap()
+            .component()
+            .unwrap();
+        assert!(last_run >= before);
+        assert!(after - last_run >= chrono::Duration::milliseconds(20));
+    }
+
+    #[test]
+    fn skip_system_with_missing_resource() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Missing(u32);
+
+        let db = Ecs::open_in_memory().unwrap();
+        fn system(ecs: &Ecs, Res(missing): Res<Missing>) {
+            ecs.new_entity()
+                .attach((Seen, A))
+                .attach(Missing(missing.0));
+        }
+
+        db.run_system(system).unwrap();
+        assert!(db.query::<Seen, ()>().next().is_none());
+        assert!(db.run_system_with_output(system).is_err());
+
+        // Optional params don't skip the system
+        db.run_system(|ecs: &Ecs, missing: Option<Res<Missing>>| {
+            assert!(missing.is_none());
+            ecs.new_entity().attach((Seen, B));
+        })
+        .unwrap();
+        assert!(db.query::<Seen, B>().next().is_some());
+
+        db.attach_resource(Missing(42));
+        db.run_system(system).unwrap();
+        assert_eq!(db.query::<Missing, A>().next().unwrap().0, 42);
+    }
+
+    #[test]
+    fn run_exclusive_system() {
+        #[derive(Debug, Serialize, Deserialize, Component)]
+        struct Config(u32);
+
+        let mut db = Ecs::open_in_memory().unwrap();
+        fn system(ecs: &mut Ecs) -> Result<(), anyhow::Error> {
+            ecs.try_register_component::<Config>()?;
+            ecs.attach_resource(Config(42));
+            Ok(())
+        }
+
+        db.run_exclusive_system(system).unwrap();
+
+        assert_eq!(db.resource::<Config>().unwrap().0, 42);
+        assert!(
+            db.system_entity(std::any::type_name_of_val(&system))
+                .is_some()
+        );
+    }
+
     #[test]
     fn run_ecs_param() {
         let db = Ecs::open_in_memory().unwrap();
@@ -0,0 +1,30 @@
+use std::path::{Path, PathBuf};
+
+/// Database file in the temp directory, removed together with its `-wal` and
+/// `-shm` files on drop. Declare it before the [`crate::Ecs`] using it, so
+/// that it's dropped last.
+pub(crate) struct TempDb(PathBuf);
+
+impl TempDb {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("ecsdb-{name}-{}.db", std::process::id())))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDb {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", self.0.display()));
+        }
+    }
+}
@@ -89,6 +89,261 @@ impl Command for Components {
     }
 }
 
+#[derive(Debug)]
+pub struct Stats;
+
+impl Command for Stats {
+    fn name(&self) -> &'static str {
+        ".stats"
+    }
+
+    fn execute(&self, db: &Ecs, input: &str) -> CommandResult {
+        if input.trim() != self.name() {
+            println!("Ignoring arguments '{input}'");
+        }
+
+        let stats = db.component_stats()?;
+
+        let resources: i64 = db
+            .raw_sql()
+            .query_row(
+                "select count(*) from components where entity = ?1",
+                [WORLD_ENTITY],
+                |row| row.get(0),
+            )
+            .map_err(ecsdb::Error::from)?;
+
+        let db_size: i64 = db
+            .raw_sql()
+            .query_row(
+                "select page_count * page_size from pragma_page_count, pragma_page_size",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(ecsdb::Error::from)?;
+
+        let mut components = stats.components.into_iter().collect::<Vec<_>>();
+        components.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+        for (component, count) in components {
+            println!("{count:>8} | {component}");
+        }
+
+        println!();
+        println!("Entities:  {}", stats.entities);
+        println!("Resources: {resources}");
+        println!("Size:      {db_size} bytes");
+
+        Ok(())
+    }
+}
+
+/// Exports every row of `components` as a JSON array of
+/// `{"entity": .., "components": {name: data, ..}}` objects. Data that isn't
+/// stored as JSON text goes into `"raw": {name: data, ..}` instead, keeping
+/// its SQLite type: integers and reals as JSON numbers, blobs as `X'..'`
+/// literals.
+#[derive(Debug)]
+pub struct Dump;
+
+impl Dump {
+    fn dump(db: &Ecs) -> Result<serde_json::Value, anyhow::Error> {
+        use ecsdb::rusqlite::types::ValueRef;
+
+        type Components = serde_json::Map<String, serde_json::Value>;
+
+        let mut stmt = db
+            .raw_sql()
+            .prepare("select entity, component, data from components order by entity, component")?;
+        let mut rows = stmt.query([])?;
+
+        let mut entities: Vec<(EntityId, Components, Components)> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let entity: EntityId = row.get("entity")?;
+            let component: String = row.get("component")?;
+
+            if entities.last().is_none_or(|(eid, _, _)| *eid != entity) {
+                entities.push((entity, Components::new(), Components::new()));
+            }
+            let (_, components, raw) = entities.last_mut().unwrap();
+
+            match row.get_ref("data")? {
+                ValueRef::Null => {
+                    components.insert(component, serde_json::Value::Null);
+                }
+                ValueRef::Text(text) => {
+                    components.insert(component, serde_json::from_slice(text)?);
+                }
+                ValueRef::Integer(n) => {
+                    raw.insert(component, n.into());
+                }
+                ValueRef::Real(r) => {
+                    raw.insert(component, r.into());
+                }
+                ValueRef::Blob(blob) => {
+                    raw.insert(component, blob_literal(blob).into());
+                }
+            }
+        }
+
+        Ok(entities
+            .into_iter()
+            .map(|(entity, components, raw)| {
+                let mut dumped = serde_json::json!({"entity": entity, "components": components});
+                if !raw.is_empty() {
+                    dumped["raw"] = raw.into();
+                }
+                dumped
+            })
+            .collect())
+    }
+}
+
+fn blob_literal(blob: &[u8]) -> String {
+    let hex = blob.iter().map(|b| format!("{b:02X}")).collect::<String>();
+    format!("X'{hex}'")
+}
+
+fn parse_blob_literal(literal: &str) -> Option<Vec<u8>> {
+    let hex = literal
+        .strip_prefix("X'")
+        .or_else(|| literal.strip_prefix("x'"))?
+        .strip_suffix('\'')?;
+
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl Command for Dump {
+    fn name(&self) -> &'static str {
+        ".dump"
+    }
+
+    fn execute(&self, db: &Ecs, input: &str) -> CommandResult {
+        let path = input.trim_start_matches(self.name()).trim();
+        let dump = Self::dump(db).map_err(CommandError::CommandFailed)?;
+
+        if path.is_empty() {
+            println!("{dump:#}");
+        } else {
+            let file =
+                std::fs::File::create(path).map_err(|e| CommandError::CommandFailed(e.into()))?;
+            serde_json::to_writer_pretty(file, &dump)
+                .map_err(|e| CommandError::CommandFailed(e.into()))?;
+            println!("Dumped to {path}");
+        }
+
+        Ok(())
+    }
+}
+
+/// Imports a file written by [`Dump`]. Existing components with the same
+/// entity and name are overwritten.
+#[derive(Debug)]
+pub struct Load;
+
+impl Load {
+    fn load(db: &Ecs, dump: &serde_json::Value) -> Result<usize, anyhow::Error> {
+        use ecsdb::rusqlite::types::Value;
+
+        let entities = dump
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("expected a JSON array of entities"))?;
+
+        let tx = db.raw_sql().unchecked_transaction()?;
+        let mut stmt = tx.prepare(
+            "insert into components (entity, component, data) values (?1, ?2, ?3)
+             on conflict (entity, component) do update set data = excluded.data",
+        )?;
+
+        let mut count = 0;
+        for entity in entities {
+            let eid = entity["entity"]
+                .as_i64()
+                .ok_or_else(|| anyhow::anyhow!("missing entity id in {entity}"))?;
+            let json = entity["components"]
+                .as_object()
+                .ok_or_else(|| anyhow::anyhow!("missing components in {entity}"))?;
+
+            let mut components = json
+                .iter()
+                .map(|(name, data)| {
+                    let data = match data {
+                        serde_json::Value::Null => Value::Null,
+                        other => Value::Text(other.to_string()),
+                    };
+                    (name, data)
+                })
+                .collect::<Vec<_>>();
+
+            for (name, data) in entity["raw"].as_object().into_iter().flatten() {
+                let data = match data {
+                    serde_json::Value::Number(n) if n.is_i64() => {
+                        Value::Integer(n.as_i64().unwrap())
+                    }
+                    serde_json::Value::Number(n) if n.is_f64() => Value::Real(n.as_f64().unwrap()),
+                    serde_json::Value::String(s) => {
+                        Value::Blob(parse_blob_literal(s).ok_or_else(|| {
+                            anyhow::anyhow!("invalid blob literal for {name}: {s}")
+                        })?)
+                    }
+                    other => anyhow::bail!("invalid raw data for {name}: {other}"),
+                };
+                components.push((name, data));
+            }
+
+            // Timestamps get touched by triggers when other components are
+            // inserted. Write them last so the dumped values win.
+            components.sort_by_key(|(name, _)| match name.as_str() {
+                "ecsdb::CreatedAt" => 1,
+                "ecsdb::LastUpdated" => 2,
+                _ => 0,
+            });
+
+            for (component, data) in components {
+                stmt.execute((eid, component, data))?;
+                count += 1;
+            }
+        }
+
+        drop(stmt);
+        tx.commit()?;
+
+        Ok(count)
+    }
+}
+
+impl Command for Load {
+    fn name(&self) -> &'static str {
+        ".load"
+    }
+
+    fn execute(&self, db: &Ecs, input: &str) -> CommandResult {
+        let path = input.trim_start_matches(self.name()).trim();
+        if path.is_empty() {
+            println!("Usage: .load <path>");
+            return Ok(());
+        }
+
+        let run = || -> Result<usize, anyhow::Error> {
+            let file = std::fs::File::open(path)?;
+            let dump = serde_json::from_reader(std::io::BufReader::new(file))?;
+            Self::load(db, &dump)
+        };
+
+        let count = run().map_err(CommandError::CommandFailed)?;
+        println!("Loaded {count} components from {path}");
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct RegisteredSystems;
 
@@ -208,3 +463,86 @@ impl Command for QueryCommand {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let db = Ecs::open_in_memory().unwrap();
+        db.new_entity().attach(ecsdb::system::Name("foo".into()));
+        db.new_entity().attach(ecsdb::system::Name("bar".into()));
+
+        Stats.execute(&db, ".stats").unwrap();
+    }
+
+    #[test]
+    fn dump_and_load() {
+        let db = Ecs::open_in_memory().unwrap();
+        let foo = db.new_entity().attach(ecsdb::system::Name("foo".into()));
+        let bar = db.new_entity().attach(ecsdb::system::Name("bar".into()));
+
+        let path = std::env::temp_dir().join(format!("ecsdb-dump-{}.json", std::process::id()));
+        Dump.execute(&db, &format!(".dump {}", path.display()))
+            .unwrap();
+
+        let dump: serde_json::Value =
+            serde_json::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+        let ids = dump
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["entity"].as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![foo.id(), bar.id()]);
+
+        let restored = Ecs::open_in_memory().unwrap();
+        Load.execute(&restored, &format!(".load {}", path.display()))
+            .unwrap();
+        assert_eq!(
+            restored.entity(bar.id()).component::<ecsdb::system::Name>(),
+            Some(ecsdb::system::Name("bar".into()))
+        );
+        assert_eq!(restored.entity(foo.id()).created_at(), foo.created_at());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn dump_keeps_sqlite_types() {
+        let db = Ecs::open_in_memory().unwrap();
+        let e = db.new_entity().attach(ecsdb::system::Name("foo".into()));
+        db.raw_sql()
+            .execute(
+                "insert into components (entity, component, data) values (?1, 'Int', 7), (?1, 'Real', 2.0), (?1, 'Blob', x'00ff10')",
+                [e.id()],
+            )
+            .unwrap();
+
+        let dump = Dump::dump(&db).unwrap();
+        assert_eq!(
+            dump[0]["raw"],
+            serde_json::json!({"Blob": "X'00FF10'", "Int": 7, "Real": 2.0})
+        );
+
+        let dump = serde_json::from_str(&dump.to_string()).unwrap();
+        let restored = Ecs::open_in_memory().unwrap();
+        Load::load(&restored, &dump).unwrap();
+
+        let typed = |component: &str| -> (String, String) {
+            restored
+                .raw_sql()
+                .query_row(
+                    "select typeof(data), quote(data) from components where entity = ?1 and component = ?2",
+                    (e.id(), component),
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap()
+        };
+        assert_eq!(typed("Int"), ("integer".into(), "7".into()));
+        assert_eq!(typed("Real"), ("real".into(), "2.0".into()));
+        assert_eq!(typed("Blob"), ("blob".into(), "X'00FF10'".into()));
+        assert_eq!(typed(ecsdb::system::Name::NAME).0, "text");
+    }
+}
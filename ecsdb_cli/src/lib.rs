@@ -6,6 +6,9 @@ pub use pipeline::Pipeline;
 
 pub const COMMANDS: Commands = &[
     &commands::Info,
+    &commands::Stats,
+    &commands::Dump,
+    &commands::Load,
     &commands::SqliteExecute,
     &commands::Entities,
     &commands::Components,
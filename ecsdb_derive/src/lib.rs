@@ -161,6 +161,8 @@ enum Storage {
     #[default]
     Json,
     Blob,
+    ArcBlob,
+    OwnedBlob,
     Null,
 }
 
@@ -182,9 +184,9 @@ fn impl_derive_component(ast: &syn::DeriveInput) -> TokenStream {
 
     let mut attributes = extract_attributes(&ast.attrs);
 
-    if let Data::Struct(ref struc) = ast.data
-        && matches!(struc.fields, Fields::Unit)
-    {
+    let is_unit_struct =
+        matches!(ast.data, Data::Struct(ref struc) if matches!(struc.fields, Fields::Unit));
+    if is_unit_struct {
         attributes.storage = Storage::Null;
     }
 
@@ -196,14 +198,39 @@ fn impl_derive_component(ast: &syn::DeriveInput) -> TokenStream {
     let storage = match attributes.storage {
         Storage::Json => quote!(ecsdb::component::JsonStorage),
         Storage::Blob => quote!(ecsdb::component::BlobStorage),
+        Storage::ArcBlob => quote!(ecsdb::component::ArcBlobStorage),
+        Storage::OwnedBlob => quote!(ecsdb::component::OwnedBlobStorage),
         Storage::Null => quote!(ecsdb::component::NullStorage),
     };
 
+    // Nothing is stored, so the value is rebuilt on read: unit structs are
+    // their only value, anything else (fieldless enums, structs with
+    // skipped fields) has to provide it via `Default`
+    let null_component = match attributes.storage {
+        Storage::Null if is_unit_struct => quote! {
+            impl ecsdb::component::NullComponent for #name {
+                fn from_null() -> Self {
+                    #name
+                }
+            }
+        },
+        Storage::Null => quote! {
+            impl ecsdb::component::NullComponent for #name {
+                fn from_null() -> Self {
+                    <Self as ::std::default::Default>::default()
+                }
+            }
+        },
+        _ => quote!(),
+    };
+
     quote! {
         impl ecsdb::component::Component for #name {
             type Storage = #storage;
             const NAME: &'static str = #component_name;
         }
+
+        #null_component
     }
     .into()
 }
@@ -264,6 +291,13 @@ fn derive_bundle_for_struct(name: syn::Ident, struc: syn::DataStruct) -> TokenSt
                 #(<#types as ecsdb::component::BundleComponent>::NAME),*
             ];
 
+            fn component_types() -> Vec<ecsdb::component::ComponentType> {
+                let types: Vec<Option<ecsdb::component::ComponentType>> = vec![
+                    #(<#types as ecsdb::component::BundleComponent>::component_type()),*
+                ];
+                types.into_iter().flatten().collect()
+            }
+
             fn to_rusqlite<'a>(
                 &'a self,
             ) -> Result<ecsdb::component::BundleData<'a>, ecsdb::component::StorageError> {
@@ -280,6 +314,24 @@ fn derive_bundle_for_struct(name: syn::Ident, struc: syn::DataStruct) -> TokenSt
                     )*
                 ])
             }
+
+            fn from_rusqlite<'a>(
+                components: ecsdb::component::BundleDataRef<'a>,
+            ) -> Result<Option<Self>, ecsdb::component::StorageError> {
+                Ok(Some(Self {
+                    #(
+                        #field_names: match <#types as ecsdb::component::BundleComponent>::from_bundle_data(
+                            ecsdb::component::find_component(
+                                components,
+                                <#types as ecsdb::component::BundleComponent>::NAME,
+                            ),
+                        )? {
+                            Some(c) => c,
+                            None => return Ok(None),
+                        },
+                    )*
+                }))
+            }
         }
 
         impl ecsdb::component::NonEmptyBundle for #name {}
@@ -304,6 +356,9 @@ fn extract_attributes(attrs: &[Attribute]) -> Attributes {
                         match lit.value().as_str() {
                             "json" => attributes.storage = Storage::Json,
                             "blob" => attributes.storage = Storage::Blob,
+                            "arc_blob" => attributes.storage = Storage::ArcBlob,
+                            "owned_blob" => attributes.storage = Storage::OwnedBlob,
+                            "null" => attributes.storage = Storage::Null,
                             other => panic!("storage {other} not supported"),
                         }
                     }
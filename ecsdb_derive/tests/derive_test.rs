@@ -46,6 +46,10 @@ pub mod component {
     pub trait NonEmptyBundle: Bundle {}
 
     pub trait ComponentWrite {}
+
+    pub trait NullComponent: Sized {
+        fn from_null() -> Self;
+    }
 }
 
 pub mod rusqlite {
@@ -94,6 +98,41 @@ fn test_component() {
     struct Unit;
 }
 
+#[test]
+fn derive_null_storage() {
+    use component::NullComponent;
+    use std::any::TypeId;
+
+    // Fields not backed by the database, e.g. `#[serde(skip)]`ped ones
+    #[derive(Debug, Default, PartialEq, Component)]
+    #[component(storage = "null")]
+    struct Cached {
+        hits: u32,
+        label: Option<String>,
+    }
+
+    assert_eq!(
+        TypeId::of::<<Cached as Component>::Storage>(),
+        TypeId::of::<component::NullStorage>()
+    );
+    assert_eq!(Cached::from_null(), Cached::default());
+
+    #[derive(Debug, Default, PartialEq, Component)]
+    #[component(storage = "null")]
+    enum State {
+        #[default]
+        Idle,
+        _Busy,
+    }
+
+    assert_eq!(State::from_null(), State::Idle);
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Marker;
+
+    assert_eq!(Marker::from_null(), Marker);
+}
+
 #[test]
 fn derive_name_attribute() {
     #[derive(Component)]
@@ -113,7 +152,6 @@ fn test_resource_standalone() {
     assert_eq!(Foo::component_name(), "derive_test::Foo".to_string());
 }
 
-
 // #[test]
 // fn derive_bundle_struct() {
 //     #[derive(Debug, Component)]